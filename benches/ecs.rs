@@ -0,0 +1,131 @@
+use knock_the_enemy::{ComponentBase, EntityManager};
+use std::any::Any;
+use std::time::{Duration, Instant};
+
+// Compares the old per-entity component vectors against the sparse-set storage, on the kind of loop every system runs:
+// find the entities with two components, read one and write the other. Run with cargo bench --bench ecs [-- COUNT]
+// Uses its own components so it doesn't need a window or GPU
+
+const POSITION_ID: u32 = 1000;
+const VELOCITY_ID: u32 = 1001;
+const FRAMES: u32 = 100;
+
+struct BenchPosition{
+    x: f32,
+    y: f32,
+    id: u32
+}
+
+struct BenchVelocity{
+    x: f32,
+    y: f32,
+    id: u32
+}
+
+impl ComponentBase for BenchPosition{
+    fn get_id(&self) -> u32{
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl ComponentBase for BenchVelocity{
+    fn get_id(&self) -> u32{
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+// Every third entity has no velocity, so both versions have to filter
+fn make_components(i: usize) -> Vec<Box<dyn ComponentBase>>{
+    let mut components = Vec::<Box<dyn ComponentBase>>::new();
+    components.push(Box::new(BenchPosition { x: i as f32, y: 0.0, id: POSITION_ID }));
+    if i % 3 != 0{
+        components.push(Box::new(BenchVelocity { x: 1.0, y: -0.5, id: VELOCITY_ID }));
+    }
+    components
+}
+
+// The way systems found components before: scan the entity's components for the id, then downcast
+fn old_storage_frame(entities: &mut Vec<Vec<Box<dyn ComponentBase>>>) -> f32{
+    let mut sum = 0.0;
+    for components in entities.iter_mut(){
+        let velocity = match components.iter().position(|x| x.get_id() == VELOCITY_ID){
+            Some(index) => {
+                let velocity = components[index].as_any().downcast_ref::<BenchVelocity>().unwrap();
+                (velocity.x, velocity.y)
+            },
+            None => continue,
+        };
+        let index = match components.iter().position(|x| x.get_id() == POSITION_ID){
+            Some(v) => v,
+            None => continue,
+        };
+        let position = components[index].as_any_mut().downcast_mut::<BenchPosition>().unwrap();
+        position.x += velocity.0 * 0.016;
+        position.y += velocity.1 * 0.016;
+        sum += position.x;
+    }
+    sum
+}
+
+fn query_frame(entity_manager: &EntityManager) -> f32{
+    let mut sum = 0.0;
+    for (_, (position, velocity)) in entity_manager.query::<(&mut BenchPosition, &BenchVelocity)>().iter(){
+        position.x += velocity.x * 0.016;
+        position.y += velocity.y * 0.016;
+        sum += position.x;
+    }
+    sum
+}
+
+fn time_frames(mut frame: impl FnMut() -> f32) -> Duration{
+    // One untimed frame so both start warm
+    let mut sum = frame();
+    let start = Instant::now();
+    for _ in 0..FRAMES{
+        sum += frame();
+    }
+    let elapsed = start.elapsed();
+    // Keep the result alive so the loops aren't optimised away
+    std::hint::black_box(sum);
+    elapsed
+}
+
+fn main(){
+    // cargo bench passes --bench, so take the first argument that is a number
+    let entity_count = std::env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(10000);
+
+    let mut old_entities = Vec::<Vec<Box<dyn ComponentBase>>>::new();
+    let mut entity_manager = EntityManager::new();
+    entity_manager.register_component::<BenchPosition>();
+    entity_manager.register_component::<BenchVelocity>();
+    for i in 0..entity_count{
+        old_entities.push(make_components(i));
+        entity_manager.create_entity(make_components(i), Vec::new());
+    }
+
+    let old_time = time_frames(|| old_storage_frame(&mut old_entities));
+    let query_time = time_frames(|| query_frame(&entity_manager));
+
+    let per_frame = |time: Duration| time.as_secs_f64() * 1000.0 / FRAMES as f64;
+    let result = format!("ECS benchmark, {} entities, {} frames: component vectors {:.3}ms/frame, sparse-set query {:.3}ms/frame ({:.1}x)",
+        entity_count, FRAMES, per_frame(old_time), per_frame(query_time), old_time.as_secs_f64() / query_time.as_secs_f64().max(1e-9));
+    println!("{}", result);
+}
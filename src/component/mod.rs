@@ -6,8 +6,11 @@ pub mod player_movement_component;
 use std::any::Any;
 
 trait Component {}
-pub trait ComponentBase{
+// Send + Sync since systems running in parallel share the component storages
+pub trait ComponentBase: Send + Sync{
     fn get_id(&self) -> u32;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut Any;
+    // Lets a boxed component be moved into its typed storage
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
\ No newline at end of file
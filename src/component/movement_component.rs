@@ -1,4 +1,7 @@
 use crate::{ComponentBase};
+use crate::scene::ComponentRegistry;
+use crate::scene::parser::{ComponentNode, Value};
+use crate::scene::args::number_arg;
 use std::any::Any;
 
 pub const ID: u32 = 5;
@@ -18,6 +21,9 @@ impl ComponentBase for MovementComponent{
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 
 impl MovementComponent{
@@ -31,4 +37,14 @@ impl MovementComponent{
     pub fn get_component_id() -> u32{
         ID
     }
+
+    // enemy_movement(speed)
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.register_with_saver("MovementComponent", ID, "enemy_movement", |node, _| {
+            Ok(Box::new(MovementComponent::new(number_arg(node, 0)?)))
+        }, |component, _| {
+            let movement = component.as_any().downcast_ref::<MovementComponent>()?;
+            Some(ComponentNode::new("enemy_movement", vec!(Value::Number(movement.speed))))
+        })
+    }
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
 use crate::{ComponentBase, Camera};
+use crate::scene::ComponentRegistry;
+use crate::scene::parser::{ComponentNode, Value};
+use crate::scene::args::number_arg;
 use std::any::Any;
 
 pub const ID: u32 = 6;
@@ -21,6 +24,9 @@ impl ComponentBase for PlayerMovementComponent{
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 
 impl PlayerMovementComponent{
@@ -37,4 +43,14 @@ impl PlayerMovementComponent{
     pub fn get_component_id() -> u32{
         ID
     }
+
+    // player_movement(speed)
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.register_with_saver("PlayerMovementComponent", ID, "player_movement", |node, _| {
+            Ok(Box::new(PlayerMovementComponent::new(number_arg(node, 0)?)))
+        }, |component, _| {
+            let movement = component.as_any().downcast_ref::<PlayerMovementComponent>()?;
+            Some(ComponentNode::new("player_movement", vec!(Value::Number(movement.speed))))
+        })
+    }
 }
\ No newline at end of file
@@ -1,105 +1,56 @@
-use crate::{ComponentBase, Rc, RefCell};
-use std::ops::DerefMut;
-
-fn downcast<T: ComponentBase + 'static>(this: &dyn ComponentBase) -> Option<&T> {
-    this.as_any().downcast_ref()
-}
-
-fn downcast_mut<T: ComponentBase + 'static>(this: &mut dyn ComponentBase) -> Option<&mut T> {
-    this.as_any_mut().downcast_mut()
-}
+use crate::{Arc, EntityHandle};
+use std::collections::HashSet;
 
+// Components live in the EntityManager's storages, use EntityManager::get_component or a query to get at them
 pub struct Entity{
-    pub components: Vec<Box<dyn ComponentBase>>,
-    pub uniforms: Vec::<Rc::<wgpu::BindGroup>>,
-    pub id: usize,
+    pub uniforms: Vec::<Arc::<wgpu::BindGroup>>,
+    pub handle: EntityHandle,
+    // Names don't have to be unique, find_by_name returns the first match
+    pub name: Option<String>,
+    pub tags: HashSet<String>,
 }
 
 impl Entity{
-    pub fn new(components: Vec<Box<dyn ComponentBase>>, id: usize) -> Self{
-        let uniforms = Vec::<Rc::<wgpu::BindGroup>>::new();
+    pub fn new(handle: EntityHandle) -> Self{
+        let uniforms = Vec::<Arc::<wgpu::BindGroup>>::new();
 
         Self{
-            components,
             uniforms,
-            id
+            handle,
+            name: None,
+            tags: HashSet::<String>::new(),
         }
     }
 
-    pub fn add_component(&mut self, component: Box<dyn ComponentBase>){
-        self.components.push(component);
+    pub fn set_name(&mut self, name: &str){
+        self.name = Some(name.to_string());
     }
 
-    pub fn try_remove_component(&mut self, component_id: u32) -> Result<(), &str>{
-        let index = self.components.iter().position(|x| x.get_id() == component_id);// Compare ID to find the component
-        let index = match index{
-            Some(v) => v,
-            None => {return Err("Component not in entity");} 
-        };
-        self.components.remove(index);
-        Ok(())
+    pub fn get_name(&self) -> Option<&str>{
+        self.name.as_deref()
     }
 
-    pub fn try_find_component(&self, component_id: u32) -> Result<&Box<dyn ComponentBase>, &str>{
-        let index = self.components.iter().position(|x| x.get_id() == component_id);// Compare ID to find the component
-        let index = match index{
-            Some(v) => v,
-            None => {return Err("Component not in entity");} 
-        };
-        Ok(&self.components[index])
+    pub fn add_tag(&mut self, tag: &str){
+        self.tags.insert(tag.to_string());
     }
 
-    pub fn try_find_component_mut(&mut self, component_id: u32) -> Result<&Box<dyn ComponentBase>, &str>{
-        let index = self.components.iter().position(|x| x.get_id() == component_id);// Compare ID to find the component
-        let index = match index{
-            Some(v) => v,
-            None => {return Err("Component not in entity");} 
-        };
-        Ok(&self.components[index])
+    pub fn remove_tag(&mut self, tag: &str) -> bool{
+        self.tags.remove(tag)
     }
 
-
-    pub fn get_index(&self, component_id: u32) -> Result<usize, &str>{
-        let index = self.components.iter().position(|x| x.get_id() == component_id);// Compare ID to find the component
-        match index{
-            Some(v) =>  Ok(v),
-            None => Err("Component not in entity")
-        }
-    }
-
-    pub fn get_component<T: ComponentBase + 'static>(&self, component_id: u32) -> Result<&T, &str>{
-        let component = self.try_find_component(component_id)?;
-        // Try and convert the rendermesh component we have (Which is currently a trait object) into a rendermesh struct so we can use it
-        if let Some(r) = downcast::<T>(&**component) {
-            return Ok(r);
-        }
-        Err("Error downcasting component result")
-    }
-
-    pub fn get_component_mut<T: ComponentBase + 'static>(&mut self, component_id: u32) -> Result<&mut T, &str>{
-        let index = match self.get_index(component_id){
-            Ok(v) => v,
-            Err(e) => return Err("Component Not In Entity"),
-        };
-
-        let mut component = self.components[index].deref_mut();
-        
-        // Try and convert the rendermesh component we have (Which is currently a trait object) into a rendermesh struct so we can use it
-        if let Some(r) = downcast_mut::<T>(&mut *component) {
-            return Ok(r);
-        }
-        Err("Error downcasting component result")
+    pub fn has_tag(&self, tag: &str) -> bool{
+        self.tags.contains(tag)
     }
 
-    pub fn add_new_uniform(&mut self, uniform: Rc<wgpu::BindGroup>){
+    pub fn add_new_uniform(&mut self, uniform: Arc<wgpu::BindGroup>){
         self.uniforms.push(uniform);
     }
 
-    pub fn set_uniforms(&mut self, uniforms: Vec::<Rc<wgpu::BindGroup>>){
+    pub fn set_uniforms(&mut self, uniforms: Vec::<Arc<wgpu::BindGroup>>){
         self.uniforms = uniforms;
     }
 
-    pub fn get_uniforms(&self) -> &Vec::<Rc<wgpu::BindGroup>>{
+    pub fn get_uniforms(&self) -> &Vec::<Arc<wgpu::BindGroup>>{
         &self.uniforms
     }
 
@@ -1,146 +1,383 @@
-use crate::{Entity, ComponentBase, Rc, b2, Physics, World};
+use crate::{Entity, ComponentBase, Arc, b2, Physics, World, PhysicsComponent, JointComponent, EntityHandle, DespawnQueue};
+use crate::{RenderMesh, Translation, Rotation, NonUniformScale, Transform, TransformBuffer, MovementComponent, PlayerMovementComponent};
+use crate::transform::{Parent, Children, TransformHierarchy};
+use super::storage::{ComponentStorage, ComponentRef, ComponentRefMut, AnyStorage, read_storage, write_storage};
+use super::query::{Query, QueryBorrow};
+use std::any::TypeId;
 use std::collections::HashMap;
-use rayon::prelude::*;
+use std::sync::RwLock;
+
+// Where a handle's entity lives in `entities`, if it's still alive
+struct Slot{
+    generation: u32,
+    index: Option<usize>,
+}
 
 pub struct EntityManager{
+    // Per-entity data that isn't a component (Uniforms, name and tags), in creation order
     pub entities: Vec::<Entity>,
+    // Indexed by EntityHandle::index, so looking up a handle doesn't scan the entities
+    slots: Vec::<Slot>,
+    free_slots: Vec::<u32>,
+    // One packed storage per component type
+    storages: HashMap::<TypeId, Box<dyn AnyStorage>>,
+    despawn_queue: DespawnQueue,
 }
 
 impl EntityManager{
     pub fn new() -> Self{
         log::info!("Entity Manager Initialized");
 
-        Self{
+        let mut entity_manager = Self{
             entities: Vec::<Entity>::new(),
-        }
+            slots: Vec::<Slot>::new(),
+            free_slots: Vec::<u32>::new(),
+            storages: HashMap::<TypeId, Box<dyn AnyStorage>>::new(),
+            despawn_queue: DespawnQueue::new(),
+        };
+        entity_manager.register_component::<RenderMesh>();
+        entity_manager.register_component::<Translation>();
+        entity_manager.register_component::<Rotation>();
+        entity_manager.register_component::<NonUniformScale>();
+        entity_manager.register_component::<Transform>();
+        entity_manager.register_component::<MovementComponent>();
+        entity_manager.register_component::<PlayerMovementComponent>();
+        entity_manager.register_component::<PhysicsComponent>();
+        entity_manager.register_component::<JointComponent>();
+        entity_manager.register_component::<TransformBuffer>();
+        entity_manager.register_component::<Parent>();
+        entity_manager.register_component::<Children>();
+        entity_manager
+    }
+
+    // Components passed to create_entity as Box<dyn ComponentBase> need their type registered first,
+    // since the storage can't be created from a trait object. add_component registers the type itself
+    pub fn register_component<T: ComponentBase + 'static>(&mut self){
+        self.storages.entry(TypeId::of::<T>()).or_insert_with(|| Box::new(RwLock::new(ComponentStorage::<T>::new())));
+    }
+
+    pub fn get_storage<T: ComponentBase + 'static>(&self) -> Option<&RwLock<ComponentStorage<T>>>{
+        self.storages.get(&TypeId::of::<T>())?.as_any().downcast_ref::<RwLock<ComponentStorage<T>>>()
     }
 
-    pub fn create_entity(&mut self, components: Vec::<Box<dyn ComponentBase>>, uniforms: Vec::<Rc<wgpu::BindGroup>>){
+    pub fn create_entity(&mut self, components: Vec::<Box<dyn ComponentBase>>, uniforms: Vec::<Arc<wgpu::BindGroup>>) -> EntityHandle{
         let component_count = format!("Entity created with {:?} components and {:?} uniforms", components.len(), uniforms.len());
         log::info!("{}", &component_count);
-        let mut entity = Entity::new(components, self.entities.len());
+
+        let handle = match self.free_slots.pop(){
+            Some(index) => EntityHandle { index, generation: self.slots[index as usize].generation },
+            None => {
+                self.slots.push(Slot { generation: 0, index: None });
+                EntityHandle { index: (self.slots.len() - 1) as u32, generation: 0 }
+            }
+        };
+        self.slots[handle.index as usize].index = Some(self.entities.len());
+
+        for component in components.into_iter(){
+            let storage = match self.storages.get(&component.as_any().type_id()){
+                Some(v) => v,
+                None => panic!("Component with id {:?} was never registered with EntityManager::register_component", component.get_id()),
+            };
+            if storage.insert_boxed(handle, component).is_err(){
+                panic!("Component storage for {} was given a component of another type", storage.type_name());
+            }
+        }
+
+        let mut entity = Entity::new(handle);
         entity.set_uniforms(uniforms);
         self.entities.push(entity);
+        handle
     }
 
-    pub fn add_component_data<T: ComponentBase + 'static>(&mut self, entity_id: &usize, component: Box<dyn ComponentBase>) -> Result<&mut T, &str>{
-        let entity = &mut self.entities[*entity_id];
-        let component_id = component.get_id();
-        entity.add_component(component);
-        self.entities[*entity_id].get_component_mut(component_id)
+    // Destroy the entity now, along with its Box2D body, its joints and its children. Its GPU buffers and bind groups are released when its components
+    // and uniforms are dropped here. Don't call this while a query is alive - use the despawn queue instead
+    pub fn destroy_entity(&mut self, handle: EntityHandle, physics: &mut Physics) -> Result<(), &'static str>{
+        self.find_entity(handle)?;
+
+        // Children go with their parent, and the parent forgets the entity
+        if let Some(children) = self.remove_component::<Children>(handle){
+            for child in children.entities.into_iter(){
+                if self.is_alive(child){
+                    self.destroy_entity(child, physics)?;
+                }
+            }
+        }
+        TransformHierarchy::remove_parent(self, handle);
+
+        let index = self.find_entity(handle)?;
+
+        // Joints on either end go with the body
+        if let Some(mut joints) = self.remove_component::<JointComponent>(handle){
+            joints.disconnect_all(physics);
+        }
+        for (_, joints) in self.query::<&mut JointComponent>().iter(){
+            joints.forget_entity(handle);
+        }
+        if let Some(physics_component) = self.remove_component::<PhysicsComponent>(handle){
+            physics.world.destroy_body(physics_component.handle);
+        }
+        for storage in self.storages.values(){
+            storage.remove_boxed(handle);
+        }
+
+        // Keep the draw order of the remaining entities, so shift everything after it down
+        let entity = self.entities.remove(index);
+        for moved in self.entities[index..].iter(){
+            if let Some(i) = self.slots[moved.handle.index as usize].index.as_mut(){
+                *i -= 1;
+            }
+        }
+        drop(entity);
+
+        let slot = &mut self.slots[handle.index as usize];
+        slot.index = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.index);
+
+        log::info!("Entity {:?} destroyed", handle);
+        Ok(())
     }
 
-    pub fn remove_component_data(&mut self, entity: &mut Entity, component_id: u32){
-        entity.try_remove_component(component_id).unwrap();
+    // Shares the queue, so systems can hold it while a query has the storages borrowed
+    pub fn get_despawn_queue(&self) -> DespawnQueue{
+        self.despawn_queue.clone()
     }
 
-    pub fn get_component_data<T: ComponentBase + 'static>(&mut self, entity: usize, component_id: u32) -> Result<&mut T, &str>{
-        self.entities[entity].get_component_mut(component_id)
+    pub fn queue_destroy(&self, handle: EntityHandle){
+        self.despawn_queue.push(handle);
     }
 
-    // Returns the index of the entity
-    pub fn find_entity(&mut self, id: usize) -> Result<usize, &str>{
-        if let Some(index) = self.entities.iter_mut().position(|x| x.id == id){
-            return Ok(index);
+    // Destroy everything in the despawn queue. Handles queued twice, or already destroyed, are skipped
+    pub fn flush_despawn_queue(&mut self, physics: &mut Physics){
+        let mut queue = self.despawn_queue.take();
+        if physics.get_config().deterministic{
+            // Systems running in parallel push in whatever order they finish, and the order entities are destroyed in
+            // decides which slots get reused
+            queue.sort_by_key(|handle| (handle.index, handle.generation));
+        }
+        for handle in queue{
+            if self.is_alive(handle){
+                self.destroy_entity(handle, physics).unwrap();
+            }
         }
-        Err("Entity not found")
     }
 
-    pub fn get_entities_with_type(&self, id: u32) -> Vec::<&Entity>{
-        let mut entities = Vec::<&Entity>::new();
-        for entity in self.entities.iter(){
-            if let Ok(_) = entity.try_find_component(id){
-                entities.push(&entity);
-            }
+    // False once the entity has been destroyed, even if its slot now holds a new entity
+    pub fn is_alive(&self, handle: EntityHandle) -> bool{
+        self.find_entity(handle).is_ok()
+    }
+
+    pub fn get_entity(&self, handle: EntityHandle) -> Option<&Entity>{
+        let index = self.find_entity(handle).ok()?;
+        Some(&self.entities[index])
+    }
+
+    pub fn get_entity_mut(&mut self, handle: EntityHandle) -> Option<&mut Entity>{
+        let index = self.find_entity(handle).ok()?;
+        Some(&mut self.entities[index])
+    }
+
+    // Returns the index of the entity in `entities`
+    pub fn find_entity(&self, handle: EntityHandle) -> Result<usize, &'static str>{
+        let slot = match self.slots.get(handle.index as usize){
+            Some(v) => v,
+            None => return Err("Entity not found"),
+        };
+        match slot.index{
+            Some(index) if slot.generation == handle.generation => Ok(index),
+            _ => Err("Stale entity handle, the entity was destroyed"),
         }
+    }
 
-        entities
+    // Replaces the component if the entity already has one of this type
+    pub fn add_component<T: ComponentBase + 'static>(&mut self, handle: EntityHandle, component: T) -> Result<(), &'static str>{
+        self.find_entity(handle)?;
+        self.register_component::<T>();
+        write_storage(self.get_storage::<T>().unwrap()).insert(handle, component);
+        Ok(())
     }
 
-    pub fn get_entities_with_type_mut(&mut self, id: u32) -> Vec::<&mut Entity>{
-        let mut entities = Vec::<&mut Entity>::new();
-        for entity in self.entities.iter_mut(){
-            match entity.try_find_component(id){
-                Ok(_) => entities.push(&mut *entity),
-                Err(_) => {}
-            };
+    pub fn remove_component<T: ComponentBase + 'static>(&mut self, handle: EntityHandle) -> Option<T>{
+        write_storage(self.get_storage::<T>()?).remove(handle)
+    }
+
+    pub fn has_component<T: ComponentBase + 'static>(&self, handle: EntityHandle) -> bool{
+        match self.get_storage::<T>(){
+            Some(storage) => read_storage(storage).contains(handle),
+            None => false,
         }
-        entities
-    }
-
-
-    pub fn get_entities_with_types_mut(&mut self, ids: &[u32]) -> Vec::<&mut Entity>{
-        // Create an empty vec to store our entity indexes
-        let mut entities = HashMap::<usize, usize>::new();
-
-        let mut loop_count = 0;
-        // Iterate through the type ids
-        for id in ids{
-            // Get all entities with the ID
-            let mut entities_to_add = self.get_entities_with_type(*id);
-            let mut contained_entities = HashMap::<usize, usize>::new();
-            // Iterate through entities with ID
-            for entity in entities_to_add{
-                // If we've just started, add entity ID into the array regardless
-                if loop_count == 0{
-                    entities.insert(entity.id, 0);
-                }else{
-
-                    // Otherwise, check that our Vec contains the entity (By checking the ID of the entity is contained in our entity array)
-                    if entities.contains_key(&entity.id){
-                        contained_entities.insert(entity.id, 0);
-                    }else{
-                        // Otherwise, don't add it
-                    }
-                }
-            }
-            // Make sure this isn't our first loop. Save some precious render time
-            if loop_count != 0{
-                // Set our entities to the ones that are contained so we only have the entities we want. Filter it down
-                entities = contained_entities;
-            }
+    }
+
+    // Panics if a live query writes this component type
+    pub fn get_component<T: ComponentBase + 'static>(&self, handle: EntityHandle) -> Option<ComponentRef<'_, T>>{
+        ComponentRef::new(read_storage(self.get_storage::<T>()?), handle)
+    }
 
-            loop_count += 1;
+    // Panics if a live query uses this component type
+    pub fn get_component_mut<T: ComponentBase + 'static>(&self, handle: EntityHandle) -> Option<ComponentRefMut<'_, T>>{
+        ComponentRefMut::new(write_storage(self.get_storage::<T>()?), handle)
+    }
+
+    // Every component of the entity, ordered by component id so the order doesn't depend on the storage map
+    pub fn visit_components(&self, handle: EntityHandle, f: &mut dyn FnMut(&dyn ComponentBase)){
+        let mut storages = Vec::<(u32, &Box<dyn AnyStorage>)>::new();
+        for storage in self.storages.values(){
+            storage.visit(handle, &mut |component| storages.push((component.get_id(), storage)));
         }
-        let ret_entities : Vec::<&mut Entity> = self.entities.iter_mut().filter(|item| entities.contains_key(&item.id)).collect();
-        ret_entities
-    }
-
-    pub fn get_entities_with_types(&mut self, ids: &[u32]) -> Vec::<&Entity>{
-        // Create an empty vec to store our entity indexes
-        let mut entities = HashMap::<usize, usize>::new();
-        let mut loop_count = 0;
-        // Iterate through the type ids
-        for id in ids{
-            // Get all entities with the ID
-            let entities_to_add = self.get_entities_with_type(*id);
-            // Vec will act as a buffer to check what entities already exist, so we can filter down
-            let mut contained_entities = HashMap::<usize, usize>::new();
-
-            // Iterate through entities with ID
-            for entity in entities_to_add.iter(){
-                // If we've just started, add entity ID into the array regardless
-                if loop_count == 0{
-                    entities.insert(entity.id, 0);
-                }else{
-
-                    // Otherwise, check that our Vec contains the entity (By checking the ID of the entity is contained in our entity array)
-                    if entities.contains_key(&entity.id){
-                        contained_entities.insert(entity.id, 0);
-                    }else{
-                        // Otherwise, don't add it
-                    }
-                }
-            }
-            // Make sure this isn't our first loop. Save some precious render time
-            if loop_count != 0{
-                // Set our entities to the ones that are contained so we only have the entities we want. Filter it down
-                entities = contained_entities;
-            }
+        storages.sort_by_key(|s| s.0);
+        for (_, storage) in storages.into_iter(){
+            storage.visit(handle, f);
+        }
+    }
 
-            loop_count += 1;
+    // Every entity that has all the requested components, e.g. query::<(&mut Transform, &PhysicsComponent)>().
+    // Panics if the query aliases a component it writes, use try_query where the query type isn't fixed
+    pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q>{
+        match QueryBorrow::<Q>::new(self){
+            Ok(v) => v,
+            Err(e) => panic!("{}", e),
         }
-        let ret_entities : Vec::<&Entity> = self.entities.iter().filter(|item| entities.contains_key(&item.id)).collect();
-        ret_entities
     }
-}
\ No newline at end of file
+
+    // Same as query, but a query like (&mut T, &T) is an Err instead of a panic. Nothing is borrowed in that case
+    pub fn try_query<Q: Query>(&self) -> Result<QueryBorrow<'_, Q>, String>{
+        QueryBorrow::<Q>::new(self)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&Entity>{
+        self.entities.iter().find(|x| x.get_name() == Some(name))
+    }
+
+    pub fn find_by_name_mut(&mut self, name: &str) -> Option<&mut Entity>{
+        self.entities.iter_mut().find(|x| x.get_name() == Some(name))
+    }
+
+    // First entity with the tag, for tags only one entity should have (e.g. "player")
+    pub fn find_by_tag(&self, tag: &str) -> Option<&Entity>{
+        self.entities.iter().find(|x| x.has_tag(tag))
+    }
+
+    pub fn find_by_tag_mut(&mut self, tag: &str) -> Option<&mut Entity>{
+        self.entities.iter_mut().find(|x| x.has_tag(tag))
+    }
+
+    pub fn entities_with_tag(&self, tag: &str) -> Vec::<&Entity>{
+        self.entities.iter().filter(|x| x.has_tag(tag)).collect()
+    }
+
+    pub fn entities_with_tag_mut(&mut self, tag: &str) -> Vec::<&mut Entity>{
+        self.entities.iter_mut().filter(|x| x.has_tag(tag)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn spawn(entity_manager: &mut EntityManager, speed: f32) -> EntityHandle{
+        entity_manager.create_entity(vec![Box::new(MovementComponent::new(speed))], Vec::new())
+    }
+
+    #[test]
+    fn stale_handle_is_none_after_slot_reuse(){
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        let old = spawn(&mut entity_manager, 1.0);
+        entity_manager.destroy_entity(old, &mut physics).unwrap();
+        let new = spawn(&mut entity_manager, 2.0);
+
+        assert_eq!(new.index, old.index);
+        assert_ne!(new.generation, old.generation);
+        assert!(!entity_manager.is_alive(old));
+        assert!(entity_manager.get_entity(old).is_none());
+        assert!(entity_manager.get_component::<MovementComponent>(old).is_none());
+        assert_eq!(entity_manager.get_component::<MovementComponent>(new).unwrap().speed, 2.0);
+        assert!(entity_manager.destroy_entity(old, &mut physics).is_err());
+        assert!(entity_manager.is_alive(new));
+    }
+
+    #[test]
+    fn destroy_and_respawn_keeps_other_handles(){
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        let a = spawn(&mut entity_manager, 1.0);
+        let b = spawn(&mut entity_manager, 2.0);
+        let c = spawn(&mut entity_manager, 3.0);
+
+        entity_manager.destroy_entity(b, &mut physics).unwrap();
+        assert_eq!(entity_manager.entities.len(), 2);
+        assert_eq!(entity_manager.get_component::<MovementComponent>(a).unwrap().speed, 1.0);
+        assert_eq!(entity_manager.get_component::<MovementComponent>(c).unwrap().speed, 3.0);
+        assert_eq!(entity_manager.get_entity(c).unwrap().handle, c);
+
+        let d = spawn(&mut entity_manager, 4.0);
+        assert_eq!(d.index, b.index);
+        assert_eq!(entity_manager.query::<&MovementComponent>().iter().count(), 3);
+        assert_eq!(entity_manager.get_entity(d).unwrap().handle, d);
+        assert!(entity_manager.get_component::<MovementComponent>(b).is_none());
+    }
+
+    // Names and tags are set after spawning, the way SceneLoader does
+    fn spawn_named(entity_manager: &mut EntityManager, name: &str, tags: &[&str]) -> EntityHandle{
+        let handle = spawn(entity_manager, 1.0);
+        let entity = entity_manager.get_entity_mut(handle).unwrap();
+        entity.set_name(name);
+        for tag in tags.iter(){
+            entity.add_tag(tag);
+        }
+        handle
+    }
+
+    #[test]
+    fn find_by_name_and_tag(){
+        let mut entity_manager = EntityManager::new();
+        let player = spawn_named(&mut entity_manager, "Player", &["player"]);
+        let first = spawn_named(&mut entity_manager, "WallEnemy", &["enemy", "wall"]);
+        let second = spawn_named(&mut entity_manager, "WallEnemy", &["enemy"]);
+        spawn(&mut entity_manager, 2.0);
+
+        assert_eq!(entity_manager.find_by_name("Player").unwrap().handle, player);
+        // The first match when names repeat
+        assert_eq!(entity_manager.find_by_name("WallEnemy").unwrap().handle, first);
+        assert!(entity_manager.find_by_name("player").is_none());
+        assert!(entity_manager.find_by_name("Nobody").is_none());
+
+        assert_eq!(entity_manager.find_by_tag("player").unwrap().handle, player);
+        assert_eq!(entity_manager.find_by_tag("wall").unwrap().handle, first);
+        assert!(entity_manager.find_by_tag("Player").is_none());
+        let enemies: Vec<EntityHandle> = entity_manager.entities_with_tag("enemy").iter().map(|e| e.handle).collect();
+        assert_eq!(enemies, vec!(first, second));
+        assert!(entity_manager.entities_with_tag("boss").is_empty());
+
+        entity_manager.find_by_tag_mut("wall").unwrap().remove_tag("wall");
+        assert!(entity_manager.find_by_tag("wall").is_none());
+        assert_eq!(entity_manager.entities_with_tag_mut("enemy").len(), 2);
+        assert_eq!(entity_manager.find_by_name_mut("Player").unwrap().handle, player);
+    }
+
+    #[test]
+    fn destroyed_entities_stop_matching(){
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        let first = spawn_named(&mut entity_manager, "WallEnemy", &["enemy"]);
+        let second = spawn_named(&mut entity_manager, "WallEnemy", &["enemy"]);
+
+        entity_manager.destroy_entity(first, &mut physics).unwrap();
+        assert_eq!(entity_manager.find_by_name("WallEnemy").unwrap().handle, second);
+        assert_eq!(entity_manager.find_by_tag("enemy").unwrap().handle, second);
+        assert_eq!(entity_manager.entities_with_tag("enemy").len(), 1);
+
+        // Through the despawn queue too
+        entity_manager.queue_destroy(second);
+        entity_manager.flush_despawn_queue(&mut physics);
+        assert!(entity_manager.find_by_name("WallEnemy").is_none());
+        assert!(entity_manager.find_by_tag("enemy").is_none());
+        assert!(entity_manager.entities_with_tag("enemy").is_empty());
+
+        // A new entity in the freed slot doesn't inherit the name or tags
+        spawn(&mut entity_manager, 3.0);
+        assert!(entity_manager.find_by_name("WallEnemy").is_none());
+        assert!(entity_manager.entities_with_tag("enemy").is_empty());
+    }
+}
+
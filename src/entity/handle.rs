@@ -0,0 +1,40 @@
+use std::sync::{Arc, Mutex};
+
+// Refers to an entity without borrowing it. The generation goes up every time a slot is reused,
+// so a handle to a destroyed entity is detected as stale instead of pointing at whatever took its place
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EntityHandle{
+    pub index: u32,
+    pub generation: u32,
+}
+
+// Entities to destroy once the current stage of systems has finished. Cloning shares the same queue, and it can be
+// pushed to from systems running in parallel
+#[derive(Clone)]
+pub struct DespawnQueue{
+    queue: Arc<Mutex<Vec<EntityHandle>>>,
+}
+
+impl DespawnQueue{
+    pub fn new() -> Self{
+        Self{
+            queue: Arc::new(Mutex::new(Vec::<EntityHandle>::new())),
+        }
+    }
+
+    pub fn push(&self, handle: EntityHandle){
+        self.queue.lock().unwrap().push(handle);
+    }
+
+    pub fn len(&self) -> usize{
+        self.queue.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.queue.lock().unwrap().is_empty()
+    }
+
+    pub fn take(&self) -> Vec<EntityHandle>{
+        std::mem::replace(&mut *self.queue.lock().unwrap(), Vec::<EntityHandle>::new())
+    }
+}
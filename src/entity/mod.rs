@@ -1,3 +1,5 @@
 pub mod rendermesh;
 pub mod entity;
-pub mod entitymanager;
\ No newline at end of file
+pub mod entitymanager;
+pub mod handle;pub mod storage;
+pub mod query;
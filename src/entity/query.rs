@@ -0,0 +1,271 @@
+use crate::{ComponentBase, EntityHandle, EntityManager};
+use super::storage::{ComponentStorage, read_storage, write_storage};
+use std::any::TypeId;
+
+// Typed queries over the component storages, e.g.
+//
+// for (handle, (transform, physics)) in entity_manager.query::<(&mut Transform, &PhysicsComponent)>().iter(){ ... }
+//
+// Every storage in the query is locked for as long as the QueryBorrow is alive, so two components of one entity
+// can be used at the same time. A query that writes a type and asks for it again, like (&mut T, &T), is rejected before
+// any storage is borrowed, since it would hand out two references to the same component
+
+// Anything that keeps a storage borrowed. Only held so the borrow lasts as long as the query
+pub trait StorageGuard{}
+impl<T> StorageGuard for T{}
+
+pub trait Fetch<'q>{
+    type Item;
+    // Safety: a handle must not be fetched again while an item fetched for it is still alive
+    unsafe fn get(&self, handle: EntityHandle) -> Option<Self::Item>;
+}
+
+pub trait Query{
+    type Fetch: for<'q> Fetch<'q>;
+    // Every component type the query uses, with whether it's written
+    fn access(access: &mut Vec<(TypeId, &'static str, bool)>);
+    // Borrow every storage the query uses. None if one of them doesn't exist yet, since then nothing can match.
+    // `candidates` is narrowed down to the owners of the smallest storage, which is what gets iterated
+    fn borrow<'w>(manager: &'w EntityManager, guards: &mut Vec<Box<dyn StorageGuard + 'w>>, candidates: &mut Option<*const [EntityHandle]>) -> Option<Self::Fetch>;
+}
+
+pub struct FetchRead<T>{
+    storage: *const ComponentStorage<T>,
+}
+
+pub struct FetchWrite<T>{
+    storage: *const ComponentStorage<T>,
+    components: *mut T,
+}
+
+impl<'q, T: 'static> Fetch<'q> for FetchRead<T>{
+    type Item = &'q T;
+    unsafe fn get(&self, handle: EntityHandle) -> Option<&'q T>{
+        (*self.storage).get(handle)
+    }
+}
+
+impl<'q, T: 'static> Fetch<'q> for FetchWrite<T>{
+    type Item = &'q mut T;
+    unsafe fn get(&self, handle: EntityHandle) -> Option<&'q mut T>{
+        let index = (*self.storage).dense_index(handle)?;
+        Some(&mut *self.components.add(index))
+    }
+}
+
+fn narrow(candidates: &mut Option<*const [EntityHandle]>, owners: &[EntityHandle]){
+    let smaller = match candidates{
+        Some(current) => unsafe { owners.len() < (&**current).len() },
+        None => true,
+    };
+    if smaller{
+        *candidates = Some(owners as *const [EntityHandle]);
+    }
+}
+
+impl<'a, T: ComponentBase + 'static> Query for &'a T{
+    type Fetch = FetchRead<T>;
+    fn access(access: &mut Vec<(TypeId, &'static str, bool)>){
+        access.push((TypeId::of::<T>(), std::any::type_name::<T>(), false));
+    }
+    fn borrow<'w>(manager: &'w EntityManager, guards: &mut Vec<Box<dyn StorageGuard + 'w>>, candidates: &mut Option<*const [EntityHandle]>) -> Option<Self::Fetch>{
+        let guard = read_storage(manager.get_storage::<T>()?);
+        let storage = &*guard as *const ComponentStorage<T>;
+        narrow(candidates, guard.owners());
+        guards.push(Box::new(guard));
+        Some(FetchRead { storage })
+    }
+}
+
+impl<'a, T: ComponentBase + 'static> Query for &'a mut T{
+    type Fetch = FetchWrite<T>;
+    fn access(access: &mut Vec<(TypeId, &'static str, bool)>){
+        access.push((TypeId::of::<T>(), std::any::type_name::<T>(), true));
+    }
+    fn borrow<'w>(manager: &'w EntityManager, guards: &mut Vec<Box<dyn StorageGuard + 'w>>, candidates: &mut Option<*const [EntityHandle]>) -> Option<Self::Fetch>{
+        let mut guard = write_storage(manager.get_storage::<T>()?);
+        let components = guard.components_ptr();
+        let storage = &*guard as *const ComponentStorage<T>;
+        narrow(candidates, guard.owners());
+        guards.push(Box::new(guard));
+        Some(FetchWrite { storage, components })
+    }
+}
+
+macro_rules! tuple_query{
+    ($($name:ident),*) => {
+        impl<'q, $($name: Fetch<'q>),*> Fetch<'q> for ($($name,)*){
+            type Item = ($(<$name as Fetch<'q>>::Item,)*);
+            #[allow(non_snake_case)]
+            unsafe fn get(&self, handle: EntityHandle) -> Option<Self::Item>{
+                let ($($name,)*) = self;
+                Some(($($name.get(handle)?,)*))
+            }
+        }
+
+        impl<$($name: Query),*> Query for ($($name,)*){
+            type Fetch = ($($name::Fetch,)*);
+            fn access(access: &mut Vec<(TypeId, &'static str, bool)>){
+                $($name::access(access);)*
+            }
+            fn borrow<'w>(manager: &'w EntityManager, guards: &mut Vec<Box<dyn StorageGuard + 'w>>, candidates: &mut Option<*const [EntityHandle]>) -> Option<Self::Fetch>{
+                Some(($($name::borrow(manager, guards, candidates)?,)*))
+            }
+        }
+    }
+}
+
+tuple_query!(A);
+tuple_query!(A, B);
+tuple_query!(A, B, C);
+tuple_query!(A, B, C, D);
+tuple_query!(A, B, C, D, E);
+tuple_query!(A, B, C, D, E, F);
+
+pub struct QueryBorrow<'w, Q: Query>{
+    fetch: Option<Q::Fetch>,
+    candidates: *const [EntityHandle],
+    // Released when the query is dropped
+    _guards: Vec<Box<dyn StorageGuard + 'w>>,
+}
+
+// Err naming the type if the query writes a component it also asks for somewhere else
+pub fn check_access<Q: Query>() -> Result<(), String>{
+    let mut access = Vec::<(TypeId, &'static str, bool)>::new();
+    Q::access(&mut access);
+    for (i, (id, name, write)) in access.iter().enumerate(){
+        for (other_id, _, other_write) in access[i + 1..].iter(){
+            if id == other_id && (*write || *other_write){
+                return Err(format!("The query asks for {} more than once, and writes it", name));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<'w, Q: Query> QueryBorrow<'w, Q>{
+    pub fn new(manager: &'w EntityManager) -> Result<Self, String>{
+        check_access::<Q>()?;
+        let mut guards = Vec::<Box<dyn StorageGuard + 'w>>::new();
+        let mut candidates = None;
+        let fetch = Q::borrow(manager, &mut guards, &mut candidates);
+        let empty: &'static [EntityHandle] = &[];
+        Ok(Self{
+            candidates: match fetch{
+                Some(_) => candidates.unwrap_or(empty as *const [EntityHandle]),
+                None => empty as *const [EntityHandle],
+            },
+            fetch,
+            _guards: guards,
+        })
+    }
+
+    // Takes &mut self so only one iterator (And one &mut per component) can exist at a time
+    pub fn iter(&mut self) -> QueryIter<'_, Q>{
+        QueryIter{
+            fetch: self.fetch.as_ref(),
+            // The owners can't change while the storages are borrowed
+            candidates: unsafe { &*self.candidates },
+            position: 0,
+        }
+    }
+}
+
+pub struct QueryIter<'q, Q: Query>{
+    fetch: Option<&'q Q::Fetch>,
+    candidates: &'q [EntityHandle],
+    position: usize,
+}
+
+impl<'q, Q: Query> Iterator for QueryIter<'q, Q>{
+    type Item = (EntityHandle, <Q::Fetch as Fetch<'q>>::Item);
+
+    fn next(&mut self) -> Option<Self::Item>{
+        let fetch = self.fetch?;
+        while self.position < self.candidates.len(){
+            let handle = self.candidates[self.position];
+            self.position += 1;
+            // Every entity appears once in candidates, so its components are never handed out twice
+            if let Some(item) = unsafe { fetch.get(handle) }{
+                return Some((handle, item));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{MovementComponent, PlayerMovementComponent};
+
+    fn spawn(entity_manager: &mut EntityManager, speed: f32, player: bool) -> EntityHandle{
+        let mut components = Vec::<Box<dyn ComponentBase>>::new();
+        components.push(Box::new(MovementComponent::new(speed)));
+        if player{
+            components.push(Box::new(PlayerMovementComponent::new(speed * 2.0)));
+        }
+        entity_manager.create_entity(components, Vec::new())
+    }
+
+    #[test]
+    fn tuples_only_match_entities_with_every_component(){
+        let mut entity_manager = EntityManager::new();
+        let a = spawn(&mut entity_manager, 1.0, false);
+        let b = spawn(&mut entity_manager, 2.0, true);
+        let c = spawn(&mut entity_manager, 3.0, true);
+
+        let mut found: Vec<(EntityHandle, f32, f32)> = entity_manager.query::<(&MovementComponent, &PlayerMovementComponent)>().iter()
+            .map(|(handle, (movement, player))| (handle, movement.speed, player.speed)).collect();
+        found.sort_by_key(|x| x.0.index);
+        assert_eq!(found, vec![(b, 2.0, 4.0), (c, 3.0, 6.0)]);
+        assert_eq!(entity_manager.query::<&MovementComponent>().iter().map(|(handle, _)| handle).collect::<Vec<_>>(), vec![a, b, c]);
+    }
+
+    #[test]
+    fn writes_reach_the_storage(){
+        let mut entity_manager = EntityManager::new();
+        let a = spawn(&mut entity_manager, 1.0, true);
+        let b = spawn(&mut entity_manager, 2.0, false);
+        for (_, (movement, player)) in entity_manager.query::<(&mut MovementComponent, &PlayerMovementComponent)>().iter(){
+            movement.speed += player.speed;
+        }
+        assert_eq!(entity_manager.get_component::<MovementComponent>(a).unwrap().speed, 3.0);
+        assert_eq!(entity_manager.get_component::<MovementComponent>(b).unwrap().speed, 2.0);
+    }
+
+    #[test]
+    fn unregistered_components_match_nothing(){
+        struct Unregistered;
+        impl ComponentBase for Unregistered{
+            fn get_id(&self) -> u32{
+                0
+            }
+            fn as_any(&self) -> &dyn std::any::Any{
+                self
+            }
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any{
+                self
+            }
+            fn into_any(self: Box<Self>) -> Box<dyn std::any::Any>{
+                self
+            }
+        }
+        let mut entity_manager = EntityManager::new();
+        spawn(&mut entity_manager, 1.0, false);
+        assert_eq!(entity_manager.query::<(&MovementComponent, &Unregistered)>().iter().count(), 0);
+    }
+
+    #[test]
+    fn aliasing_writes_are_rejected_before_borrowing(){
+        let mut entity_manager = EntityManager::new();
+        spawn(&mut entity_manager, 1.0, true);
+
+        assert!(entity_manager.try_query::<(&mut MovementComponent, &MovementComponent)>().is_err());
+        assert!(entity_manager.try_query::<(&MovementComponent, &PlayerMovementComponent, &mut MovementComponent)>().is_err());
+        assert!(entity_manager.try_query::<(&mut MovementComponent, &mut MovementComponent)>().is_err());
+        assert!(entity_manager.try_query::<(&MovementComponent, &MovementComponent)>().is_ok());
+        // The rejected queries left nothing locked
+        assert_eq!(entity_manager.query::<&mut MovementComponent>().iter().count(), 1);
+    }
+}
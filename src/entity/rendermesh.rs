@@ -1,5 +1,7 @@
 use wgpu::util::DeviceExt;
-use crate::{Renderer, Vertex, Material, MaterialUniform, Rc, ComponentBase, UniformUtils};
+use crate::{Renderer, Vertex, Material, MaterialUniform, Arc, ComponentBase, UniformUtils, Texture, TextureMode};
+use crate::scene::{ComponentRegistry, MaterialDescription};
+use crate::scene::args::error_at;
 use std::any::Any;
 
 const ID: u32 = 0;
@@ -23,6 +25,9 @@ impl ComponentBase for RenderMesh{
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 impl RenderMesh{
     pub fn new(renderer_reference: &Renderer, material: Material) -> Self{
@@ -76,6 +81,39 @@ impl RenderMesh{
         ID
     }
 
+    // material(path, color(r,g,b), shininess)
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.register_render_component("RenderMesh", ID, "material", |node, context| {
+            let material_def = MaterialDescription::from_node(node, context.version)?;
+            // Render components are skipped by the registry when there's no renderer
+            let renderer = context.renderer.unwrap();
+            let texture = match Texture::load_texture(renderer, &material_def.texture, TextureMode::RGB){
+                Ok(v) => v,
+                Err(e) => return Err(error_at(node, format!("failed to load texture {:?}: {}", material_def.texture, e))
+                    .hint("texture paths are relative to the working directory, e.g. ./data/textures/white.png").into()),
+            };
+            let material = Material::new(renderer, Arc::new(texture), material_def.color, material_def.shininess, 0.0, -1, "main".to_string());
+
+            let mut mesh = RenderMesh::new(renderer, material);
+            let (bindgroup, _, _) = mesh.generate_material_uniforms(renderer);
+            context.uniforms.push(Arc::new(bindgroup));
+            Ok(Box::new(mesh))
+        }, |component, _| {
+            let material = component.as_any().downcast_ref::<RenderMesh>()?.borrow_material();
+            match &material.borrow_texture().path{
+                Some(path) => Some(MaterialDescription{
+                    texture: path.clone(),
+                    color: material.get_color(),
+                    shininess: material.get_shininess(),
+                }.to_node()),
+                None => {
+                    log::warn!("Material without a texture path can't be saved");
+                    None
+                }
+            }
+        })
+    }
+
     pub fn get_vertex_buffer(&self) -> &wgpu::Buffer{
         &self.vertex_buffer
     }
@@ -100,7 +138,7 @@ impl RenderMesh{
         self.material.create_uniform_group(renderer_reference)
     }
 
-    pub fn draw<'a>(&'a self, render_pass: &'a mut wgpu::RenderPass<'a>, uniforms: &'a Vec::<Rc<wgpu::BindGroup>>){
+    pub fn draw<'a>(&'a self, render_pass: &'a mut wgpu::RenderPass<'a>, uniforms: &'a Vec::<Arc<wgpu::BindGroup>>){
         // 0 - texture count is reserved for textures
         render_pass.set_bind_group(0, self.borrow_material().borrow_texture().get_texture_group(), &[]);
         let mut i: u32 = 1;
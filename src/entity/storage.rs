@@ -0,0 +1,263 @@
+use crate::{ComponentBase, EntityHandle};
+use std::any::Any;
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// Sparse set holding every component of one type. The components are packed together so queries walk
+// contiguous memory, and `sparse` maps an entity's slot index to where its component is packed
+pub struct ComponentStorage<T>{
+    components: Vec<T>,
+    // owners[i] is the entity that components[i] belongs to
+    owners: Vec<EntityHandle>,
+    sparse: Vec<Option<usize>>,
+}
+
+impl<T> ComponentStorage<T>{
+    pub fn new() -> Self{
+        Self{
+            components: Vec::<T>::new(),
+            owners: Vec::<EntityHandle>::new(),
+            sparse: Vec::<Option<usize>>::new(),
+        }
+    }
+
+    // Returns the component it replaced, if the entity already had one
+    pub fn insert(&mut self, handle: EntityHandle, component: T) -> Option<T>{
+        if let Some(index) = self.dense_index(handle){
+            return Some(std::mem::replace(&mut self.components[index], component));
+        }
+        let slot = handle.index as usize;
+        if slot >= self.sparse.len(){
+            self.sparse.resize(slot + 1, None);
+        }
+        self.sparse[slot] = Some(self.components.len());
+        self.components.push(component);
+        self.owners.push(handle);
+        None
+    }
+
+    pub fn remove(&mut self, handle: EntityHandle) -> Option<T>{
+        let index = self.dense_index(handle)?;
+        self.sparse[handle.index as usize] = None;
+        // Move the last component into the gap so the storage stays packed
+        let component = self.components.swap_remove(index);
+        self.owners.swap_remove(index);
+        if index < self.owners.len(){
+            self.sparse[self.owners[index].index as usize] = Some(index);
+        }
+        Some(component)
+    }
+
+    // Where the entity's component is packed. None if it has none, or the handle is stale
+    pub fn dense_index(&self, handle: EntityHandle) -> Option<usize>{
+        let index = (*self.sparse.get(handle.index as usize)?)?;
+        if self.owners[index] == handle{
+            Some(index)
+        }else{
+            None
+        }
+    }
+
+    pub fn contains(&self, handle: EntityHandle) -> bool{
+        self.dense_index(handle).is_some()
+    }
+
+    pub fn get(&self, handle: EntityHandle) -> Option<&T>{
+        let index = self.dense_index(handle)?;
+        Some(&self.components[index])
+    }
+
+    pub fn get_mut(&mut self, handle: EntityHandle) -> Option<&mut T>{
+        let index = self.dense_index(handle)?;
+        Some(&mut self.components[index])
+    }
+
+    pub fn len(&self) -> usize{
+        self.components.len()
+    }
+
+    pub fn owners(&self) -> &[EntityHandle]{
+        &self.owners
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityHandle, &T)>{
+        self.owners.iter().copied().zip(self.components.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityHandle, &mut T)>{
+        self.owners.iter().copied().zip(self.components.iter_mut())
+    }
+
+    // Base of the packed components, used by queries to hand out one &mut per entity
+    pub(crate) fn components_ptr(&mut self) -> *mut T{
+        self.components.as_mut_ptr()
+    }
+}
+
+// One component, keeping its storage locked for reading while it's alive
+pub struct ComponentRef<'a, T>{
+    guard: RwLockReadGuard<'a, ComponentStorage<T>>,
+    index: usize,
+}
+
+// One component, keeping its storage locked for writing while it's alive
+pub struct ComponentRefMut<'a, T>{
+    guard: RwLockWriteGuard<'a, ComponentStorage<T>>,
+    index: usize,
+}
+
+impl<'a, T> ComponentRef<'a, T>{
+    // None if the entity doesn't have the component
+    pub fn new(guard: RwLockReadGuard<'a, ComponentStorage<T>>, handle: EntityHandle) -> Option<Self>{
+        let index = guard.dense_index(handle)?;
+        Some(Self { guard, index })
+    }
+}
+
+impl<'a, T> ComponentRefMut<'a, T>{
+    pub fn new(guard: RwLockWriteGuard<'a, ComponentStorage<T>>, handle: EntityHandle) -> Option<Self>{
+        let index = guard.dense_index(handle)?;
+        Some(Self { guard, index })
+    }
+}
+
+impl<'a, T> Deref for ComponentRef<'a, T>{
+    type Target = T;
+    fn deref(&self) -> &T{
+        &self.guard.components[self.index]
+    }
+}
+
+impl<'a, T> Deref for ComponentRefMut<'a, T>{
+    type Target = T;
+    fn deref(&self) -> &T{
+        &self.guard.components[self.index]
+    }
+}
+
+impl<'a, T> DerefMut for ComponentRefMut<'a, T>{
+    fn deref_mut(&mut self) -> &mut T{
+        &mut self.guard.components[self.index]
+    }
+}
+
+// Systems in the same stage can lock storages from different threads, so a storage that's already locked
+// the other way is a broken access declaration (Or a component held across a query), not something to wait for
+pub fn read_storage<T>(storage: &RwLock<ComponentStorage<T>>) -> RwLockReadGuard<'_, ComponentStorage<T>>{
+    match storage.try_read(){
+        Ok(v) => v,
+        Err(_) => panic!("{} is already borrowed mutably, it can't also be read", std::any::type_name::<T>()),
+    }
+}
+
+pub fn write_storage<T>(storage: &RwLock<ComponentStorage<T>>) -> RwLockWriteGuard<'_, ComponentStorage<T>>{
+    match storage.try_write(){
+        Ok(v) => v,
+        Err(_) => panic!("{} is already borrowed, it can't also be written", std::any::type_name::<T>()),
+    }
+}
+
+// Lets EntityManager keep storages of different types in one map, and do the things that don't need the type
+pub trait AnyStorage: Send + Sync{
+    fn as_any(&self) -> &dyn Any;
+    fn type_name(&self) -> &'static str;
+    fn contains(&self, handle: EntityHandle) -> bool;
+    // Fails (Giving the component back) if the component isn't of this storage's type
+    fn insert_boxed(&self, handle: EntityHandle, component: Box<dyn ComponentBase>) -> Result<(), Box<dyn ComponentBase>>;
+    fn remove_boxed(&self, handle: EntityHandle) -> Option<Box<dyn ComponentBase>>;
+    fn visit(&self, handle: EntityHandle, f: &mut dyn FnMut(&dyn ComponentBase));
+}
+
+impl<T: ComponentBase + 'static> AnyStorage for RwLock<ComponentStorage<T>>{
+    fn as_any(&self) -> &dyn Any{
+        self
+    }
+
+    fn type_name(&self) -> &'static str{
+        std::any::type_name::<T>()
+    }
+
+    fn contains(&self, handle: EntityHandle) -> bool{
+        read_storage(self).contains(handle)
+    }
+
+    fn insert_boxed(&self, handle: EntityHandle, component: Box<dyn ComponentBase>) -> Result<(), Box<dyn ComponentBase>>{
+        if !component.as_any().is::<T>(){
+            return Err(component);
+        }
+        let component = component.into_any().downcast::<T>().unwrap();
+        write_storage(self).insert(handle, *component);
+        Ok(())
+    }
+
+    fn remove_boxed(&self, handle: EntityHandle) -> Option<Box<dyn ComponentBase>>{
+        let component = write_storage(self).remove(handle)?;
+        Some(Box::new(component))
+    }
+
+    fn visit(&self, handle: EntityHandle, f: &mut dyn FnMut(&dyn ComponentBase)){
+        if let Some(component) = read_storage(self).get(handle){
+            f(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn handle(index: u32, generation: u32) -> EntityHandle{
+        EntityHandle { index, generation }
+    }
+
+    #[test]
+    fn remove_swaps_the_last_component_into_the_gap(){
+        let mut storage = ComponentStorage::<u32>::new();
+        for i in 0..4{
+            storage.insert(handle(i, 0), i * 10);
+        }
+        assert_eq!(storage.remove(handle(1, 0)), Some(10));
+
+        assert_eq!(storage.len(), 3);
+        assert_eq!(storage.owners(), &[handle(0, 0), handle(3, 0), handle(2, 0)]);
+        assert_eq!(storage.dense_index(handle(3, 0)), Some(1));
+        for i in [0, 2, 3].iter(){
+            assert_eq!(storage.get(handle(*i, 0)), Some(&(i * 10)));
+        }
+        assert_eq!(storage.get(handle(1, 0)), None);
+    }
+
+    #[test]
+    fn removing_the_last_and_only_components(){
+        let mut storage = ComponentStorage::<u32>::new();
+        storage.insert(handle(0, 0), 1);
+        storage.insert(handle(5, 0), 2);
+        assert_eq!(storage.remove(handle(5, 0)), Some(2));
+        assert_eq!(storage.get(handle(0, 0)), Some(&1));
+        assert_eq!(storage.remove(handle(0, 0)), Some(1));
+        assert_eq!(storage.len(), 0);
+        assert_eq!(storage.remove(handle(0, 0)), None);
+    }
+
+    #[test]
+    fn stale_handles_miss(){
+        let mut storage = ComponentStorage::<u32>::new();
+        storage.insert(handle(2, 0), 1);
+        storage.remove(handle(2, 0));
+        storage.insert(handle(2, 1), 2);
+
+        assert!(!storage.contains(handle(2, 0)));
+        assert_eq!(storage.get_mut(handle(2, 0)), None);
+        assert_eq!(storage.remove(handle(2, 0)), None);
+        assert_eq!(storage.get(handle(2, 1)), Some(&2));
+    }
+
+    #[test]
+    fn insert_replaces_an_existing_component(){
+        let mut storage = ComponentStorage::<u32>::new();
+        assert_eq!(storage.insert(handle(0, 0), 1), None);
+        assert_eq!(storage.insert(handle(0, 0), 2), Some(1));
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage.iter().collect::<Vec<_>>(), vec![(handle(0, 0), &2)]);
+    }
+}
@@ -0,0 +1,131 @@
+use winit::event::{VirtualKeyCode, ElementState};
+use crate::InputManager;
+
+// A key press or release at the start of a fixed step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputEvent{
+    pub tick: u64,
+    pub key: VirtualKeyCode,
+    pub state: ElementState,
+}
+
+// Keyboard input for headless runs, played back by tick so a run gives the same result every time.
+// The file format is one event per line, "tick press|release key", e.g.
+//     0 press D
+//     90 release D   // Stop before the edge
+#[derive(Clone)]
+pub struct InputScript{
+    // Sorted by tick, events on the same tick stay in file order
+    events: Vec<InputEvent>,
+}
+
+impl InputScript{
+    // No input at all
+    pub fn new() -> Self{
+        Self{
+            events: Vec::<InputEvent>::new(),
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String>{
+        let source = std::fs::read_to_string(path).map_err(|e| format!("Can't read input script {}: {}", path, e))?;
+        InputScript::parse(&source).map_err(|e| format!("{}:{}", path, e))
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String>{
+        let mut script = InputScript::new();
+        for (i, line) in source.lines().enumerate(){
+            let line = match line.find("//"){
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty(){
+                continue;
+            }
+            if words.len() != 3{
+                return Err(format!("{}: expected \"tick press|release key\", found {:?}", i + 1, line.trim()));
+            }
+            let tick = words[0].parse::<u64>().map_err(|_| format!("{}: tick {:?} isn't a whole number", i + 1, words[0]))?;
+            let state = match words[1]{
+                "press" => ElementState::Pressed,
+                "release" => ElementState::Released,
+                other => return Err(format!("{}: unknown action {:?}, use press or release", i + 1, other)),
+            };
+            let key = key_from_name(words[2]).ok_or_else(|| format!("{}: unknown key {:?}", i + 1, words[2]))?;
+            script.push(tick, key, state);
+        }
+        Ok(script)
+    }
+
+    pub fn press(&mut self, tick: u64, key: VirtualKeyCode){
+        self.push(tick, key, ElementState::Pressed);
+    }
+
+    pub fn release(&mut self, tick: u64, key: VirtualKeyCode){
+        self.push(tick, key, ElementState::Released);
+    }
+
+    fn push(&mut self, tick: u64, key: VirtualKeyCode, state: ElementState){
+        // After every event on the same tick, so events keep the order they were added in
+        let index = self.events.iter().position(|e| e.tick > tick).unwrap_or(self.events.len());
+        self.events.insert(index, InputEvent { tick, key, state });
+    }
+
+    pub fn get_events(&self) -> &Vec<InputEvent>{
+        &self.events
+    }
+
+    // Apply the events of one tick. Keys stay held until they're released
+    pub fn apply(&self, tick: u64, input_manager: &mut InputManager){
+        for event in self.events.iter().filter(|e| e.tick == tick){
+            input_manager.set_key(event.key, event.state);
+        }
+    }
+}
+
+// Key names as they're written in the VirtualKeyCode enum, case insensitive. Covers the keys the game reads
+fn key_from_name(name: &str) -> Option<VirtualKeyCode>{
+    let key = match name.to_lowercase().as_str(){
+        "a" => VirtualKeyCode::A,
+        "b" => VirtualKeyCode::B,
+        "c" => VirtualKeyCode::C,
+        "d" => VirtualKeyCode::D,
+        "e" => VirtualKeyCode::E,
+        "f" => VirtualKeyCode::F,
+        "g" => VirtualKeyCode::G,
+        "h" => VirtualKeyCode::H,
+        "i" => VirtualKeyCode::I,
+        "j" => VirtualKeyCode::J,
+        "k" => VirtualKeyCode::K,
+        "l" => VirtualKeyCode::L,
+        "m" => VirtualKeyCode::M,
+        "n" => VirtualKeyCode::N,
+        "o" => VirtualKeyCode::O,
+        "p" => VirtualKeyCode::P,
+        "q" => VirtualKeyCode::Q,
+        "r" => VirtualKeyCode::R,
+        "s" => VirtualKeyCode::S,
+        "t" => VirtualKeyCode::T,
+        "u" => VirtualKeyCode::U,
+        "v" => VirtualKeyCode::V,
+        "w" => VirtualKeyCode::W,
+        "x" => VirtualKeyCode::X,
+        "y" => VirtualKeyCode::Y,
+        "z" => VirtualKeyCode::Z,
+        "up" => VirtualKeyCode::Up,
+        "down" => VirtualKeyCode::Down,
+        "left" => VirtualKeyCode::Left,
+        "right" => VirtualKeyCode::Right,
+        "space" => VirtualKeyCode::Space,
+        "return" | "enter" => VirtualKeyCode::Return,
+        "escape" => VirtualKeyCode::Escape,
+        "tab" => VirtualKeyCode::Tab,
+        "lshift" => VirtualKeyCode::LShift,
+        "rshift" => VirtualKeyCode::RShift,
+        "lcontrol" => VirtualKeyCode::LControl,
+        "rcontrol" => VirtualKeyCode::RControl,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -0,0 +1,609 @@
+pub mod input_script;
+
+pub use input_script::{InputScript, InputEvent};
+
+use std::fmt::Write;
+use crate::{EntityManager, SystemManager, ComponentRegistry, SceneLoader, SceneWriter, Physics, PhysicsComponent, InputManager, Camera, Time, EntityHandle, DebugLine, PhysicsDebugDraw, PhysicsSnapshot, b2};
+use crate::scene::SCENE_FORMAT_VERSION;
+
+// What a headless run loads, how long it runs and where the result goes
+pub struct HeadlessOptions{
+    pub scene: String,
+    pub ticks: u64,
+    // Fixed steps per second, None keeps Time's default
+    pub tick_rate: Option<f32>,
+    pub input: InputScript,
+    // The final state is written to stdout when there's no output file
+    pub output: Option<String>,
+}
+
+impl HeadlessOptions{
+    pub fn new(scene: &str, ticks: u64) -> Self{
+        Self{
+            scene: scene.to_string(),
+            ticks,
+            tick_rate: None,
+            input: InputScript::new(),
+            output: None,
+        }
+    }
+}
+
+// One entity's body at the end of a tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyState{
+    pub entity: EntityHandle,
+    pub position: b2::Vec2,
+    pub angle: f32,
+    pub velocity: b2::Vec2,
+    pub spin: f32,
+    pub awake: bool,
+}
+
+impl BodyState{
+    // == on floats counts 0.0 and -0.0 as the same and NaN as different from itself, this compares what's stored
+    pub fn same_bits(&self, other: &BodyState) -> bool{
+        let bits = |state: &BodyState| [state.position.x.to_bits(), state.position.y.to_bits(), state.angle.to_bits(),
+            state.velocity.x.to_bits(), state.velocity.y.to_bits(), state.spin.to_bits()];
+        self.entity == other.entity && self.awake == other.awake && bits(self) == bits(other)
+    }
+}
+
+// The game world without a window or GPU. Runs the same systems as the game, one fixed step per tick,
+// so the result only depends on the scene and the input script
+pub struct HeadlessWorld{
+    pub entity_manager: EntityManager,
+    pub system_manager: SystemManager,
+    pub registry: ComponentRegistry,
+    input: InputScript,
+    tick: u64,
+}
+
+impl HeadlessWorld{
+    // Render components in the scene (e.g. material) are left out
+    pub fn load(scene: &str, input: InputScript, tick_rate: Option<f32>) -> anyhow::Result<Self>{
+        let registry = ComponentRegistry::with_engine_components().map_err(anyhow::Error::msg)?;
+        let mut entity_manager = EntityManager::new();
+        let mut physics_manager = Physics::new();
+        SceneLoader::load(scene, &registry, &mut entity_manager, &mut physics_manager, None, None)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        let mut system_manager = SystemManager::new();
+        crate::system::add_game_systems(&mut system_manager);
+        system_manager.build_schedule().map_err(anyhow::Error::msg)?;
+
+        if let Some(rate) = tick_rate{
+            system_manager.resources.get_mut::<Time>().unwrap().set_tick_rate(rate);
+        }
+        // The camera still follows the player, it's just never drawn
+        let camera = Camera::new(
+            (0.0, 0.0, 10.0).into(),
+            (0.0, 0.0, 0.0).into(),
+            cgmath::Vector3::unit_y(),
+            16.0 / 9.0,
+            45.0,
+            0.1,
+            25.0,
+        );
+        system_manager.resources.insert_main_thread(physics_manager);
+        system_manager.resources.insert(InputManager::new());
+        system_manager.resources.insert(camera);
+
+        log::info!("Headless world loaded from {} with {} entities", scene, entity_manager.entities.len());
+        Ok(Self{
+            entity_manager,
+            system_manager,
+            registry,
+            input,
+            tick: 0,
+        })
+    }
+
+    // Fixed steps run so far
+    pub fn get_tick(&self) -> u64{
+        self.tick
+    }
+
+    // Apply this tick's input, then run exactly one fixed step and one update
+    pub fn step(&mut self){
+        {
+            let mut input_manager = self.system_manager.resources.get_mut::<InputManager>().unwrap();
+            self.input.apply(self.tick, &mut input_manager);
+        }
+        {
+            let mut time = self.system_manager.resources.get_mut::<Time>().unwrap();
+            let fixed_delta_time = time.fixed_delta_time;
+            time.advance(fixed_delta_time);
+        }
+        self.system_manager.update_systems(None, &mut self.entity_manager);
+        self.tick += 1;
+    }
+
+    // Runs the physics in deterministic mode from now on, whatever the scene's physics settings say
+    pub fn set_deterministic(&mut self) -> anyhow::Result<()>{
+        let mut physics = self.system_manager.resources.get_mut::<Physics>().unwrap();
+        let config = physics.get_config().clone().with_deterministic(true);
+        physics.set_config(config).map_err(anyhow::Error::msg)
+    }
+
+    // Every body in entity handle order
+    pub fn body_states(&self) -> Vec<BodyState>{
+        let physics = self.system_manager.resources.get::<Physics>().unwrap();
+        let mut states: Vec<BodyState> = self.entity_manager.query::<&PhysicsComponent>().iter().map(|(entity, physics_component)| {
+            let body = physics.world.body(physics_component.handle);
+            BodyState{
+                entity,
+                position: *body.position(),
+                angle: body.angle(),
+                velocity: *body.linear_velocity(),
+                spin: body.angular_velocity(),
+                awake: body.is_awake(),
+            }
+        }).collect();
+        states.sort_by_key(|state| (state.entity.index, state.entity.generation));
+        states
+    }
+
+    pub fn snapshot(&self) -> PhysicsSnapshot{
+        let physics = self.system_manager.resources.get::<Physics>().unwrap();
+        PhysicsSnapshot::take(&self.entity_manager, &physics)
+    }
+
+    // Rewind the physics to a snapshot taken after `tick` ticks. The input script is replayed up to there,
+    // so keys held at the time are held again
+    pub fn restore(&mut self, snapshot: &PhysicsSnapshot, tick: u64) -> anyhow::Result<()>{
+        {
+            let mut physics = self.system_manager.resources.get_mut::<Physics>().unwrap();
+            snapshot.restore(&self.entity_manager, &mut physics).map_err(anyhow::Error::msg)?;
+        }
+        let mut input_manager = self.system_manager.resources.get_mut::<InputManager>().unwrap();
+        *input_manager = InputManager::new();
+        for t in 0..tick{
+            self.input.apply(t, &mut input_manager);
+        }
+        self.tick = tick;
+        Ok(())
+    }
+
+    // What the physics overlay would draw now with these settings, whether or not they're enabled
+    pub fn debug_lines(&self, debug_draw: &PhysicsDebugDraw) -> Vec<DebugLine>{
+        let mut physics = self.system_manager.resources.get_mut::<Physics>().unwrap();
+        debug_draw.build_lines(&mut physics)
+    }
+
+    // Every entity as a .dbscene entity line, so a dump can be diffed or loaded as a scene again.
+    // The handle and body velocities go in a comment above each entity
+    pub fn dump(&self) -> String{
+        let physics = self.system_manager.resources.get::<Physics>().unwrap();
+        let mut out = String::new();
+        writeln!(out, "// Headless state after {} ticks", self.tick).unwrap();
+        writeln!(out, "version {};", SCENE_FORMAT_VERSION).unwrap();
+        writeln!(out).unwrap();
+        for entity in self.entity_manager.entities.iter(){
+            let mut comment = format!("// entity {}:{}", entity.handle.index, entity.handle.generation);
+            if let Some(physics_component) = self.entity_manager.get_component::<crate::PhysicsComponent>(entity.handle){
+                let body = physics.world.body(physics_component.handle);
+                let velocity = body.linear_velocity();
+                comment.push_str(&format!(" velocity({:?},{:?}) spin({:?})", velocity.x, velocity.y, body.angular_velocity()));
+            }
+            writeln!(out, "{}", comment).unwrap();
+            let description = SceneWriter::describe_entity(&self.registry, &self.entity_manager, &physics, entity);
+            writeln!(out, "{}", SceneWriter::write_entity(&description)).unwrap();
+        }
+        out
+    }
+}
+
+// Load the scene, run it for options.ticks fixed steps and write the final state out.
+// Returns the same text that was written
+pub fn run(options: &HeadlessOptions) -> anyhow::Result<String>{
+    let mut world = HeadlessWorld::load(&options.scene, options.input.clone(), options.tick_rate)?;
+
+    for _ in 0..options.ticks{
+        world.step();
+    }
+    log::info!("Headless run of {} finished after {} ticks", options.scene, world.get_tick());
+
+    let dump = world.dump();
+    match &options.output{
+        Some(path) => {
+            std::fs::write(path, &dump).map_err(|e| anyhow::anyhow!("Can't write {}: {}", path, e))?;
+        },
+        None => print!("{}", dump),
+    }
+    Ok(dump)
+}
+
+// Run the scene twice side by side in deterministic mode with the same input, and check every body is the same
+// down to the bit after every tick. Fails at the first tick where they differ
+pub fn check_determinism(options: &HeadlessOptions) -> anyhow::Result<String>{
+    let mut first = HeadlessWorld::load(&options.scene, options.input.clone(), options.tick_rate)?;
+    let mut second = HeadlessWorld::load(&options.scene, options.input.clone(), options.tick_rate)?;
+    first.set_deterministic()?;
+    second.set_deterministic()?;
+
+    let mut bodies = 0;
+    for _ in 0..options.ticks{
+        first.step();
+        second.step();
+        let (a, b) = (first.body_states(), second.body_states());
+        if a.len() != b.len(){
+            anyhow::bail!("Not deterministic: after tick {} the first run has {} bodies and the second has {}", first.get_tick(), a.len(), b.len());
+        }
+        if let Some((a, b)) = a.iter().zip(b.iter()).find(|(a, b)| !a.same_bits(b)){
+            anyhow::bail!("Not deterministic: after tick {} the runs differ\n  first:  {:?}\n  second: {:?}", first.get_tick(), a, b);
+        }
+        bodies = a.len();
+    }
+    let summary = format!("Deterministic: {} bodies identical in both runs of {} after {} ticks", bodies, options.scene, first.get_tick());
+    log::info!("{}", summary);
+    Ok(summary)
+}
+
+// Run the scene in deterministic mode to the halfway tick, snapshot it and run on to the end. A second run gets to the halfway tick too,
+// lets its physics run ahead on their own for the rest of the ticks, is rewound to the snapshot and plays the second half again.
+// Every body has to match the first run down to the bit after every tick. Game state outside the physics (Like the player position
+// the enemies chase) is the same in both runs, so any difference is the restore's. The snapshot is written out and read back
+// before it's restored, so saving is checked too
+pub fn check_snapshot(options: &HeadlessOptions) -> anyhow::Result<String>{
+    let mut first = HeadlessWorld::load(&options.scene, options.input.clone(), options.tick_rate)?;
+    let mut second = HeadlessWorld::load(&options.scene, options.input.clone(), options.tick_rate)?;
+    first.set_deterministic()?;
+    second.set_deterministic()?;
+    let rewind_to = options.ticks / 2;
+    for _ in 0..rewind_to{
+        first.step();
+        second.step();
+    }
+
+    let source = first.snapshot().to_source();
+    let snapshot = PhysicsSnapshot::parse(&source).map_err(|e| anyhow::anyhow!("The snapshot doesn't read back: {}", e))?;
+    if snapshot.to_source() != source{
+        anyhow::bail!("The snapshot after tick {} doesn't read back to the same thing", rewind_to);
+    }
+    let mut expected = Vec::<Vec<BodyState>>::new();
+    for _ in rewind_to..options.ticks{
+        first.step();
+        expected.push(first.body_states());
+    }
+
+    {
+        let mut physics = second.system_manager.resources.get_mut::<Physics>().unwrap();
+        let fixed_delta_time = second.system_manager.resources.get::<Time>().unwrap().fixed_delta_time;
+        for _ in rewind_to..options.ticks{
+            physics.step(fixed_delta_time);
+        }
+    }
+    second.restore(&snapshot, rewind_to)?;
+    let hint = {
+        let physics = second.system_manager.resources.get::<Physics>().unwrap();
+        if physics.get_config().warm_starting && snapshot.bodies.iter().any(|body| !body.joints.is_empty()){
+            "\n  Joints can't be rewound exactly with warm starting, try warm_starting(false) in the scene's physics settings"
+        }else if physics.get_config().allow_sleep && snapshot.bodies.iter().any(|body| body.allow_sleep && body.body_type == b2::BodyType::Dynamic){
+            "\n  How long bodies have been still for can't be rewound, try sleep(false) in the scene's physics settings"
+        }else{
+            ""
+        }
+    };
+    for states in expected.iter(){
+        second.step();
+        let replayed = second.body_states();
+        if replayed.len() != states.len(){
+            anyhow::bail!("Restore doesn't match: after tick {} the first run has {} bodies and the rewound one has {}", second.get_tick(), states.len(), replayed.len());
+        }
+        if let Some((a, b)) = states.iter().zip(replayed.iter()).find(|(a, b)| !a.same_bits(b)){
+            anyhow::bail!("Restore doesn't match: after tick {} the runs differ\n  first:   {:?}\n  rewound: {:?}{}", second.get_tick(), a, b, hint);
+        }
+    }
+    let summary = format!("Snapshot restored: {} bodies of {} identical for {} ticks after rewinding to tick {}",
+        snapshot.bodies.len(), options.scene, options.ticks - rewind_to, rewind_to);
+    log::info!("{}", summary);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{CollisionEvent, CollisionEvents};
+    use crate::physics::layers;
+    use crate::QueryFilter;
+    use crate::physics::debug_draw::{CONTACT_POINT_COLOR, CONTACT_NORMAL_COLOR};
+
+    // Two worlds from the same scene, stepped side by side and compared after every tick
+    fn run_twice(scene: &str, ticks: u64) -> Vec<BodyState>{
+        let mut first = HeadlessWorld::load(scene, InputScript::new(), None).unwrap();
+        let mut second = HeadlessWorld::load(scene, InputScript::new(), None).unwrap();
+        first.set_deterministic().unwrap();
+        second.set_deterministic().unwrap();
+        for _ in 0..ticks{
+            first.step();
+            second.step();
+            let (a, b) = (first.body_states(), second.body_states());
+            assert_eq!(a.len(), b.len());
+            for (a, b) in a.iter().zip(b.iter()){
+                assert!(a.same_bits(b), "after tick {}\n  first:  {:?}\n  second: {:?}", first.get_tick(), a, b);
+            }
+        }
+        first.body_states()
+    }
+
+    // Writes the scene to its own temp file (Tests run in parallel) and loads it
+    fn load_scene(file: &str, source: &str) -> HeadlessWorld{
+        let path = std::env::temp_dir().join(file);
+        std::fs::write(&path, source).unwrap();
+        let world = HeadlessWorld::load(path.to_str().unwrap(), InputScript::new(), None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        world
+    }
+
+    fn entity(world: &HeadlessWorld, name: &str) -> EntityHandle{
+        world.entity_manager.find_by_name(name).unwrap().handle
+    }
+
+    fn frame_events(world: &HeadlessWorld) -> Vec<CollisionEvent>{
+        world.system_manager.resources.get::<CollisionEvents>().unwrap().iter_frame().cloned().collect()
+    }
+
+    #[test]
+    fn landing_starts_and_leaving_ends_a_collision(){
+        let mut world = load_scene("knock_the_enemy_collision_events.dbscene", r#"version 1;
+entity[ name("Floor") pos(0.0, 0.0, 0.0) physics(static, 0.0, 10.0, 0.5, ground, false) ];
+entity[ name("Box") pos(0.0, 3.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, false) ];
+entity[ name("Other") pos(4.0, 3.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, false) ];
+"#);
+        let (floor, falling, other) = (entity(&world, "Floor"), entity(&world, "Box"), entity(&world, "Other"));
+
+        let mut events = Vec::<CollisionEvent>::new();
+        for _ in 0..120{
+            world.step();
+            events = frame_events(&world);
+            if !events.is_empty(){
+                break;
+            }
+        }
+        // Both boxes land on the same step, each with its own collision
+        assert_eq!(events.len(), 2, "{:?}", events);
+        for entity in [falling, other].iter(){
+            let collision = match events.iter().find(|event| event.collision().involves(*entity)){
+                Some(CollisionEvent::CollisionStarted(collision)) => collision,
+                other => panic!("expected {:?} to land, got {:?}", entity, other),
+            };
+            assert_eq!(collision.other(*entity), Some(floor), "{:?}", collision);
+            // Points up from the floor into the box
+            let normal = collision.normal_from(floor);
+            assert!(normal.y > 0.99 && normal.x.abs() < 0.01, "{:?}", normal);
+            assert!(collision.impulse > 0.0, "{:?}", collision);
+            assert!(!collision.is_sensor);
+        }
+
+        // Resting on the floor isn't reported again
+        for _ in 0..10{
+            world.step();
+            assert!(frame_events(&world).is_empty(), "tick {}: {:?}", world.get_tick(), frame_events(&world));
+        }
+
+        {
+            let mut physics = world.system_manager.resources.get_mut::<Physics>().unwrap();
+            let mut component = world.entity_manager.get_component_mut::<PhysicsComponent>(falling).unwrap();
+            component.set_transform(&mut physics, b2::Vec2 { x: 0.0, y: 5.0 }, 0.0);
+        }
+        world.step();
+        let events = frame_events(&world);
+        assert_eq!(events.len(), 1, "{:?}", events);
+        match events[0]{
+            CollisionEvent::CollisionEnded(collision) => {
+                assert!(collision.involves(floor) && collision.involves(falling));
+                assert_eq!(collision.impulse, 0.0);
+            },
+            other => panic!("expected the collision to end, got {:?}", other),
+        }
+        world.step();
+        assert!(frame_events(&world).is_empty());
+    }
+
+    fn body_y(world: &HeadlessWorld, entity: EntityHandle) -> f32{
+        let physics = world.system_manager.resources.get::<Physics>().unwrap();
+        world.entity_manager.get_component::<PhysicsComponent>(entity).unwrap().get_position(&physics).y
+    }
+
+    #[test]
+    fn ignored_layers_pass_through_each_other(){
+        let mut world = load_scene("knock_the_enemy_ignore_layers.dbscene", r#"version 1;
+collision[ ignore(enemy, enemy) ];
+entity[ name("Floor") pos(0.0, 0.0, 0.0) physics(static, 0.0, 10.0, 0.5, ground, false) ];
+entity[ name("Lower") pos(0.0, 1.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, false) ];
+entity[ name("Upper") pos(0.0, 3.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, false) ];
+"#);
+        let (lower, upper) = (entity(&world, "Lower"), entity(&world, "Upper"));
+        {
+            let physics = world.system_manager.resources.get::<Physics>().unwrap();
+            let handle = world.entity_manager.get_component::<PhysicsComponent>(upper).unwrap().handle;
+            for (_, fixture) in physics.world.body(handle).fixtures(){
+                let filter = fixture.borrow().filter_data().clone();
+                assert_eq!(filter.category_bits, 1 << layers::ENEMY);
+                assert_eq!(filter.mask_bits & (1 << layers::ENEMY), 0);
+                assert_ne!(filter.mask_bits & (1 << layers::GROUND), 0);
+            }
+        }
+        for _ in 0..180{
+            world.step();
+            assert!(!frame_events(&world).iter().any(|event| event.collision().involves(lower) && event.collision().involves(upper)));
+        }
+        // The upper box fell through the lower one, both rest on the floor
+        assert!((body_y(&world, lower) - 1.0).abs() < 0.05, "{}", body_y(&world, lower));
+        assert!((body_y(&world, upper) - 1.0).abs() < 0.05, "{}", body_y(&world, upper));
+
+        // collide(enemy, enemy) lets them stack again
+        {
+            let mut physics = world.system_manager.resources.get_mut::<Physics>().unwrap();
+            let mut collision_layers = physics.get_collision_layers().clone();
+            collision_layers.set_collides(layers::ENEMY, layers::ENEMY, true);
+            physics.set_collision_layers(collision_layers);
+            let mut component = world.entity_manager.get_component_mut::<PhysicsComponent>(upper).unwrap();
+            component.set_transform(&mut physics, b2::Vec2 { x: 0.0, y: 3.0 }, 0.0);
+        }
+        for _ in 0..180{
+            world.step();
+        }
+        assert!((body_y(&world, lower) - 1.0).abs() < 0.05, "{}", body_y(&world, lower));
+        assert!((body_y(&world, upper) - 2.0).abs() < 0.05, "{}", body_y(&world, upper));
+    }
+
+    fn close(a: b2::Vec2, b: b2::Vec2) -> bool{
+        (a.x - b.x).abs() < 0.02 && (a.y - b.y).abs() < 0.02
+    }
+
+    #[test]
+    fn queries_find_the_ground_and_the_box(){
+        let mut world = load_scene("knock_the_enemy_queries.dbscene", r#"version 1;
+entity[ name("Floor") pos(0.0, 0.0, 0.0) physics(static, 0.0, 10.0, 0.5, ground, false) ];
+entity[ name("Box") pos(0.0, 3.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, player, false) ];
+entity[ name("Trigger") pos(6.0, 3.0, 0.0) physics{ body(static) layer(enemy) fixture(box(1.0, 1.0)){ sensor(true) } } ];
+"#);
+        // Let the box settle on the floor, resting between y 0.5 and 1.5
+        for _ in 0..120{
+            world.step();
+        }
+        let (floor, falling, trigger) = (entity(&world, "Floor"), entity(&world, "Box"), entity(&world, "Trigger"));
+        let physics = world.system_manager.resources.get::<Physics>().unwrap();
+        let all = QueryFilter::new();
+        let (above, below) = (b2::Vec2 { x: 0.0, y: 5.0 }, b2::Vec2 { x: 0.0, y: -5.0 });
+
+        // Closest and all, nearest first
+        let hit = physics.raycast_closest(above, below, &all).unwrap();
+        assert_eq!(hit.entity, falling);
+        assert!(close(hit.point, b2::Vec2 { x: 0.0, y: 1.5 }), "{:?}", hit);
+        assert!(close(hit.normal, b2::Vec2 { x: 0.0, y: 1.0 }), "{:?}", hit);
+        assert!((hit.fraction - 0.35).abs() < 0.01, "{:?}", hit);
+        let hits = physics.raycast_all(above, below, &all);
+        assert_eq!(hits.iter().map(|h| h.entity).collect::<Vec<EntityHandle>>(), vec!(falling, floor));
+        assert!(hits[0].fraction < hits[1].fraction);
+        assert!(close(hits[1].point, b2::Vec2 { x: 0.0, y: 0.5 }), "{:?}", hits[1]);
+        assert!(physics.raycast_any(above, below, &all).is_some());
+        assert!(physics.raycast_any(above, b2::Vec2 { x: 0.0, y: 2.0 }, &all).is_none());
+
+        // Layers, the entity asking and sensors
+        assert_eq!(physics.raycast_closest(above, below, &QueryFilter::new().ignoring(falling)).unwrap().entity, floor);
+        assert_eq!(physics.raycast_closest(above, below, &QueryFilter::new().without_layer(layers::PLAYER)).unwrap().entity, floor);
+        assert!(physics.raycast_closest(above, below, &QueryFilter::only(&[layers::ENEMY])).is_none());
+        let (over_trigger, under_trigger) = (b2::Vec2 { x: 6.0, y: 5.0 }, b2::Vec2 { x: 6.0, y: -5.0 });
+        assert_eq!(physics.raycast_closest(over_trigger, under_trigger, &all).unwrap().entity, floor);
+        assert_eq!(physics.raycast_closest(over_trigger, under_trigger, &QueryFilter::new().with_sensors(true)).unwrap().entity, trigger);
+
+        // A zero length ray hits nothing instead of tripping Box2D's assert
+        let inside = b2::Vec2 { x: 0.0, y: 1.0 };
+        assert!(physics.raycast_closest(inside, inside, &all).is_none());
+        assert!(physics.raycast_any(inside, inside, &all).is_none());
+        assert!(physics.raycast_all(inside, inside, &all).is_empty());
+
+        // Boxes only check bounding boxes, so the corner of the box counts for the box query but not the circle
+        let (lower, upper) = (b2::Vec2 { x: 0.45, y: 1.45 }, b2::Vec2 { x: 0.95, y: 1.95 });
+        assert_eq!(physics.query_aabb(lower, upper, &all), vec!(falling));
+        assert!(physics.overlap_circle(b2::Vec2 { x: 0.7, y: 1.7 }, 0.25, &all).is_empty());
+        assert_eq!(physics.overlap_circle(b2::Vec2 { x: 0.0, y: 1.0 }, 0.1, &all), vec!(falling));
+        let mut around = physics.query_aabb(b2::Vec2 { x: -1.0, y: 0.0 }, b2::Vec2 { x: 1.0, y: 2.0 }, &all);
+        around.sort_by_key(|e| e.index);
+        assert_eq!(around, vec!(floor, falling));
+        assert_eq!(physics.query_aabb(b2::Vec2 { x: -1.0, y: 0.0 }, b2::Vec2 { x: 1.0, y: 2.0 }, &QueryFilter::new().ignoring(falling)), vec!(floor));
+        assert!(physics.query_aabb(b2::Vec2 { x: 5.5, y: 2.5 }, b2::Vec2 { x: 6.5, y: 3.5 }, &all).is_empty());
+        assert_eq!(physics.query_aabb(b2::Vec2 { x: 5.5, y: 2.5 }, b2::Vec2 { x: 6.5, y: 3.5 }, &QueryFilter::new().with_sensors(true)), vec!(trigger));
+        let probe = b2::PolygonShape::new_box(0.2, 0.2);
+        assert_eq!(physics.overlap_shape(&probe, b2::Vec2 { x: 3.0, y: 0.5 }, 0.5, &all), vec!(floor));
+        assert!(physics.overlap_shape(&probe, b2::Vec2 { x: 3.0, y: 1.5 }, 0.0, &all).is_empty());
+
+        // Sweeping a circle sideways into the box, and a box down onto the floor
+        let circle = b2::CircleShape::new_with(b2::Vec2 { x: 0.0, y: 0.0 }, 0.25);
+        let hit = physics.shape_cast(&circle, b2::Vec2 { x: -3.0, y: 1.0 }, b2::Vec2 { x: 3.0, y: 1.0 }, 0.0, &all).unwrap();
+        assert_eq!(hit.entity, falling);
+        assert!(close(hit.position, b2::Vec2 { x: -0.75, y: 1.0 }), "{:?}", hit);
+        assert!(close(hit.normal, b2::Vec2 { x: -1.0, y: 0.0 }), "{:?}", hit);
+        assert!((hit.fraction - 0.375).abs() < 0.01, "{:?}", hit);
+        assert_eq!(physics.circle_cast(b2::Vec2 { x: -3.0, y: 1.0 }, b2::Vec2 { x: 3.0, y: 1.0 }, 0.25, &all), Some(hit));
+        let hit = physics.box_cast(b2::Vec2 { x: 3.0, y: 5.0 }, b2::Vec2 { x: 3.0, y: -5.0 }, 0.25, 0.25, 0.0, &all).unwrap();
+        assert_eq!(hit.entity, floor);
+        assert!(close(hit.position, b2::Vec2 { x: 3.0, y: 0.75 }), "{:?}", hit);
+        assert!(close(hit.normal, b2::Vec2 { x: 0.0, y: 1.0 }), "{:?}", hit);
+        assert!(physics.circle_cast(b2::Vec2 { x: -3.0, y: 1.0 }, b2::Vec2 { x: 3.0, y: 1.0 }, 0.25, &QueryFilter::new().ignoring(falling)).is_none());
+    }
+
+    fn has_line(lines: &[DebugLine], from: b2::Vec2, to: b2::Vec2) -> bool{
+        let at = |point: cgmath::Vector3::<f32>, expected: b2::Vec2| close(b2::Vec2 { x: point.x, y: point.y }, expected);
+        lines.iter().any(|line| (at(line.from, from) && at(line.to, to)) || (at(line.from, to) && at(line.to, from)))
+    }
+
+    #[test]
+    fn debug_lines_show_shapes_contacts_and_joints(){
+        let mut world = load_scene("knock_the_enemy_debug_lines.dbscene", r#"version 1;
+entity[ name("Floor") pos(0.0, 0.0, 0.0) physics(static, 0.0, 10.0, 0.5, ground, false) ];
+entity[ name("Box") pos(0.0, 1.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, false) ];
+entity[ name("Post") pos(5.0, 5.0, 0.0) physics(static, 0.0, 1.0, 0.5, ground, false) ];
+entity[ name("Hanging") pos(5.0, 3.0, 0.0) physics(dynamic, 1.0, 0.25, 0.25, enemy, false) joints{ distance("Post") } ];
+"#);
+        for _ in 0..30{
+            world.step();
+        }
+        let only = |shapes: bool, contacts: bool, joints: bool| {
+            let mut debug_draw = PhysicsDebugDraw::new();
+            debug_draw.shapes = shapes;
+            debug_draw.contacts = contacts;
+            debug_draw.joints = joints;
+            debug_draw
+        };
+
+        // The post's outline, in Box2D's static body color
+        let lines = world.debug_lines(&only(true, false, false));
+        let corners = [b2::Vec2 { x: 4.0, y: 4.5 }, b2::Vec2 { x: 6.0, y: 4.5 }, b2::Vec2 { x: 6.0, y: 5.5 }, b2::Vec2 { x: 4.0, y: 5.5 }];
+        for i in 0..4{
+            assert!(has_line(&lines, corners[i], corners[(i + 1) % 4]), "no edge {:?} to {:?} in {:?}", corners[i], corners[(i + 1) % 4], lines);
+        }
+        let post_edges = lines.iter().filter(|line| line.from.x >= 3.99 && line.from.y >= 4.49 && line.to.x >= 3.99 && line.to.y >= 4.49).collect::<Vec<&DebugLine>>();
+        assert_eq!(post_edges.len(), 4);
+        assert!(post_edges.iter().all(|line| line.color == [0.5, 0.9, 0.5]), "{:?}", post_edges);
+        // Four bodies of one box each
+        assert_eq!(lines.len(), 16);
+
+        // The box rests on two contact points, each a cross and a normal
+        let lines = world.debug_lines(&only(false, true, false));
+        assert_eq!(lines.len(), 6, "{:?}", lines);
+        let normals: Vec<&DebugLine> = lines.iter().filter(|line| line.color == CONTACT_NORMAL_COLOR).collect();
+        assert_eq!(normals.len(), 2);
+        for normal in normals.iter(){
+            assert!((normal.from.y - 0.5).abs() < 0.02, "{:?}", normal);
+            assert!(((normal.to.y - normal.from.y).abs() - 0.4).abs() < 0.01 && (normal.to.x - normal.from.x).abs() < 0.01, "{:?}", normal);
+        }
+        assert_eq!(lines.iter().filter(|line| line.color == CONTACT_POINT_COLOR).count(), 4);
+
+        // The distance joint goes from the hanging box's origin to the post's
+        let lines = world.debug_lines(&only(false, false, true));
+        let hanging = {
+            let physics = world.system_manager.resources.get::<Physics>().unwrap();
+            world.entity_manager.get_component::<PhysicsComponent>(entity(&world, "Hanging")).unwrap().get_position(&physics)
+        };
+        assert_eq!(lines.len(), 1, "{:?}", lines);
+        assert!(has_line(&lines, hanging, b2::Vec2 { x: 5.0, y: 5.0 }), "{:?} {:?}", hanging, lines);
+        assert_eq!(lines[0].color, [0.5, 0.8, 0.8]);
+
+        // Everything at once
+        assert_eq!(world.debug_lines(&PhysicsDebugDraw::new()).len(), 16 + 6 + 1);
+    }
+
+    #[test]
+    fn game_scene_runs_the_same_twice(){
+        let states = run_twice("data/scene/scene.dbscene", 300);
+        assert!(!states.is_empty());
+    }
+
+    #[test]
+    fn piles_fall_asleep_the_same_way_twice(){
+        let path = std::env::temp_dir().join("knock_the_enemy_sleep_test.dbscene");
+        std::fs::write(&path, r#"version 1;
+entity[ name("Floor") pos(0.0, 0.0, 0.0) physics(static, 0.0, 10.0, 0.5, ground, false) ];
+entity[ name("Bottom") pos(0.0, 1.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, true) ];
+entity[ name("Top") pos(0.2, 2.5, 0.0) rot(0.0, 0.0, 20.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, true) ];
+"#).unwrap();
+        let states = run_twice(path.to_str().unwrap(), 600);
+        std::fs::remove_file(&path).unwrap();
+        // Box2D's island sleeping put the whole pile to sleep
+        assert!(states.iter().all(|state| !state.awake), "{:?}", states);
+    }
+}
@@ -42,6 +42,11 @@ impl InputManager{
         }
     }
 
+    // Press or release a key without a window event, used by scripted input
+    pub fn set_key(&mut self, key: VirtualKeyCode, state: ElementState){
+        self.keys.insert(key, state);
+    }
+
     pub fn get_key_value(&self, key: VirtualKeyCode) -> ElementState{
         self.keys[&key]
     }
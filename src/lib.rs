@@ -0,0 +1,71 @@
+// The engine as a library, so the game binary, headless runs and tools can share it.
+// Everything used across modules is re-exported here, modules import it from crate::
+
+
+pub use wrapped2d::b2;
+pub use wrapped2d::user_data::NoUserData;
+
+
+pub mod renderer;
+pub mod component;
+pub mod transform;
+pub mod input_manager;
+pub mod entity;
+pub mod system;
+pub mod physics;
+pub mod audio;
+pub mod scene;
+pub mod headless;
+
+pub use renderer::renderer::Renderer;
+pub use renderer::vertex::Vertex;
+pub use renderer::debug_lines::DebugVertex;
+pub use renderer::texture::{Texture, DepthTexture, TextureMode};
+pub use renderer::material::{Material, MaterialUniform};
+pub use renderer::postprocessing::{PostProcessing, BloomUniform};
+pub use input_manager::input_manager::InputManager;
+pub use entity::rendermesh::RenderMesh;
+pub use entity::entity::Entity;
+pub use entity::entitymanager::EntityManager;
+pub use entity::handle::{EntityHandle, DespawnQueue};
+pub use renderer::camera::camera::{Camera};
+pub use renderer::camera::camerabuffer::{CameraBuffer, CameraUniform};
+pub use renderer::transform_sync::{TransformBuffer, TransformSync, TransformUniform};
+pub use renderer::camera::cameracontroller::CameraController;
+pub use renderer::uniforms::{UniformUtils, UniformBuffer};
+pub use renderer::uniforms::base_uniforms::BaseUniforms;
+pub use component::ComponentBase;
+pub use transform::{Translation, Rotation, NonUniformScale, Transform, Parent, Children, TransformHierarchy};
+pub use system::{SystemBase, SystemContext};
+pub use system::access::SystemAccess;
+pub use system::resources::{Resources, SystemResources};
+pub use system::time::Time;
+pub use system::movement_system::MovementSystem;
+pub use system::player_movement_system::PlayerMovementSystem;
+pub use system::systemmanager::SystemManager;
+pub use system::physics_system::PhysicsSystem;
+pub use system::camera_follow_system::CameraFollowSystem;
+pub use system::transform_composition_system::TransformCompositionSystem;
+pub use system::transform_propagation_system::TransformPropagationSystem;
+pub use scene::{SceneLoader, SceneWatcher, SceneWriter, ComponentRegistry};
+pub use component::movement_component::MovementComponent;
+pub use component::player_movement_component::PlayerMovementComponent;
+pub use physics::physicscomponent::PhysicsComponent;
+pub use physics::fixture::{PhysicsFixture, ShapeKind};
+pub use physics::joint::{PhysicsJoint, JointKind};
+pub use physics::jointcomponent::{JointComponent, JointLink};
+pub use physics::collision::{Collision, CollisionEvent, CollisionEvents};
+pub use physics::layers::CollisionLayers;
+pub use physics::config::PhysicsConfig;
+pub use physics::debug_draw::{DebugLine, PhysicsDebugDraw};
+pub use physics::snapshot::{PhysicsSnapshot, BodySnapshot, JointSnapshot, ContactSnapshot};
+pub use physics::query::{QueryFilter, RaycastHit, ShapeCastHit};
+pub use physics::{Physics, PhysicsFilter, LayerType, BodyData};
+pub use audio::{Audio};
+pub use headless::{HeadlessOptions, InputScript};
+pub type World = b2::World<PhysicsFilter>;
+
+
+pub use std::rc::Rc;
+pub use std::sync::Arc;
+pub use std::cell::RefCell;
@@ -21,47 +21,7 @@ use log4rs::{
     encode::pattern::PatternEncoder,
 };
 
-use wrapped2d::b2;
-use wrapped2d::user_data::NoUserData;
-
-
-mod renderer;
-mod component;
-mod transform;
-mod input_manager;
-mod entity;
-mod system;
-mod physics;
-mod audio;
-mod scene;
-
-use renderer::renderer::Renderer;
-use renderer::vertex::Vertex;
-use renderer::texture::{Texture, DepthTexture, TextureMode};
-use renderer::material::{Material, MaterialUniform};
-use renderer::postprocessing::{PostProcessing, BloomUniform};
-use input_manager::input_manager::InputManager;
-use entity::rendermesh::RenderMesh;
-use entity::entity::Entity;
-use entity::entitymanager::EntityManager;
-use renderer::camera::camera::{Camera, CameraUniform};
-use renderer::camera::cameracontroller::CameraController;
-use renderer::uniforms::{UniformUtils, UniformBuffer};
-use renderer::uniforms::base_uniforms::BaseUniforms;
-use component::ComponentBase;
-use transform::{Translation, Rotation, NonUniformScale, Transform, TransformUniform};
-use system::SystemBase;
-use system::movement_system::MovementSystem;
-use system::player_movement_system::PlayerMovementSystem;
-use system::systemmanager::SystemManager;
-use system::physics_system::PhysicsSystem;
-use scene::SceneLoader;
-use component::movement_component::MovementComponent;
-use component::player_movement_component::PlayerMovementComponent;
-use physics::physicscomponent::PhysicsComponent;
-use physics::{Physics, PhysicsFilter, LayerType};
-use audio::{Audio};
-type World = b2::World<PhysicsFilter>;
+use knock_the_enemy::*;
 
 
 use std::rc::Rc;
@@ -102,6 +62,48 @@ pub fn main() {
                                         .help("Set the screen mode: [full, borderless, windowed]")
                                         .takes_value(true)
                                         .value_name("SCREENMODE"))
+                          .arg(Arg::with_name("scene")
+                                        .long("scene")
+                                        .help("Scene file to load. It is reloaded whenever it changes")
+                                        .takes_value(true)
+                                        .value_name("SCENE"))
+                          .arg(Arg::with_name("tickrate")
+                                        .long("tick-rate")
+                                        .help("Fixed simulation steps per second, independent of the framerate")
+                                        .takes_value(true)
+                                        .value_name("HZ"))
+                          .arg(Arg::with_name("maxsteps")
+                                        .long("max-catch-up")
+                                        .help("Most fixed steps simulated in one frame before falling behind")
+                                        .takes_value(true)
+                                        .value_name("STEPS"))
+                          .arg(Arg::with_name("headless")
+                                        .long("headless")
+                                        .help("Run the scene without a window or GPU for --ticks fixed steps, then print every entity's state"))
+                          .arg(Arg::with_name("ticks")
+                                        .long("ticks")
+                                        .help("Fixed steps a headless run simulates")
+                                        .takes_value(true)
+                                        .value_name("TICKS"))
+                          .arg(Arg::with_name("input")
+                                        .long("input")
+                                        .help("Input script for a headless run, one \"tick press|release key\" per line")
+                                        .takes_value(true)
+                                        .value_name("SCRIPT"))
+                          .arg(Arg::with_name("output")
+                                        .long("output")
+                                        .help("File a headless run writes the final state to, instead of stdout")
+                                        .takes_value(true)
+                                        .value_name("FILE"))
+                          .arg(Arg::with_name("debug-physics")
+                                        .long("debug-physics")
+                                        .help("Start with the physics debug overlay shown. F3 toggles it"))
+                          .arg(Arg::with_name("check-determinism")
+                                        .long("check-determinism")
+                                        .help("With --headless, run the scene twice in deterministic mode and fail if the bodies ever differ"))
+                          .arg(Arg::with_name("check-snapshot")
+                                        .long("check-snapshot")
+                                        .help("With --headless, snapshot the physics halfway, rewind to it at the end and fail if the replay differs"))
                           .get_matches();
 
     let backend = matches.value_of("backend").unwrap_or("primary");
@@ -191,6 +193,34 @@ pub fn main() {
     log::info!("Vsync Mode: {:?}", vsync_mode);
     
 
+    // Before anything else is printed, so stdout only has the state dump
+    if matches.is_present("headless"){
+        let scene = matches.value_of("scene").unwrap_or("./data/scene/scene.dbscene");
+        let ticks = matches.value_of("ticks").unwrap_or("600").parse().expect("--ticks expects a whole number");
+        let mut options = HeadlessOptions::new(scene, ticks);
+        if let Some(rate) = matches.value_of("tickrate"){
+            options.tick_rate = Some(rate.parse().expect("--tick-rate expects a number"));
+        }
+        if let Some(path) = matches.value_of("input"){
+            options.input = InputScript::from_file(path).unwrap_or_else(|e| { eprintln!("{}", e); std::process::exit(1) });
+        }
+        options.output = matches.value_of("output").map(|path| path.to_string());
+
+        let result = if matches.is_present("check-determinism"){
+            headless::check_determinism(&options).map(|summary| println!("{}", summary))
+        }else if matches.is_present("check-snapshot"){
+            headless::check_snapshot(&options).map(|summary| println!("{}", summary))
+        }else{
+            headless::run(&options).map(|_| ())
+        };
+        if let Err(e) = result{
+            eprintln!("{}", e);
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     if cfg!(debug_assertions) {
         println!("RUNNING: Debug");
         log::info!("App is running in debug mode");
@@ -279,19 +309,27 @@ pub fn main() {
     /* User Defined */
     let mut system_manager = SystemManager::new();
     let mut entity_manager = EntityManager::new();
-    let mut input_manager = InputManager::new();
+    let input_manager = InputManager::new();
     let mut physics_manager = Physics::new();
+    // Fails if two component types claim the same id or scene keyword
+    let component_registry = ComponentRegistry::with_engine_components().expect("Invalid component registry");
 
-    let movement_system = MovementSystem::new();
-    let player_movement_system = PlayerMovementSystem::new();
-    let physics_system = PhysicsSystem::new();
+    system::add_game_systems(&mut system_manager);
 
     log::info!("Systems successfully initialized");
 
+    system_manager.build_schedule().expect("Invalid system ordering");
 
-    system_manager.add_system(Box::new(movement_system));
-    system_manager.add_system(Box::new(player_movement_system));
-    system_manager.add_system(Box::new(physics_system));
+    {
+        let mut time = system_manager.resources.get_mut::<Time>().unwrap();
+        if let Some(rate) = matches.value_of("tickrate"){
+            time.set_tick_rate(rate.parse().expect("--tick-rate expects a number"));
+        }
+        if let Some(steps) = matches.value_of("maxsteps"){
+            time.max_fixed_steps = steps.parse().expect("--max-catch-up expects a whole number");
+        }
+        log::info!("Simulation tick rate: {}Hz, at most {} steps per frame", time.get_tick_rate(), time.max_fixed_steps);
+    }
 
 
     // Since we share the renderer around, borrow it mutably
@@ -303,7 +341,6 @@ pub fn main() {
     let sc_desc = &temp_renderer.sc_desc;
 
     let mut camera = Camera::new(
-        &temp_renderer,
         // position the camera one unit up and 2 units back
         // +z is out of the screen
         (0.0, 0.0, 10.0).into(),
@@ -317,17 +354,17 @@ pub fn main() {
         25.0,
     );
 
-    let (camera_bind_group, camera_layout, mut cam_uniform) = camera.create_uniforms(&temp_renderer);
-    let camera_bind_group = Rc::new(camera_bind_group);
+    let (mut camera_buffer, camera_bind_group, camera_layout) = CameraBuffer::new(&temp_renderer);
+    let camera_bind_group = Arc::new(camera_bind_group);
 
 
     // load textures (Define the texture layout)
     let texture_layout = Texture::generate_texture_layout(&temp_renderer);
 
-    let white_texture = Rc::new(Texture::load_texture(&temp_renderer, "./data/textures/white.png", TextureMode::RGB).unwrap());
-    let player_tex = Rc::new(Texture::load_texture(&temp_renderer, "./data/textures/player.png", TextureMode::RGBA).unwrap());
-    let derp_texture = Rc::new(Texture::load_texture(&temp_renderer, "./data/textures/derp.png", TextureMode::RGBA).unwrap());
-    let pepe_texture = Rc::new(Texture::load_texture(&temp_renderer, "./data/textures/pepe.png", TextureMode::RGBA).unwrap());
+    let white_texture = Arc::new(Texture::load_texture(&temp_renderer, "./data/textures/white.png", TextureMode::RGB).unwrap());
+    let player_tex = Arc::new(Texture::load_texture(&temp_renderer, "./data/textures/player.png", TextureMode::RGBA).unwrap());
+    let derp_texture = Arc::new(Texture::load_texture(&temp_renderer, "./data/textures/derp.png", TextureMode::RGBA).unwrap());
+    let pepe_texture = Arc::new(Texture::load_texture(&temp_renderer, "./data/textures/pepe.png", TextureMode::RGBA).unwrap());
     
     // Create transform layout
     let transform_layout = UniformUtils::create_bind_group_layout(&temp_renderer, 0, wgpu::ShaderStage::VERTEX, Some("Transform"));
@@ -343,11 +380,11 @@ pub fn main() {
 
 
 
-    /*let mut uniforms = Vec::<Rc<wgpu::BindGroup>>::new();
+    /*let mut uniforms = Vec::<Arc<wgpu::BindGroup>>::new();
     let mut components = Vec::<Box<dyn ComponentBase>>::new();
 
     // create material - lower depth is sorted higher
-    let material = Material::new(&temp_renderer, Rc::clone(&player_tex), cgmath::Vector3::<f32> { x: 0.0, y: 500.0, z: 500.0 }, 1.0, 0.0, 0, "main".to_string());
+    let material = Material::new(&temp_renderer, Arc::clone(&player_tex), cgmath::Vector3::<f32> { x: 0.0, y: 500.0, z: 500.0 }, 1.0, 0.0, 0, "main".to_string());
 
     // create new mesh (TODO - mesh loading) and assign material
     let mut mesh = RenderMesh::new(&temp_renderer, material);
@@ -364,19 +401,20 @@ pub fn main() {
 
     let scale = NonUniformScale::new(cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0});
 
-    let mut transform = Transform::new(&temp_renderer, translation.value, rotation.value, scale.value);
-    let (transform_group, _, _) = transform.create_uniforms(&temp_renderer);
-    let transform_group = Rc::new(transform_group);
+    let transform = Transform::new(translation.value, rotation.value, scale.value);
+    let (transform_buffer, transform_group) = TransformBuffer::new(&temp_renderer, &transform);
+    let transform_group = Arc::new(transform_group);
     let pmc = PlayerMovementComponent::new(15.0);
-    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.position, (0.2, 1.0), 1.0, b2::BodyType::Dynamic, 1, false);
+    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.get_position(), (0.2, 1.0), 1.0, b2::BodyType::Dynamic, physics::layers::PLAYER, false);
 
 
-    uniforms.push(Rc::clone(&camera_bind_group));
-    uniforms.push(Rc::new(material_group));
-    uniforms.push(Rc::clone(&transform_group));
+    uniforms.push(Arc::clone(&camera_bind_group));
+    uniforms.push(Arc::new(material_group));
+    uniforms.push(Arc::clone(&transform_group));
 
     components.push(Box::new(mesh));
     components.push(Box::new(transform));
+    components.push(Box::new(transform_buffer));
     components.push(Box::new(pmc));
     components.push(Box::new(phs_comp));
 
@@ -389,16 +427,16 @@ pub fn main() {
 
 
 
-    let mut uniforms = Vec::<Rc<wgpu::BindGroup>>::new();
+    let mut uniforms = Vec::<Arc<wgpu::BindGroup>>::new();
     let mut components = Vec::<Box<dyn ComponentBase>>::new();
 
     // create material
-    let material = Material::new(&temp_renderer, Rc::clone(&derp_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
+    let material = Material::new(&temp_renderer, Arc::clone(&derp_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
 
     // create new mesh (TODO - mesh loading) and assign material
     let mut mesh = RenderMesh::new(&temp_renderer, material);
     let (material_group, _, _) = mesh.generate_material_uniforms(&temp_renderer);
-    let material_group = Rc::new(material_group);
+    let material_group = Arc::new(material_group);
 
     let translation = Translation::new(cgmath::Vector3::<f32> { x: 3.0, y: 2.0, z: 0.0});
 
@@ -411,40 +449,39 @@ pub fn main() {
     let scale = NonUniformScale::new(cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0});
 
 
-    let mut transform = Transform::new(&temp_renderer, translation.value, rotation.value, scale.value);
-    let (transform_group, _, _) = transform.create_uniforms(&temp_renderer);
-    let transform_group = Rc::new(transform_group);
+    let transform = Transform::new(translation.value, rotation.value, scale.value);
+    let (transform_buffer, transform_group) = TransformBuffer::new(&temp_renderer, &transform);
+    let transform_group = Arc::new(transform_group);
 
-    let phs_comp = PhysicsComponent::new_circle(&mut physics_manager, transform.position, 1.0, 5.0, b2::BodyType::Dynamic, 0, false);
+    let phs_comp = PhysicsComponent::new_circle(&mut physics_manager, transform.get_position(), 1.0, 5.0, b2::BodyType::Dynamic, physics::layers::ENEMY, false);
 
 
-    uniforms.push(Rc::clone(&camera_bind_group));
-    uniforms.push(Rc::clone(&material_group));
-    uniforms.push(Rc::clone(&transform_group));
+    uniforms.push(Arc::clone(&camera_bind_group));
+    uniforms.push(Arc::clone(&material_group));
+    uniforms.push(Arc::clone(&transform_group));
 
     components.push(Box::new(mesh));
     components.push(Box::new(transform));
+    components.push(Box::new(transform_buffer));
     components.push(Box::new(phs_comp));
 
 
-    {
-        entity_manager.create_entity(components, uniforms);
-    }
+    let ball_entity = entity_manager.create_entity(components, uniforms);
 
 
 
 
 
-    let mut uniforms = Vec::<Rc<wgpu::BindGroup>>::new();
+    let mut uniforms = Vec::<Arc<wgpu::BindGroup>>::new();
     let mut components = Vec::<Box<dyn ComponentBase>>::new();
 
     // create material
-    let material = Material::new(&temp_renderer, Rc::clone(&pepe_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
+    let material = Material::new(&temp_renderer, Arc::clone(&pepe_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
 
     // create new mesh (TODO - mesh loading) and assign material
     let mut mesh = RenderMesh::new(&temp_renderer, material);
     let (material_group, _, _) = mesh.generate_material_uniforms(&temp_renderer);
-    let material_group = Rc::new(material_group);
+    let material_group = Arc::new(material_group);
 
     let translation = Translation::new(cgmath::Vector3::<f32> { x: -2.0, y: 1.0, z: 0.0});
 
@@ -457,36 +494,36 @@ pub fn main() {
     let scale = NonUniformScale::new(cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0});
 
 
-    let mut transform = Transform::new(&temp_renderer, translation.value, rotation.value, scale.value);
-    let (transform_group, _, _) = transform.create_uniforms(&temp_renderer);
-    let transform_group = Rc::new(transform_group);
+    let transform = Transform::new(translation.value, rotation.value, scale.value);
+    let (transform_buffer, transform_group) = TransformBuffer::new(&temp_renderer, &transform);
+    let transform_group = Arc::new(transform_group);
 
-    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.position, (1.0, 1.0), 2.0, b2::BodyType::Dynamic, 0, false);
+    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.get_position(), (1.0, 1.0), 2.0, b2::BodyType::Dynamic, physics::layers::ENEMY, false);
 
 
-    uniforms.push(Rc::clone(&camera_bind_group));
-    uniforms.push(Rc::clone(&material_group));
-    uniforms.push(Rc::clone(&transform_group));
+    uniforms.push(Arc::clone(&camera_bind_group));
+    uniforms.push(Arc::clone(&material_group));
+    uniforms.push(Arc::clone(&transform_group));
 
     components.push(Box::new(mesh));
     components.push(Box::new(transform));
+    components.push(Box::new(transform_buffer));
     components.push(Box::new(phs_comp));
 
 
     {
 
-        entity_manager.create_entity(components, uniforms);
-        let new_entity = entity_manager.find_entity(0).unwrap();
+        let wall_entity = entity_manager.create_entity(components, uniforms);
 
-        entity_manager.add_component_data::<MovementComponent>(&new_entity, Box::new(MovementComponent::new(-75.0))).unwrap();
-        let component = entity_manager.get_component_data::<Transform>(new_entity, Transform::get_component_id()).unwrap();
-        component.position = cgmath::Vector3::<f32> { x: 2.5, y: -1.0, z: 0.0 };
-        component.update_uniform_buffers(&temp_renderer);
+        // With the player block above commented out, the ball and this box were entities 0 and 1
+        entity_manager.add_component(ball_entity, MovementComponent::new(-75.0)).unwrap();
+        let mut component = entity_manager.get_component_mut::<Transform>(ball_entity).unwrap();
+        component.set_position(cgmath::Vector3::<f32> { x: 2.5, y: -1.0, z: 0.0 });
+        component.store_previous();
 
-        //drop(component);
+        drop(component);
 
-        let new_entity = entity_manager.find_entity(1).unwrap();
-        entity_manager.add_component_data::<MovementComponent>(&new_entity, Box::new(MovementComponent::new(-75.0))).unwrap();
+        entity_manager.add_component(wall_entity, MovementComponent::new(-75.0)).unwrap();
     }
 
 
@@ -494,16 +531,16 @@ pub fn main() {
 
 
 
-    let mut uniforms = Vec::<Rc<wgpu::BindGroup>>::new();
+    let mut uniforms = Vec::<Arc<wgpu::BindGroup>>::new();
     let mut components = Vec::<Box<dyn ComponentBase>>::new();
 
     // create material
-    let material = Material::new(&temp_renderer, Rc::clone(&white_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
+    let material = Material::new(&temp_renderer, Arc::clone(&white_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
 
     // create new mesh (TODO - mesh loading) and assign material
     let mut mesh = RenderMesh::new(&temp_renderer, material);
     let (material_group, _, _) = mesh.generate_material_uniforms(&temp_renderer);
-    let material_group = Rc::new(material_group);
+    let material_group = Arc::new(material_group);
 
     let translation = Translation::new(cgmath::Vector3::<f32> { x: 0.0, y: -5.0, z: 0.0});
 
@@ -516,19 +553,20 @@ pub fn main() {
     let scale = NonUniformScale::new(cgmath::Vector3::<f32> { x: 20.0, y: 1.0, z: 1.0});
 
 
-    let mut transform = Transform::new(&temp_renderer, translation.value, rotation.value, scale.value);
-    let (transform_group, _, _) = transform.create_uniforms(&temp_renderer);
-    let transform_group = Rc::new(transform_group);
+    let transform = Transform::new(translation.value, rotation.value, scale.value);
+    let (transform_buffer, transform_group) = TransformBuffer::new(&temp_renderer, &transform);
+    let transform_group = Arc::new(transform_group);
 
-    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.position, (20.0, 1.0), 0.0, b2::BodyType::Static, 2, false);
+    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.get_position(), (20.0, 1.0), 0.0, b2::BodyType::Static, physics::layers::GROUND, false);
 
 
-    uniforms.push(Rc::clone(&camera_bind_group));
-    uniforms.push(Rc::clone(&material_group));
-    uniforms.push(Rc::clone(&transform_group));
+    uniforms.push(Arc::clone(&camera_bind_group));
+    uniforms.push(Arc::clone(&material_group));
+    uniforms.push(Arc::clone(&transform_group));
 
     components.push(Box::new(mesh));
     components.push(Box::new(transform));
+    components.push(Box::new(transform_buffer));
     components.push(Box::new(phs_comp));
 
 
@@ -539,16 +577,16 @@ pub fn main() {
 
 
 
-    let mut uniforms = Vec::<Rc<wgpu::BindGroup>>::new();
+    let mut uniforms = Vec::<Arc<wgpu::BindGroup>>::new();
     let mut components = Vec::<Box<dyn ComponentBase>>::new();
 
     // create material
-    let material = Material::new(&temp_renderer, Rc::clone(&white_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
+    let material = Material::new(&temp_renderer, Arc::clone(&white_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
 
     // create new mesh (TODO - mesh loading) and assign material
     let mut mesh = RenderMesh::new(&temp_renderer, material);
     let (material_group, _, _) = mesh.generate_material_uniforms(&temp_renderer);
-    let material_group = Rc::new(material_group);
+    let material_group = Arc::new(material_group);
 
     let translation = Translation::new(cgmath::Vector3::<f32> { x: 45.0, y: -3.0, z: 0.0});
 
@@ -561,19 +599,20 @@ pub fn main() {
     let scale = NonUniformScale::new(cgmath::Vector3::<f32> { x: 20.0, y: 1.0, z: 1.0});
 
 
-    let mut transform = Transform::new(&temp_renderer, translation.value, rotation.value, scale.value);
-    let (transform_group, _, _) = transform.create_uniforms(&temp_renderer);
-    let transform_group = Rc::new(transform_group);
+    let transform = Transform::new(translation.value, rotation.value, scale.value);
+    let (transform_buffer, transform_group) = TransformBuffer::new(&temp_renderer, &transform);
+    let transform_group = Arc::new(transform_group);
 
-    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.position, (20.0, 1.0), 0.0, b2::BodyType::Static, 2, false);
+    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.get_position(), (20.0, 1.0), 0.0, b2::BodyType::Static, physics::layers::GROUND, false);
 
 
-    uniforms.push(Rc::clone(&camera_bind_group));
-    uniforms.push(Rc::clone(&material_group));
-    uniforms.push(Rc::clone(&transform_group));
+    uniforms.push(Arc::clone(&camera_bind_group));
+    uniforms.push(Arc::clone(&material_group));
+    uniforms.push(Arc::clone(&transform_group));
 
     components.push(Box::new(mesh));
     components.push(Box::new(transform));
+    components.push(Box::new(transform_buffer));
     components.push(Box::new(phs_comp));
 
 
@@ -582,16 +621,16 @@ pub fn main() {
     }
 
 
-    let mut uniforms = Vec::<Rc<wgpu::BindGroup>>::new();
+    let mut uniforms = Vec::<Arc<wgpu::BindGroup>>::new();
     let mut components = Vec::<Box<dyn ComponentBase>>::new();
 
     // create material
-    let material = Material::new(&temp_renderer, Rc::clone(&white_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
+    let material = Material::new(&temp_renderer, Arc::clone(&white_texture), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, -1, "main".to_string());
 
     // create new mesh (TODO - mesh loading) and assign material
     let mut mesh = RenderMesh::new(&temp_renderer, material);
     let (material_group, _, _) = mesh.generate_material_uniforms(&temp_renderer);
-    let material_group = Rc::new(material_group);
+    let material_group = Arc::new(material_group);
 
     let translation = Translation::new(cgmath::Vector3::<f32> { x: -45.0, y: -2.0, z: 0.0});
 
@@ -604,19 +643,20 @@ pub fn main() {
     let scale = NonUniformScale::new(cgmath::Vector3::<f32> { x: 20.0, y: 1.0, z: 1.0});
 
 
-    let mut transform = Transform::new(&temp_renderer, translation.value, rotation.value, scale.value);
-    let (transform_group, _, _) = transform.create_uniforms(&temp_renderer);
-    let transform_group = Rc::new(transform_group);
+    let transform = Transform::new(translation.value, rotation.value, scale.value);
+    let (transform_buffer, transform_group) = TransformBuffer::new(&temp_renderer, &transform);
+    let transform_group = Arc::new(transform_group);
 
-    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.position, (20.0, 1.0), 0.0, b2::BodyType::Static, 2, false);
+    let phs_comp = PhysicsComponent::new_box(&mut physics_manager, transform.get_position(), (20.0, 1.0), 0.0, b2::BodyType::Static, physics::layers::GROUND, false);
 
 
-    uniforms.push(Rc::clone(&camera_bind_group));
-    uniforms.push(Rc::clone(&material_group));
-    uniforms.push(Rc::clone(&transform_group));
+    uniforms.push(Arc::clone(&camera_bind_group));
+    uniforms.push(Arc::clone(&material_group));
+    uniforms.push(Arc::clone(&transform_group));
 
     components.push(Box::new(mesh));
     components.push(Box::new(transform));
+    components.push(Box::new(transform_buffer));
     components.push(Box::new(phs_comp));
 
 
@@ -628,7 +668,11 @@ pub fn main() {
     println!("Entity Count: {:?}", entity_manager.entities.len());
 
     
-    SceneLoader::load("./data/scene/scene.dbscene", &mut entity_manager, &mut physics_manager, &temp_renderer, Rc::clone(&camera_bind_group));
+    let mut scene_watcher = SceneWatcher::new(matches.value_of("scene").unwrap_or("./data/scene/scene.dbscene"), Some(Arc::clone(&camera_bind_group)));
+    if let Err(e) = scene_watcher.reload(&component_registry, &mut entity_manager, &mut physics_manager, Some(&temp_renderer)){
+        eprintln!("{}", e);
+        log::error!("{}", e);
+    }
 
     println!("Entity Count: {:?}", entity_manager.entities.len());
 
@@ -730,6 +774,9 @@ pub fn main() {
 
     let sample_count = temp_renderer.sample_count;
     temp_renderer.create_pipeline("framebuffer".to_string(), &layouts, wgpu::include_spirv!("./shaders/framebuffer.vert.spv"), wgpu::include_spirv!("./shaders/framebuffer.frag.spv"), &color_states, &[], sample_count, false);
+
+    // Physics debug overlay, drawn in the same pass as the framebuffer
+    temp_renderer.create_line_pipeline("debug_lines".to_string(), &layouts, wgpu::include_spirv!("./shaders/debug_line.vert.spv"), wgpu::include_spirv!("./shaders/debug_line.frag.spv"), &color_states, &[DebugVertex::desc()], sample_count);
    
     layouts.clear();
     color_states.clear();
@@ -740,8 +787,18 @@ pub fn main() {
     // drop the borrowed mut reference (to stay safe)
     drop(temp_renderer);
 
+    // Systems get everything that isn't an entity from the system manager's resources from here on
+    system_manager.resources.insert_main_thread(physics_manager);
+    system_manager.resources.insert(input_manager);
+    system_manager.resources.insert(camera);
+    system_manager.resources.insert(audio);
+    let mut physics_debug_draw = PhysicsDebugDraw::new();
+    physics_debug_draw.enabled = matches.is_present("debug-physics");
+    system_manager.resources.insert(physics_debug_draw);
+
     /* Define some runtime variables */
     let mut framerate: f32 = 0.0;
+    let mut last_frame = std::time::Instant::now();
 
     /* Game Loop Defined */
 
@@ -753,7 +810,7 @@ pub fn main() {
             window_id,
         } if window_id == window.id() =>  {
             //camera_controller.process_events(event);
-            input_manager.update(event);
+            system_manager.resources.get_mut::<InputManager>().unwrap().update(event);
             let mut renderer = renderer.borrow_mut();
             match event{
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
@@ -767,20 +824,36 @@ pub fn main() {
                         virtual_keycode: Some(VirtualKeyCode::Escape),
                         ..
                     } => {    log::info!("User Quit Application"); *control_flow = ControlFlow::Exit},
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F5),
+                        ..
+                    } => {
+                        // Save the live world so tweaked positions don't have to be copied by hand
+                        let physics_manager = system_manager.resources.get::<Physics>().unwrap();
+                        if let Err(e) = SceneWriter::save("./data/scene/scene_saved.dbscene", &component_registry, &entity_manager, &physics_manager){
+                            log::error!("Failed to save scene: {}", e);
+                        }
+                    },
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F3),
+                        ..
+                    } => system_manager.resources.get_mut::<PhysicsDebugDraw>().unwrap().toggle(),
                     _ => {}
                 }
             },
             WindowEvent::Resized(physical_size) => {
                 renderer.resize(*physical_size);
                 let sc_desc = &renderer.sc_desc;
-                camera.aspect = sc_desc.width as f32 / sc_desc.height as f32;
+                system_manager.resources.get_mut::<Camera>().unwrap().resize(sc_desc.width, sc_desc.height);
                 log::info!("User resized screen");
             }
             WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                 // new_inner_size is &&mut so we have to dereference it twice
                 renderer.resize(**new_inner_size);
                 let sc_desc = &renderer.sc_desc;
-                camera.aspect = sc_desc.width as f32 / sc_desc.height as f32;
+                system_manager.resources.get_mut::<Camera>().unwrap().resize(sc_desc.width, sc_desc.height);
                 log::info!("User resized screen");
             },
             
@@ -789,21 +862,46 @@ pub fn main() {
         },
         Event::RedrawRequested(_) => {
 
-            let start = std::time::SystemTime::now();
+            // Time since the last frame started, so the simulation keeps up with real time whatever the framerate
+            let delta_time = last_frame.elapsed().as_secs_f32();
+            last_frame = std::time::Instant::now();
+            system_manager.resources.get_mut::<Time>().unwrap().advance(delta_time);
+
             let window_size = renderer.borrow().get_window_size();
             let mut renderer = renderer.borrow_mut();
  
+            // Pick up edits to the scene file without restarting
+            {
+                let mut physics_manager = system_manager.resources.get_mut::<Physics>().unwrap();
+                scene_watcher.poll(&component_registry, &mut entity_manager, &mut physics_manager, Some(&renderer));
+            }
+
+            system_manager.update_systems(Some(&*renderer), &mut entity_manager);
 
-            //camera_controller.update_camera(&mut camera);
-            cam_uniform.update_view_proj(&mut camera, &renderer.sc_desc);
-            renderer.write_buffer(camera.get_buffer_reference(), 0, &[cam_uniform]);         
-        
-            system_manager.update_systems(&renderer, &mut entity_manager,  &input_manager, &mut physics_manager, &mut camera);
+            // Copy what the systems changed to the GPU
+            {
+                let alpha = system_manager.resources.get::<Time>().unwrap().alpha;
+                TransformSync::sync(&renderer, &entity_manager, alpha);
+
+                let camera = system_manager.resources.get::<Camera>().unwrap();
+                //camera_controller.update_camera(&mut camera);
+                camera_buffer.sync(&renderer, &camera);
+
+                // Collision shapes as they are after this frame's steps
+                let physics_debug_draw = system_manager.resources.get::<PhysicsDebugDraw>().unwrap();
+                if physics_debug_draw.enabled{
+                    let mut physics_manager = system_manager.resources.get_mut::<Physics>().unwrap();
+                    renderer.set_debug_lines(physics_debug_draw.build_lines(&mut physics_manager));
+                }else{
+                    renderer.set_debug_lines(Vec::<DebugLine>::new());
+                }
+            }
             renderer.update();
             
 
 
-            match renderer.render(&mut camera, &entity_manager, &time, framerate) {
+            let render_result = renderer.render(&mut system_manager.resources.get_mut::<Camera>().unwrap(), &entity_manager, &time, framerate);
+            match render_result {
                 Ok(_) => {}
                 // Recreate the swap_chain if lost
                 Err(wgpu::SwapChainError::Lost) => renderer.resize(window_size),
@@ -813,8 +911,6 @@ pub fn main() {
                 Err(e) => {eprintln!("{:?}", e); log::error!("{:?}", e)},
             }
 
-            let delta_time = start.elapsed().unwrap().as_secs_f32();
-            system_manager.delta_time = delta_time;
             camera_controller.delta_time = delta_time;
             if ((1.0 / delta_time) - framerate).abs() > 2.0{
                 framerate = 1.0 / delta_time;
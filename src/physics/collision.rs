@@ -0,0 +1,232 @@
+use wrapped2d::b2;
+use wrapped2d::dynamics::world::callbacks::ContactAccess;
+use wrapped2d::user_data::UserData;
+use crate::{EntityHandle, PhysicsFilter};
+use std::sync::{Arc, Mutex};
+
+// Two entities' bodies touching. One is reported for every pair of fixtures, so a body made of
+// several shapes can start and end more than one collision with the same entity
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Collision{
+    pub entity_a: EntityHandle,
+    pub entity_b: EntityHandle,
+    // World space, pointing from a to b. Zero when the shapes have already moved apart
+    pub normal: b2::Vec2,
+    // Middle of the contact points in world space
+    pub point: b2::Vec2,
+    // How hard the bodies hit on the step they started touching (Summed over the contact points). Always 0 for
+    // sensors and for ended collisions
+    pub impulse: f32,
+    // Either fixture is a sensor, so the bodies overlap instead of pushing apart
+    pub is_sensor: bool,
+}
+
+impl Collision{
+    pub fn involves(&self, entity: EntityHandle) -> bool{
+        self.entity_a == entity || self.entity_b == entity
+    }
+
+    // The entity `entity` collided with, if it's part of this collision
+    pub fn other(&self, entity: EntityHandle) -> Option<EntityHandle>{
+        if self.entity_a == entity{
+            Some(self.entity_b)
+        }else if self.entity_b == entity{
+            Some(self.entity_a)
+        }else{
+            None
+        }
+    }
+
+    // The normal pointing away from `entity`, towards what it hit
+    pub fn normal_from(&self, entity: EntityHandle) -> b2::Vec2{
+        if self.entity_b == entity{
+            b2::Vec2 { x: -self.normal.x, y: -self.normal.y }
+        }else{
+            self.normal
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CollisionEvent{
+    CollisionStarted(Collision),
+    CollisionEnded(Collision),
+}
+
+impl CollisionEvent{
+    pub fn collision(&self) -> &Collision{
+        match self{
+            CollisionEvent::CollisionStarted(collision) => collision,
+            CollisionEvent::CollisionEnded(collision) => collision,
+        }
+    }
+}
+
+// Collisions reported by PhysicsSystem, kept in Resources so any system can read them.
+// Systems in fixed_update should read the last step, systems in update should read the whole frame,
+// since a frame can run several fixed steps (Or none)
+pub struct CollisionEvents{
+    step: Vec<CollisionEvent>,
+    frame: Vec<CollisionEvent>,
+}
+
+impl CollisionEvents{
+    pub fn new() -> Self{
+        Self{
+            step: Vec::<CollisionEvent>::new(),
+            frame: Vec::<CollisionEvent>::new(),
+        }
+    }
+
+    // Called by SystemManager before the frame's fixed steps
+    pub fn begin_frame(&mut self){
+        self.step.clear();
+        self.frame.clear();
+    }
+
+    // Called by PhysicsSystem with the events of the step it just ran
+    pub fn push_step(&mut self, events: Vec<CollisionEvent>){
+        self.frame.extend(events.iter().cloned());
+        self.step = events;
+    }
+
+    // Events from the last fixed step, in the order Box2D reported them
+    pub fn iter(&self) -> std::slice::Iter<'_, CollisionEvent>{
+        self.step.iter()
+    }
+
+    // Events from every fixed step this frame
+    pub fn iter_frame(&self) -> std::slice::Iter<'_, CollisionEvent>{
+        self.frame.iter()
+    }
+
+    // Collisions `entity` started in the last fixed step
+    pub fn started_with(&self, entity: EntityHandle) -> impl Iterator<Item = &Collision>{
+        self.step.iter().filter_map(move |event| match event{
+            CollisionEvent::CollisionStarted(collision) if collision.involves(entity) => Some(collision),
+            _ => None,
+        })
+    }
+
+    // Collisions `entity` ended in the last fixed step
+    pub fn ended_with(&self, entity: EntityHandle) -> impl Iterator<Item = &Collision>{
+        self.step.iter().filter_map(move |event| match event{
+            CollisionEvent::CollisionEnded(collision) if collision.involves(entity) => Some(collision),
+            _ => None,
+        })
+    }
+}
+
+// A contact as Box2D reported it. The entities come from the bodies' user data, bodies that aren't on an entity
+// (Or that PhysicsSystem hasn't seen yet) have none and are left out of CollisionEvents
+#[derive(Debug, Copy, Clone)]
+pub struct ContactRecord{
+    pub started: bool,
+    // Fixture handles are only unique within a body, so each is paired with its body
+    pub entity_a: Option<EntityHandle>,
+    pub fixture_a: (b2::BodyHandle, b2::FixtureHandle),
+    pub entity_b: Option<EntityHandle>,
+    pub fixture_b: (b2::BodyHandle, b2::FixtureHandle),
+    pub normal: b2::Vec2,
+    pub point: b2::Vec2,
+    pub impulse: f32,
+    pub is_sensor: bool,
+}
+
+// A contact that was touching when a restored snapshot was taken (See Physics::set_restored_contacts). Fixtures are the
+// rebuilt ones with their body and the child (Edge of a chain) that touches, and the points are Box2D's ids with their normal and tangent impulse
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestoredContact{
+    pub fixture_a: ((b2::BodyHandle, b2::FixtureHandle), i32),
+    pub fixture_b: ((b2::BodyHandle, b2::FixtureHandle), i32),
+    pub points: Vec<(u32, f32, f32)>,
+}
+
+// Set on the world by Physics::new. Box2D calls it in the middle of World::step, so it only writes down
+// what happened, and the queue is shared with Physics to read it back afterwards
+pub struct CollisionListener{
+    contacts: Arc<Mutex<Vec<ContactRecord>>>,
+    restored: Arc<Mutex<Vec<RestoredContact>>>,
+}
+
+impl CollisionListener{
+    pub fn new(contacts: Arc<Mutex<Vec<ContactRecord>>>, restored: Arc<Mutex<Vec<RestoredContact>>>) -> Self{
+        Self{
+            contacts,
+            restored,
+        }
+    }
+
+    // A contact from a restored snapshot never stopped touching, so it isn't reported. Its points start the solver from
+    // the impulses they had (Box2D only carries them over from the contact's last step, and this one is new)
+    fn resume(&mut self, access: &mut ContactAccess<PhysicsFilter>) -> bool{
+        let key = (access.contact.fixture_a(), access.contact.child_index_a());
+        let key = (key, (access.contact.fixture_b(), access.contact.child_index_b()));
+        let mut restored = self.restored.lock().unwrap();
+        let restored = match restored.iter().position(|r| (r.fixture_a, r.fixture_b) == key){
+            Some(index) => restored.remove(index),
+            None => return false,
+        };
+        let manifold = access.contact.manifold_mut();
+        for point in manifold.points[..manifold.count as usize].iter_mut(){
+            if let Some((_, normal, tangent)) = restored.points.iter().find(|p| p.0 == point.id.key()){
+                point.normal_impulse = *normal;
+                point.tangent_impulse = *tangent;
+            }
+        }
+        true
+    }
+
+    fn record(&mut self, access: ContactAccess<PhysicsFilter>, started: bool){
+        let (normal, point) = if access.contact.manifold().count > 0{
+            let world_manifold = access.contact.world_manifold();
+            let count = access.contact.manifold().count as usize;
+            let mut point = b2::Vec2 { x: 0.0, y: 0.0 };
+            for p in world_manifold.points[..count].iter(){
+                point.x += p.x / count as f32;
+                point.y += p.y / count as f32;
+            }
+            (world_manifold.normal, point)
+        }else{
+            // Ended contacts usually have no points left
+            (b2::Vec2 { x: 0.0, y: 0.0 }, b2::Vec2 { x: 0.0, y: 0.0 })
+        };
+
+        self.contacts.lock().unwrap().push(ContactRecord{
+            started,
+            entity_a: access.body_a.user_data().entity,
+            fixture_a: access.contact.fixture_a(),
+            entity_b: access.body_b.user_data().entity,
+            fixture_b: access.contact.fixture_b(),
+            normal,
+            point,
+            impulse: 0.0,
+            is_sensor: access.fixture_a.is_sensor() || access.fixture_b.is_sensor(),
+        });
+    }
+}
+
+impl b2::ContactListener<PhysicsFilter> for CollisionListener{
+    fn begin_contact(&mut self, mut access: ContactAccess<PhysicsFilter>){
+        if !self.resume(&mut access){
+            self.record(access, true);
+        }
+    }
+
+    fn end_contact(&mut self, access: ContactAccess<PhysicsFilter>){
+        self.record(access, false);
+    }
+
+    // Runs after the contact is solved, every step it's touching. Only the step it started on is kept
+    fn post_solve(&mut self, access: ContactAccess<PhysicsFilter>, impulse: &b2::ContactImpulse){
+        let fixture_a = access.contact.fixture_a();
+        let fixture_b = access.contact.fixture_b();
+        let total: f32 = impulse.normal_impulses[..impulse.count as usize].iter().sum();
+
+        let mut contacts = self.contacts.lock().unwrap();
+        if let Some(record) = contacts.iter_mut().rev().find(|c| c.started && c.fixture_a == fixture_a && c.fixture_b == fixture_b){
+            // The TOI solver can solve a contact more than once in a step
+            record.impulse = record.impulse.max(total);
+        }
+    }
+}
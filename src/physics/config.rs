@@ -0,0 +1,119 @@
+use wrapped2d::b2;
+
+// Box2D's sleep thresholds, b2_linearSleepTolerance, b2_angularSleepTolerance and b2_timeToSleep in b2Settings.h
+pub const BOX2D_LINEAR_SLEEP_TOLERANCE: f32 = 0.01;
+pub const BOX2D_ANGULAR_SLEEP_TOLERANCE: f32 = 2.0 / 180.0 * std::f32::consts::PI;
+pub const BOX2D_TIME_TO_SLEEP: f32 = 0.5;
+
+// How the physics world is set up and stepped. Scenes set it with a physics[...] block (See PhysicsConfig::new for the defaults)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicsConfig{
+    // Meters per second squared
+    pub gravity: b2::Vec2,
+    // Box2D's solver passes per step. More is stiffer stacks and joints, at a cost
+    pub velocity_iterations: i32,
+    pub position_iterations: i32,
+    // Each fixed step is split into this many world steps, for fast bodies and long chains of joints
+    pub sub_steps: u32,
+    // World units (What Transforms and scene positions use) per Box2D meter. Only positions are scaled,
+    // shapes, anchors and everything else given to Physics are in meters
+    pub pixels_per_meter: f32,
+    // Box2D puts a pile of touching bodies to sleep once all of them have been slower than the thresholds for time_to_sleep
+    // seconds. Bodies whose PhysicsComponent doesn't allow it keep their whole pile awake
+    pub allow_sleep: bool,
+    // Meters per second. The thresholds are compiled into Box2D, so they can only be set to its own (See validate)
+    pub linear_sleep_tolerance: f32,
+    // Radians per second
+    pub angular_sleep_tolerance: f32,
+    pub time_to_sleep: f32,
+    // Entities are handled in handle order and collision events are sorted, so the same scene, input and
+    // frame times always give the same bodies down to the bit
+    pub deterministic: bool,
+    // Start the solver from the last step's impulses. Steadier stacks, but joints keep impulses a PhysicsSnapshot can't
+    // read, so a scene with joints only rewinds exactly with it off
+    pub warm_starting: bool,
+}
+
+impl PhysicsConfig{
+    // Box2D's own defaults
+    pub fn new() -> Self{
+        Self{
+            gravity: b2::Vec2 { x: 0.0, y: -10.0 },
+            velocity_iterations: 6,
+            position_iterations: 2,
+            sub_steps: 1,
+            pixels_per_meter: 1.0,
+            allow_sleep: true,
+            linear_sleep_tolerance: BOX2D_LINEAR_SLEEP_TOLERANCE,
+            angular_sleep_tolerance: BOX2D_ANGULAR_SLEEP_TOLERANCE,
+            time_to_sleep: BOX2D_TIME_TO_SLEEP,
+            deterministic: false,
+            warm_starting: true,
+        }
+    }
+
+    pub fn with_gravity(mut self, x: f32, y: f32) -> Self{
+        self.gravity = b2::Vec2 { x, y };
+        self
+    }
+
+    pub fn with_iterations(mut self, velocity_iterations: i32, position_iterations: i32) -> Self{
+        self.velocity_iterations = velocity_iterations;
+        self.position_iterations = position_iterations;
+        self
+    }
+
+    pub fn with_sub_steps(mut self, sub_steps: u32) -> Self{
+        self.sub_steps = sub_steps;
+        self
+    }
+
+    pub fn with_pixels_per_meter(mut self, pixels_per_meter: f32) -> Self{
+        self.pixels_per_meter = pixels_per_meter;
+        self
+    }
+
+    pub fn with_sleep(mut self, allow_sleep: bool) -> Self{
+        self.allow_sleep = allow_sleep;
+        self
+    }
+
+    // Angular tolerance in radians per second
+    pub fn with_sleep_tolerance(mut self, linear: f32, angular: f32, time_to_sleep: f32) -> Self{
+        self.linear_sleep_tolerance = linear;
+        self.angular_sleep_tolerance = angular;
+        self.time_to_sleep = time_to_sleep;
+        self
+    }
+
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self{
+        self.deterministic = deterministic;
+        self
+    }
+
+    pub fn with_warm_starting(mut self, warm_starting: bool) -> Self{
+        self.warm_starting = warm_starting;
+        self
+    }
+
+    // Settings Box2D would assert on, or that can't step at all
+    pub fn validate(&self) -> Result<(), String>{
+        if self.velocity_iterations < 1 || self.position_iterations < 1{
+            return Err(format!("iterations must be at least 1, found {} and {}", self.velocity_iterations, self.position_iterations));
+        }
+        if self.sub_steps < 1{
+            return Err("sub_steps must be at least 1".to_string());
+        }
+        if !(self.pixels_per_meter > 0.0) || !self.pixels_per_meter.is_finite(){
+            return Err(format!("pixels_per_meter must be above 0, found {}", self.pixels_per_meter));
+        }
+        // Any other thresholds would be ignored without a word
+        if (self.linear_sleep_tolerance - BOX2D_LINEAR_SLEEP_TOLERANCE).abs() > 1e-6 || (self.angular_sleep_tolerance - BOX2D_ANGULAR_SLEEP_TOLERANCE).abs() > 1e-6
+            || (self.time_to_sleep - BOX2D_TIME_TO_SLEEP).abs() > 1e-6{
+            return Err(format!("sleep tolerances are compiled into Box2D as {} meters per second, {:.1} degrees per second and {} seconds, found {}, {:.1} and {}",
+                BOX2D_LINEAR_SLEEP_TOLERANCE, BOX2D_ANGULAR_SLEEP_TOLERANCE.to_degrees(), BOX2D_TIME_TO_SLEEP,
+                self.linear_sleep_tolerance, self.angular_sleep_tolerance.to_degrees(), self.time_to_sleep));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use wrapped2d::b2;
+use crate::Physics;
+
+// Most segments a circle is drawn with
+const CIRCLE_SEGMENTS: usize = 16;
+// Length of contact normals and center of mass axes, in meters
+const MARKER_LENGTH: f32 = 0.4;
+// Half the size of the cross on each contact point, in meters
+const POINT_SIZE: f32 = 0.1;
+
+pub const CONTACT_POINT_COLOR: [f32; 3] = [0.3, 0.95, 0.3];
+pub const CONTACT_NORMAL_COLOR: [f32; 3] = [0.9, 0.9, 0.3];
+
+// One line of the physics overlay, in world units (The same space as Transforms)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DebugLine{
+    pub from: cgmath::Vector3::<f32>,
+    pub to: cgmath::Vector3::<f32>,
+    pub color: [f32; 3],
+}
+
+// What the physics overlay shows, kept in Resources. The lines can be built whether or not it's shown,
+// so tests and tools can read the collision shapes without a GPU
+pub struct PhysicsDebugDraw{
+    // Drawn over the scene. Toggled with F3 in the game
+    pub enabled: bool,
+    // Fixture outlines, colored by body type. Sleeping bodies are grey
+    pub shapes: bool,
+    // Bounding boxes Box2D keeps for each fixture
+    pub aabbs: bool,
+    // A cross on every touching contact point, with the contact normal
+    pub contacts: bool,
+    // Lines from each body to its joints' anchors
+    pub joints: bool,
+    // Center of mass axes of every body
+    pub centers: bool,
+}
+
+impl PhysicsDebugDraw{
+    pub fn new() -> Self{
+        Self{
+            enabled: false,
+            shapes: true,
+            aabbs: false,
+            contacts: true,
+            joints: true,
+            centers: false,
+        }
+    }
+
+    pub fn toggle(&mut self){
+        self.enabled = !self.enabled;
+        log::info!("Physics debug draw {}", if self.enabled { "on" } else { "off" });
+    }
+
+    // The overlay as it is now. Box2D draws shapes, bounding boxes, joints and centers, contacts are read from the bodies
+    pub fn build_lines(&self, physics: &mut Physics) -> Vec<DebugLine>{
+        let mut flags = b2::DrawFlags::empty();
+        flags.set(b2::DrawFlags::DRAW_SHAPE, self.shapes);
+        flags.set(b2::DrawFlags::DRAW_AABB, self.aabbs);
+        flags.set(b2::DrawFlags::DRAW_JOINT, self.joints);
+        flags.set(b2::DrawFlags::DRAW_CENTER_OF_MASS, self.centers);
+
+        let mut collector = LineCollector{
+            lines: Vec::<DebugLine>::new(),
+            pixels_per_meter: physics.get_config().pixels_per_meter,
+        };
+        physics.world.draw_debug_data(&mut collector, flags);
+        if self.contacts{
+            collector.add_contacts(physics);
+        }
+        collector.lines
+    }
+}
+
+// Turns Box2D's debug draw calls into lines. Filled shapes are drawn as outlines
+struct LineCollector{
+    lines: Vec<DebugLine>,
+    pixels_per_meter: f32,
+}
+
+impl LineCollector{
+    fn line(&mut self, from: &b2::Vec2, to: &b2::Vec2, color: [f32; 3]){
+        let scale = self.pixels_per_meter;
+        self.lines.push(DebugLine{
+            from: cgmath::Vector3::<f32> { x: from.x * scale, y: from.y * scale, z: 0.0 },
+            to: cgmath::Vector3::<f32> { x: to.x * scale, y: to.y * scale, z: 0.0 },
+            color,
+        });
+    }
+
+    fn outline(&mut self, vertices: &[b2::Vec2], color: [f32; 3]){
+        for i in 0..vertices.len(){
+            self.line(&vertices[i], &vertices[(i + 1) % vertices.len()], color);
+        }
+    }
+
+    // Every contact is on both of its bodies, so it's only drawn from body a
+    fn add_contacts(&mut self, physics: &Physics){
+        for (handle, body) in physics.world.bodies(){
+            for (_, contact) in body.borrow().contacts(){
+                if !contact.is_touching() || contact.fixture_a().0 != handle{
+                    continue;
+                }
+                let count = contact.manifold().count as usize;
+                let manifold = contact.world_manifold();
+                let normal = manifold.normal;
+                for point in manifold.points.iter().take(count){
+                    self.line(&b2::Vec2 { x: point.x - POINT_SIZE, y: point.y }, &b2::Vec2 { x: point.x + POINT_SIZE, y: point.y }, CONTACT_POINT_COLOR);
+                    self.line(&b2::Vec2 { x: point.x, y: point.y - POINT_SIZE }, &b2::Vec2 { x: point.x, y: point.y + POINT_SIZE }, CONTACT_POINT_COLOR);
+                    let end = b2::Vec2 { x: point.x + normal.x * MARKER_LENGTH, y: point.y + normal.y * MARKER_LENGTH };
+                    self.line(point, &end, CONTACT_NORMAL_COLOR);
+                }
+            }
+        }
+    }
+}
+
+fn rgb(color: &b2::Color) -> [f32; 3]{
+    [color.r, color.g, color.b]
+}
+
+impl b2::Draw for LineCollector{
+    fn draw_polygon(&mut self, vertices: &[b2::Vec2], color: &b2::Color){
+        self.outline(vertices, rgb(color));
+    }
+
+    fn draw_solid_polygon(&mut self, vertices: &[b2::Vec2], color: &b2::Color){
+        self.outline(vertices, rgb(color));
+    }
+
+    fn draw_circle(&mut self, center: &b2::Vec2, radius: f32, color: &b2::Color){
+        let vertices: Vec<b2::Vec2> = (0..CIRCLE_SEGMENTS).map(|i| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+            b2::Vec2 { x: center.x + angle.cos() * radius, y: center.y + angle.sin() * radius }
+        }).collect();
+        self.outline(&vertices, rgb(color));
+    }
+
+    // The axis line shows how far the circle has turned
+    fn draw_solid_circle(&mut self, center: &b2::Vec2, radius: f32, axis: &b2::Vec2, color: &b2::Color){
+        self.draw_circle(center, radius, color);
+        self.line(center, &b2::Vec2 { x: center.x + axis.x * radius, y: center.y + axis.y * radius }, rgb(color));
+    }
+
+    fn draw_segment(&mut self, p1: &b2::Vec2, p2: &b2::Vec2, color: &b2::Color){
+        self.line(p1, p2, rgb(color));
+    }
+
+    // Red x and green y axis
+    fn draw_transform(&mut self, xf: &b2::Transform){
+        let origin = xf.pos;
+        let (sin, cos) = (xf.rot.sin, xf.rot.cos);
+        self.line(&origin, &b2::Vec2 { x: origin.x + cos * MARKER_LENGTH, y: origin.y + sin * MARKER_LENGTH }, [1.0, 0.0, 0.0]);
+        self.line(&origin, &b2::Vec2 { x: origin.x - sin * MARKER_LENGTH, y: origin.y + cos * MARKER_LENGTH }, [0.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,185 @@
+use wrapped2d::b2;
+
+// Box2D asserts (And takes the game down) on degenerate shapes, so they're checked before they get there
+const MAX_POLYGON_VERTICES: usize = 8;
+// Points closer than this are welded together by Box2D (b2_linearSlop)
+const MIN_VERTEX_DISTANCE: f32 = 0.005;
+
+// What a fixture's shape was built from, since the b2 shape itself can't be read back.
+// Points are in body space, before the fixture's offset and angle are applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeKind{
+    // Half extents
+    Box(f32, f32),
+    Circle(f32),
+    // Convex, 3 to 8 points in either winding
+    Polygon(Vec<b2::Vec2>),
+    // A single line, one sided collisions with other edges don't happen. Good for flat ground
+    Edge(b2::Vec2, b2::Vec2),
+    // Connected edges for terrain, without the bumps of separate edges. True if the last point joins the first
+    Chain(Vec<b2::Vec2>, bool),
+}
+
+// One shape on a body, with its own material. A body can have any number of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicsFixture{
+    pub shape: ShapeKind,
+    // Where the shape sits on the body, and how far it's turned (Radians)
+    pub offset: b2::Vec2,
+    pub angle: f32,
+    pub density: f32,
+    pub friction: f32,
+    // Bounciness, 0 - 1
+    pub restitution: f32,
+    // Sensors report contacts but don't collide
+    pub is_sensor: bool,
+    // Fixtures in the same negative group never collide, in the same positive group they always do. 0 leaves it to the layers
+    pub group: i16,
+}
+
+impl PhysicsFixture{
+    fn new(shape: ShapeKind) -> Self{
+        Self{
+            shape,
+            offset: b2::Vec2 { x: 0.0, y: 0.0 },
+            angle: 0.0,
+            density: 1.0,
+            friction: 0.3,
+            restitution: 0.0,
+            is_sensor: false,
+            group: 0,
+        }
+    }
+
+    pub fn new_box(half_width: f32, half_height: f32) -> Self{
+        PhysicsFixture::new(ShapeKind::Box(half_width, half_height))
+    }
+
+    pub fn new_circle(radius: f32) -> Self{
+        PhysicsFixture::new(ShapeKind::Circle(radius))
+    }
+
+    pub fn new_polygon(points: Vec<b2::Vec2>) -> Result<Self, String>{
+        if points.len() < 3 || points.len() > MAX_POLYGON_VERTICES{
+            return Err(format!("a polygon needs 3 to {} points, found {}", MAX_POLYGON_VERTICES, points.len()));
+        }
+        check_spacing(&points, true)?;
+        // Every corner has to turn the same way, and the polygon can't be flat
+        let mut sign = 0.0;
+        for i in 0..points.len(){
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            let c = points[(i + 2) % points.len()];
+            let cross = (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x);
+            if cross.abs() <= f32::EPSILON{
+                return Err(format!("polygon points {}, {} and {} are in a line", i + 1, (i + 1) % points.len() + 1, (i + 2) % points.len() + 1));
+            }
+            if sign == 0.0{
+                sign = cross.signum();
+            }else if cross.signum() != sign{
+                return Err("polygon is not convex, split it into several convex fixtures".to_string());
+            }
+        }
+        Ok(PhysicsFixture::new(ShapeKind::Polygon(points)))
+    }
+
+    pub fn new_edge(from: b2::Vec2, to: b2::Vec2) -> Result<Self, String>{
+        check_spacing(&[from, to], false)?;
+        Ok(PhysicsFixture::new(ShapeKind::Edge(from, to)))
+    }
+
+    // `looped` joins the last point back to the first
+    pub fn new_chain(points: Vec<b2::Vec2>, looped: bool) -> Result<Self, String>{
+        let needed = if looped { 3 } else { 2 };
+        if points.len() < needed{
+            return Err(format!("a {} needs at least {} points, found {}", if looped { "loop" } else { "chain" }, needed, points.len()));
+        }
+        check_spacing(&points, looped)?;
+        Ok(PhysicsFixture::new(ShapeKind::Chain(points, looped)))
+    }
+
+    pub fn with_offset(mut self, x: f32, y: f32) -> Self{
+        self.offset = b2::Vec2 { x, y };
+        self
+    }
+
+    pub fn with_angle(mut self, angle: f32) -> Self{
+        self.angle = angle;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self{
+        self.density = density;
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self{
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self{
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_sensor(mut self, is_sensor: bool) -> Self{
+        self.is_sensor = is_sensor;
+        self
+    }
+
+    pub fn with_group(mut self, group: i16) -> Self{
+        self.group = group;
+        self
+    }
+
+    // The b2 shape, with the offset and angle baked in
+    pub fn create_shape(&self) -> Box<dyn b2::Shape>{
+        match &self.shape{
+            ShapeKind::Box(half_width, half_height) => {
+                Box::new(b2::PolygonShape::new_oriented_box(*half_width, *half_height, &self.offset, self.angle))
+            },
+            ShapeKind::Circle(radius) => {
+                Box::new(b2::CircleShape::new_with(self.offset, *radius))
+            },
+            ShapeKind::Polygon(points) => {
+                Box::new(b2::PolygonShape::new_with(&self.place(points)))
+            },
+            ShapeKind::Edge(from, to) => {
+                let points = self.place(&[*from, *to]);
+                Box::new(b2::EdgeShape::new_with(&points[0], &points[1]))
+            },
+            ShapeKind::Chain(points, looped) => {
+                let points = self.place(points);
+                if *looped{
+                    Box::new(b2::ChainShape::new_loop(&points))
+                }else{
+                    Box::new(b2::ChainShape::new_chain(&points))
+                }
+            },
+        }
+    }
+
+    // Points turned by the angle, then moved by the offset
+    fn place(&self, points: &[b2::Vec2]) -> Vec<b2::Vec2>{
+        let (sin, cos) = self.angle.sin_cos();
+        points.iter().map(|p| b2::Vec2 {
+            x: p.x * cos - p.y * sin + self.offset.x,
+            y: p.x * sin + p.y * cos + self.offset.y,
+        }).collect()
+    }
+}
+
+// Neighbouring points Box2D would weld together make it assert
+fn check_spacing(points: &[b2::Vec2], looped: bool) -> Result<(), String>{
+    let count = if looped { points.len() } else { points.len() - 1 };
+    for i in 0..count{
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        let distance = ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+        if distance < MIN_VERTEX_DISTANCE{
+            return Err(format!("points {} and {} are too close together", i + 1, (i + 1) % points.len() + 1));
+        }
+    }
+    Ok(())
+}
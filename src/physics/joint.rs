@@ -0,0 +1,184 @@
+use wrapped2d::b2;
+
+// The Box2D joints an entity can be connected with
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum JointKind{
+    // Pinned together at the anchors, free to turn. Hinges, wheels, swinging hazards
+    Revolute,
+    // Slides along the axis without turning. Doors, lifts, pistons
+    Prismatic,
+    // Keeps the anchors the same distance apart. Springy with a frequency
+    Distance,
+    // Glued together. Soft with a frequency
+    Weld,
+    // The anchors can't get further apart than the length, but can get closer. Chains and ropes
+    Rope,
+    // Pulls the entity's anchor towards a target in the world. For dragging bodies around
+    Mouse,
+}
+
+impl JointKind{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            JointKind::Revolute => "revolute",
+            JointKind::Prismatic => "prismatic",
+            JointKind::Distance => "distance",
+            JointKind::Weld => "weld",
+            JointKind::Rope => "rope",
+            JointKind::Mouse => "mouse",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>{
+        match name{
+            "revolute" => Some(JointKind::Revolute),
+            "prismatic" => Some(JointKind::Prismatic),
+            "distance" => Some(JointKind::Distance),
+            "weld" => Some(JointKind::Weld),
+            "rope" => Some(JointKind::Rope),
+            "mouse" => Some(JointKind::Mouse),
+            _ => None,
+        }
+    }
+
+    pub fn has_limit(&self) -> bool{
+        *self == JointKind::Revolute || *self == JointKind::Prismatic
+    }
+
+    pub fn has_motor(&self) -> bool{
+        *self == JointKind::Revolute || *self == JointKind::Prismatic
+    }
+
+    pub fn has_length(&self) -> bool{
+        *self == JointKind::Distance || *self == JointKind::Rope
+    }
+
+    pub fn has_spring(&self) -> bool{
+        *self == JointKind::Distance || *self == JointKind::Weld || *self == JointKind::Mouse
+    }
+}
+
+// How an entity is connected to another entity, or to the world. The live joint is made by Physics::create_joint
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicsJoint{
+    pub kind: JointKind,
+    // Name of the entity at the other end, None for the world. Mouse joints are always on the world
+    pub other: Option<String>,
+    // Where the joint holds this entity's body, relative to the body
+    pub anchor: b2::Vec2,
+    // Where it holds the other body, relative to that body. None is wherever the anchor is when the joint is made,
+    // except for distance and rope joints to an entity, which hold the other body's origin. A mouse joint's target
+    pub other_anchor: Option<b2::Vec2>,
+    // Prismatic joints slide along this, relative to this entity's body
+    pub axis: b2::Vec2,
+    // Lower and upper angle (Radians) for revolute joints, translation for prismatic joints
+    pub limit: Option<(f32, f32)>,
+    // Speed (Radians or meters per second) and the most torque or force the motor can use to reach it
+    pub motor: Option<(f32, f32)>,
+    // Distance and rope joints. None is however far apart the anchors are when the joint is made
+    pub length: Option<f32>,
+    // Springiness of distance, weld and mouse joints in hertz, with its damping ratio. 0 hertz is rigid
+    pub frequency: f32,
+    pub damping: f32,
+    // Most force a mouse joint pulls with. 0 is a thousand times the body's mass
+    pub max_force: f32,
+    // Whether the two bodies still collide with each other
+    pub collide_connected: bool,
+}
+
+impl PhysicsJoint{
+    pub fn new(kind: JointKind) -> Self{
+        // Box2D's defaults, a mouse joint with no spring can't move anything
+        let (frequency, damping) = if kind == JointKind::Mouse { (5.0, 0.7) } else { (0.0, 0.0) };
+        Self{
+            kind,
+            other: None,
+            anchor: b2::Vec2 { x: 0.0, y: 0.0 },
+            other_anchor: None,
+            axis: b2::Vec2 { x: 1.0, y: 0.0 },
+            limit: None,
+            motor: None,
+            length: None,
+            frequency,
+            damping,
+            max_force: 0.0,
+            collide_connected: false,
+        }
+    }
+
+    pub fn new_revolute() -> Self{
+        PhysicsJoint::new(JointKind::Revolute)
+    }
+
+    pub fn new_prismatic(axis: b2::Vec2) -> Self{
+        PhysicsJoint::new(JointKind::Prismatic).with_axis(axis)
+    }
+
+    pub fn new_distance() -> Self{
+        PhysicsJoint::new(JointKind::Distance)
+    }
+
+    pub fn new_weld() -> Self{
+        PhysicsJoint::new(JointKind::Weld)
+    }
+
+    pub fn new_rope(length: f32) -> Self{
+        PhysicsJoint::new(JointKind::Rope).with_length(length)
+    }
+
+    pub fn new_mouse() -> Self{
+        PhysicsJoint::new(JointKind::Mouse)
+    }
+
+    // Connect to the entity with this name when the scene is loaded
+    pub fn with_other(mut self, name: &str) -> Self{
+        self.other = Some(name.to_string());
+        self
+    }
+
+    pub fn with_anchor(mut self, x: f32, y: f32) -> Self{
+        self.anchor = b2::Vec2 { x, y };
+        self
+    }
+
+    pub fn with_other_anchor(mut self, x: f32, y: f32) -> Self{
+        self.other_anchor = Some(b2::Vec2 { x, y });
+        self
+    }
+
+    pub fn with_axis(mut self, axis: b2::Vec2) -> Self{
+        self.axis = axis;
+        self
+    }
+
+    pub fn with_limit(mut self, lower: f32, upper: f32) -> Self{
+        self.limit = Some((lower, upper));
+        self
+    }
+
+    pub fn with_motor(mut self, speed: f32, max_force: f32) -> Self{
+        self.motor = Some((speed, max_force));
+        self
+    }
+
+    pub fn with_length(mut self, length: f32) -> Self{
+        self.length = Some(length);
+        self
+    }
+
+    pub fn with_spring(mut self, frequency: f32, damping: f32) -> Self{
+        self.frequency = frequency;
+        self.damping = damping;
+        self
+    }
+
+    pub fn with_max_force(mut self, max_force: f32) -> Self{
+        self.max_force = max_force;
+        self
+    }
+
+    pub fn with_collide_connected(mut self, collide_connected: bool) -> Self{
+        self.collide_connected = collide_connected;
+        self
+    }
+}
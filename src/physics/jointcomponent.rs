@@ -0,0 +1,314 @@
+use wrapped2d::b2;
+use wrapped2d::b2::Joint;
+use crate::{ComponentBase, Physics, PhysicsJoint, JointKind, PhysicsComponent, EntityManager, EntityHandle};
+use crate::scene::{ComponentRegistry, JointsDescription};
+use std::any::Any;
+
+const ID: u32 = 11;
+
+// A joint on an entity, and the live Box2D joint once it's connected
+pub struct JointLink{
+    pub joint: PhysicsJoint,
+    // The entity at the other end. None for the world, and until the joint is connected
+    pub other: Option<EntityHandle>,
+    // None until connected, and again once the other entity is destroyed
+    pub handle: Option<b2::JointHandle>,
+}
+
+// Joints from this entity's body to other entities, or to the world. Scenes name the other entity, and the joints
+// are connected once everything is spawned (See connect_all). Destroying either entity destroys the joint
+pub struct JointComponent{
+    id: u32,
+    pub joints: Vec<JointLink>,
+}
+
+impl JointComponent{
+    // Joints waiting to be connected by connect_all
+    pub fn new(joints: Vec<PhysicsJoint>) -> Self{
+        Self{
+            id: ID,
+            joints: joints.into_iter().map(|joint| JointLink { joint, other: None, handle: None }).collect(),
+        }
+    }
+
+    // Join `entity` to `other` (None for the world) straight away, giving it a JointComponent if it has none.
+    // Returns the joint's index in the component
+    pub fn connect(entity_manager: &mut EntityManager, physics: &mut Physics, entity: EntityHandle, other: Option<EntityHandle>, mut joint: PhysicsJoint) -> Result<usize, String>{
+        let body = JointComponent::get_body(entity_manager, entity)?;
+        let other_body = match other{
+            Some(other) => {
+                // Kept so the joint is saved with the scene, and found again on reload
+                joint.other = entity_manager.get_entity(other).and_then(|e| e.name.clone());
+                Some(JointComponent::get_body(entity_manager, other)?)
+            },
+            None => {
+                joint.other = None;
+                None
+            },
+        };
+        if !entity_manager.has_component::<JointComponent>(entity){
+            entity_manager.add_component(entity, JointComponent::new(Vec::<PhysicsJoint>::new()))?;
+        }
+        let handle = physics.create_joint(&joint, body, other_body);
+        let mut link = JointLink { joint, other, handle: Some(handle) };
+        JointComponent::pin_to_world(physics, &mut link);
+        let mut component = entity_manager.get_component_mut::<JointComponent>(entity).unwrap();
+        component.joints.push(link);
+        Ok(component.joints.len() - 1)
+    }
+
+    // Connect every scene joint that isn't connected yet to the entity with the name it gives. Called by the scene loader after spawning,
+    // so joints whose other entity was rebuilt by a hot reload are connected to the new one. Returns the joints that couldn't be connected,
+    // with the entity they're on
+    pub fn connect_all(entity_manager: &EntityManager, physics: &mut Physics) -> Vec<(EntityHandle, String)>{
+        let mut problems = Vec::<(EntityHandle, String)>::new();
+        for (entity, component) in entity_manager.query::<&mut JointComponent>().iter(){
+            let name = entity_manager.get_entity(entity).and_then(|e| e.name.clone()).unwrap_or(format!("{:?}", entity));
+            for link in component.joints.iter_mut().filter(|l| l.handle.is_none()){
+                let body = match entity_manager.get_component::<PhysicsComponent>(entity){
+                    Some(v) => v.handle,
+                    None => {
+                        problems.push((entity, format!("{} has a {} joint but no physics body", name, link.joint.kind.get_name())));
+                        continue;
+                    },
+                };
+                let other = match &link.joint.other{
+                    Some(other_name) => {
+                        let found = entity_manager.find_by_name(other_name).map(|e| e.handle)
+                            .and_then(|handle| entity_manager.get_component::<PhysicsComponent>(handle).map(|p| (handle, p.handle)));
+                        match found{
+                            Some(v) => Some(v),
+                            None => {
+                                problems.push((entity, format!("{}'s {} joint connects to '{}', which isn't an entity with a physics body", name, link.joint.kind.get_name(), other_name)));
+                                continue;
+                            },
+                        }
+                    },
+                    None => None,
+                };
+                link.handle = Some(physics.create_joint(&link.joint, body, other.map(|o| o.1)));
+                link.other = other.map(|o| o.0);
+                JointComponent::pin_to_world(physics, link);
+            }
+        }
+        problems
+    }
+
+    // A joint to the world holds on where the entity was when it was connected. Write that down, so saving
+    // the scene mid swing keeps the pivot where it was
+    fn pin_to_world(physics: &Physics, link: &mut JointLink){
+        if let Some(handle) = link.handle{
+            if link.joint.other.is_none() && link.joint.other_anchor.is_none() && link.joint.kind != JointKind::Mouse{
+                link.joint.other_anchor = Some(physics.world.joint(handle).anchor_b());
+            }
+        }
+    }
+
+    fn get_body(entity_manager: &EntityManager, entity: EntityHandle) -> Result<b2::BodyHandle, String>{
+        match entity_manager.get_component::<PhysicsComponent>(entity){
+            Some(physics) => Ok(physics.handle),
+            None => Err(format!("Entity {:?} has no physics body to join", entity)),
+        }
+    }
+
+    // Destroy the joint and take it off the entity
+    pub fn remove_joint(&mut self, physics: &mut Physics, index: usize) -> Option<PhysicsJoint>{
+        if index >= self.joints.len(){
+            return None;
+        }
+        let link = self.joints.remove(index);
+        if let Some(handle) = link.handle{
+            if physics.has_joint(handle){
+                physics.world.destroy_joint(handle);
+            }
+        }
+        Some(link.joint)
+    }
+
+    // Destroy every joint, e.g. before the entity's body goes
+    pub fn disconnect_all(&mut self, physics: &mut Physics){
+        for link in self.joints.iter_mut(){
+            if let Some(handle) = link.handle.take(){
+                if physics.has_joint(handle){
+                    physics.world.destroy_joint(handle);
+                }
+            }
+        }
+    }
+
+    // Called when `entity` is destroyed, its body takes the joints with it. Scene joints are kept so they can be connected
+    // again to an entity with the same name, joints to unnamed entities are dropped
+    pub fn forget_entity(&mut self, entity: EntityHandle){
+        self.joints.retain(|link| link.other != Some(entity) || link.joint.other.is_some());
+        for link in self.joints.iter_mut().filter(|l| l.other == Some(entity)){
+            link.other = None;
+            link.handle = None;
+        }
+    }
+
+    pub fn get_handle(&self, index: usize) -> Option<b2::JointHandle>{
+        self.joints.get(index)?.handle
+    }
+
+    // Everything below acts on the live joint and keeps the description in step, so the change is saved with the scene
+
+    // Turn the motor of a revolute or prismatic joint on with a speed (Radians or meters per second) and the most torque or force it can use, or off with None
+    pub fn set_motor(&mut self, physics: &mut Physics, index: usize, motor: Option<(f32, f32)>) -> Result<(), String>{
+        let handle = self.get_live(physics, index, JointKind::has_motor, "a motor")?;
+        self.joints[index].joint.motor = motor;
+        match &mut **physics.world.joint_mut(handle){
+            b2::UnknownJoint::Revolute(revolute) => {
+                revolute.enable_motor(motor.is_some());
+                if let Some((speed, max_torque)) = motor{
+                    revolute.set_motor_speed(speed);
+                    revolute.set_max_motor_torque(max_torque);
+                }
+            },
+            b2::UnknownJoint::Prismatic(prismatic) => {
+                prismatic.enable_motor(motor.is_some());
+                if let Some((speed, max_force)) = motor{
+                    prismatic.set_motor_speed(speed);
+                    prismatic.set_max_motor_force(max_force);
+                }
+            },
+            _ => {},
+        }
+        JointComponent::wake(physics, handle);
+        Ok(())
+    }
+
+    // Lower and upper angle (Radians) of a revolute joint, or translation of a prismatic joint. None removes the limit
+    pub fn set_limit(&mut self, physics: &mut Physics, index: usize, limit: Option<(f32, f32)>) -> Result<(), String>{
+        let handle = self.get_live(physics, index, JointKind::has_limit, "limits")?;
+        self.joints[index].joint.limit = limit;
+        match &mut **physics.world.joint_mut(handle){
+            b2::UnknownJoint::Revolute(revolute) => {
+                revolute.enable_limit(limit.is_some());
+                if let Some((lower, upper)) = limit{
+                    revolute.set_limits(lower, upper);
+                }
+            },
+            b2::UnknownJoint::Prismatic(prismatic) => {
+                prismatic.enable_limit(limit.is_some());
+                if let Some((lower, upper)) = limit{
+                    prismatic.set_limits(lower, upper);
+                }
+            },
+            _ => {},
+        }
+        JointComponent::wake(physics, handle);
+        Ok(())
+    }
+
+    // Length of a distance joint, or the longest a rope joint can stretch
+    pub fn set_length(&mut self, physics: &mut Physics, index: usize, length: f32) -> Result<(), String>{
+        let handle = self.get_live(physics, index, JointKind::has_length, "a length")?;
+        self.joints[index].joint.length = Some(length);
+        match &mut **physics.world.joint_mut(handle){
+            b2::UnknownJoint::Distance(distance) => distance.set_length(length),
+            b2::UnknownJoint::Rope(rope) => rope.set_max_length(length),
+            _ => {},
+        }
+        JointComponent::wake(physics, handle);
+        Ok(())
+    }
+
+    // Springiness of a distance, weld or mouse joint
+    pub fn set_spring(&mut self, physics: &mut Physics, index: usize, frequency: f32, damping: f32) -> Result<(), String>{
+        let handle = self.get_live(physics, index, JointKind::has_spring, "a spring")?;
+        self.joints[index].joint.frequency = frequency;
+        self.joints[index].joint.damping = damping;
+        match &mut **physics.world.joint_mut(handle){
+            b2::UnknownJoint::Distance(distance) => {
+                distance.set_frequency(frequency);
+                distance.set_damping_ratio(damping);
+            },
+            b2::UnknownJoint::Weld(weld) => {
+                weld.set_frequency(frequency);
+                weld.set_damping_ratio(damping);
+            },
+            b2::UnknownJoint::Mouse(mouse) => {
+                mouse.set_frequency(frequency);
+                mouse.set_damping_ratio(damping);
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    // Where a mouse joint pulls the entity to, in world space
+    pub fn set_target(&mut self, physics: &mut Physics, index: usize, target: b2::Vec2) -> Result<(), String>{
+        let handle = self.get_live(physics, index, |kind| *kind == JointKind::Mouse, "a target")?;
+        self.joints[index].joint.other_anchor = Some(target);
+        if let b2::UnknownJoint::Mouse(mouse) = &mut **physics.world.joint_mut(handle){
+            mouse.set_target(&target);
+        }
+        JointComponent::wake(physics, handle);
+        Ok(())
+    }
+
+    // The live joint, if it's connected and of a kind that has the setting
+    fn get_live(&self, physics: &Physics, index: usize, has: fn(&JointKind) -> bool, setting: &str) -> Result<b2::JointHandle, String>{
+        let link = match self.joints.get(index){
+            Some(v) => v,
+            None => return Err(format!("No joint {}, the entity has {}", index, self.joints.len())),
+        };
+        if !has(&link.joint.kind){
+            return Err(format!("{} joints don't have {}", link.joint.kind.get_name(), setting));
+        }
+        match link.handle{
+            Some(handle) if physics.has_joint(handle) => Ok(handle),
+            _ => Err(format!("The {} joint isn't connected", link.joint.kind.get_name())),
+        }
+    }
+
+    // Bodies asleep when the joint changes wouldn't notice it
+    fn wake(physics: &mut Physics, handle: b2::JointHandle){
+        let (body_a, body_b) = {
+            let joint = physics.world.joint(handle);
+            (joint.body_a(), joint.body_b())
+        };
+        physics.world.body_mut(body_a).set_awake(true);
+        physics.world.body_mut(body_b).set_awake(true);
+    }
+
+    pub fn get_component_id() -> u32{
+        ID
+    }
+
+    // joints{ revolute("Other"){ anchor(0.0,1.0) limit(-45,45) } rope(world){ length(3.0) } } - see JointsDescription.
+    // Joints to unnamed entities made in code can't be written, they're left out when saving
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.register_with_saver("JointComponent", ID, "joints", |node, _context| {
+            let joints = JointsDescription::from_node(node)?;
+            Ok(Box::new(JointComponent::new(joints.joints)))
+        }, |component, _| {
+            let component = component.as_any().downcast_ref::<JointComponent>()?;
+            let joints: Vec<PhysicsJoint> = component.joints.iter()
+                .filter(|link| link.other.is_none() || link.joint.other.is_some())
+                .map(|link| link.joint.clone())
+                .collect();
+            if joints.is_empty(){
+                return None;
+            }
+            Some(JointsDescription { joints }.to_node())
+        })
+    }
+}
+
+impl ComponentBase for JointComponent{
+
+    fn get_id(&self) -> u32{
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+}
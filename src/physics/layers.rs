@@ -0,0 +1,95 @@
+use wrapped2d::b2;
+use crate::LayerType;
+
+// Box2D filters on 16 category bits, so that's how many layers there can be
+pub const MAX_LAYERS: usize = 16;
+
+// Layers the engine's own systems look for. Scenes can name more after these
+pub const ENEMY: LayerType = 0;
+pub const PLAYER: LayerType = 1;
+pub const GROUND: LayerType = 2;
+
+// Named collision layers and which of them collide. A body's layer becomes the category bit of each of its
+// fixtures, and the layer's row of the matrix becomes their mask bits. Everything collides unless told otherwise.
+// Layers can be used by number without a name, as older scenes do
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollisionLayers{
+    // names[i] is layer i
+    names: Vec<String>,
+    // Bit j of masks[i] is set if layer i collides with layer j. Always kept symmetric
+    masks: [u16; MAX_LAYERS],
+}
+
+impl CollisionLayers{
+    pub fn new() -> Self{
+        Self{
+            names: vec!("enemy".to_string(), "player".to_string(), "ground".to_string()),
+            masks: [0xFFFF; MAX_LAYERS],
+        }
+    }
+
+    // Name the next free layer. Naming a layer twice returns the layer it already has
+    pub fn add(&mut self, name: &str) -> Result<LayerType, String>{
+        if let Some(layer) = self.find(name){
+            return Ok(layer);
+        }
+        if self.names.len() >= MAX_LAYERS{
+            return Err(format!("can't add layer '{}', all {} collision layers are in use", name, MAX_LAYERS));
+        }
+        self.names.push(name.to_string());
+        Ok((self.names.len() - 1) as LayerType)
+    }
+
+    pub fn find(&self, name: &str) -> Option<LayerType>{
+        self.names.iter().position(|n| n == name).map(|i| i as LayerType)
+    }
+
+    pub fn get_name(&self, layer: LayerType) -> Option<&str>{
+        self.names.get(layer as usize).map(|n| n.as_str())
+    }
+
+    // In layer order
+    pub fn get_names(&self) -> &[String]{
+        &self.names
+    }
+
+    pub fn is_valid(layer: LayerType) -> bool{
+        (layer as usize) < MAX_LAYERS
+    }
+
+    // Works both ways, ignoring a with b also ignores b with a. A layer can be set to ignore itself
+    pub fn set_collides(&mut self, a: LayerType, b: LayerType, collides: bool){
+        if !CollisionLayers::is_valid(a) || !CollisionLayers::is_valid(b){
+            log::warn!("Collision layers {} and {} aren't both below {}, the matrix is unchanged", a, b, MAX_LAYERS);
+            return;
+        }
+        if collides{
+            self.masks[a as usize] |= 1 << b;
+            self.masks[b as usize] |= 1 << a;
+        }else{
+            self.masks[a as usize] &= !(1 << b);
+            self.masks[b as usize] &= !(1 << a);
+        }
+    }
+
+    pub fn collides(&self, a: LayerType, b: LayerType) -> bool{
+        if !CollisionLayers::is_valid(a) || !CollisionLayers::is_valid(b){
+            return true;
+        }
+        self.masks[a as usize] & (1 << b) != 0
+    }
+
+    // The Box2D filter for a fixture on a body on this layer. Fixtures sharing a negative group never collide with each other,
+    // fixtures sharing a positive group always do, whatever their layers say
+    pub fn filter(&self, layer: LayerType, group: i16) -> b2::Filter{
+        if !CollisionLayers::is_valid(layer){
+            log::warn!("Collision layer {} is out of range (0 to {}), it collides with everything", layer, MAX_LAYERS - 1);
+            return b2::Filter { group_index: group, ..b2::Filter::new() };
+        }
+        b2::Filter{
+            category_bits: 1 << layer,
+            mask_bits: self.masks[layer as usize],
+            group_index: group,
+        }
+    }
+}
@@ -1,24 +1,102 @@
 use wrapped2d::b2;
 
 pub mod physicscomponent;
+pub mod fixture;
+pub mod collision;
+pub mod layers;
+pub mod query;
+pub mod joint;
+pub mod jointcomponent;
+pub mod config;
+pub mod debug_draw;
+pub mod snapshot;
 
-use crate::{World};
+use crate::{World, PhysicsFixture, PhysicsJoint, JointKind, CollisionLayers, EntityHandle, PhysicsConfig};
+use collision::{CollisionListener, ContactRecord, RestoredContact};
+use std::sync::{Arc, Mutex};
 
 pub struct Physics{
     pub world: World,
+    // Filled by the world's contact listener while it steps
+    contacts: Arc<Mutex<Vec<ContactRecord>>>,
+    // Contacts from a restored snapshot that Box2D hasn't made again yet, shared with the listener
+    restored: Arc<Mutex<Vec<RestoredContact>>>,
+    // Turned into each fixture's collision filter
+    collision_layers: CollisionLayers,
+    // Static body without fixtures for joints to hold on to the world with. Made by the first one that needs it
+    ground: Option<b2::BodyHandle>,
+    config: PhysicsConfig,
 }
 
 impl Physics{
     pub fn new() -> Self{
         /* Physics */
-        let mut world = World::new(&b2::Vec2 { x: 0., y: -10. }); // Physics world
+        let config = PhysicsConfig::new();
+        let mut world = World::new(&config.gravity); // Physics world
+        let contacts = Arc::new(Mutex::new(Vec::<ContactRecord>::new()));
+        let restored = Arc::new(Mutex::new(Vec::<RestoredContact>::new()));
+        world.set_contact_listener(Box::new(CollisionListener::new(contacts.clone(), restored.clone())));
+        world.set_sleeping_allowed(config.allow_sleep);
+        world.set_warm_starting(config.warm_starting);
 
         log::info!("Initialized the physics world");
         Self{
-            world
+            world,
+            contacts,
+            restored,
+            collision_layers: CollisionLayers::new(),
+            ground: None,
+            config,
         }
     }
 
+    pub fn get_config(&self) -> &PhysicsConfig{
+        &self.config
+    }
+
+    // Takes effect on the next step. Bodies are woken if gravity changes, so none are left hanging, and Box2D wakes them
+    // all when sleeping is turned off. Bodies already in the world keep their positions in meters when pixels_per_meter changes
+    pub fn set_config(&mut self, config: PhysicsConfig) -> Result<(), String>{
+        config.validate()?;
+        if config == self.config{
+            return Ok(());
+        }
+        let wake = config.gravity != self.config.gravity;
+        self.world.set_gravity(&config.gravity);
+        self.world.set_sleeping_allowed(config.allow_sleep);
+        self.world.set_warm_starting(config.warm_starting);
+        self.config = config;
+        if wake{
+            let handles: Vec<b2::BodyHandle> = self.world.bodies().map(|(handle, _)| handle).collect();
+            for handle in handles.into_iter(){
+                self.world.body_mut(handle).set_awake(true);
+            }
+        }
+        Ok(())
+    }
+
+    // A Transform position in world units to a body position in meters
+    pub fn to_meters(&self, position: cgmath::Vector3::<f32>) -> b2::Vec2{
+        b2::Vec2 { x: position.x / self.config.pixels_per_meter, y: position.y / self.config.pixels_per_meter }
+    }
+
+    // A body position in meters to a Transform position in world units
+    pub fn to_units(&self, position: &b2::Vec2) -> cgmath::Vector3::<f32>{
+        cgmath::Vector3::<f32> { x: position.x * self.config.pixels_per_meter, y: position.y * self.config.pixels_per_meter, z: 0.0 }
+    }
+
+    // Step the world by delta_time in the config's sub-steps, returning the contacts that started and ended during the step.
+    // Contacts ended outside a step (By destroying a body) are dropped, their entity is already gone
+    pub fn step(&mut self, delta_time: f32) -> Vec<ContactRecord>{
+        self.contacts.lock().unwrap().clear();
+        let sub_delta_time = delta_time / self.config.sub_steps as f32;
+        for _ in 0..self.config.sub_steps{
+            self.world.step(sub_delta_time, self.config.velocity_iterations, self.config.position_iterations);
+        }
+        self.end_restored_contacts();
+        std::mem::replace(&mut *self.contacts.lock().unwrap(), Vec::<ContactRecord>::new())
+    }
+
     pub fn create_box_shape(&self, width: f32, height: f32) -> Box<dyn b2::Shape>{
         log::info!("Generating new box shape");
         Box::new(b2::PolygonShape::new_box(width, height))
@@ -56,18 +134,224 @@ impl Physics{
         
     }
 
-    pub fn check_collision(&self, layer: u32, body: &std::cell::Ref<wrapped2d::b2::MetaBody<PhysicsFilter>>) -> bool{
-        //Collision detection - TODO abstract this
-        let mut has_collided = false;
-        for contact in body.contacts(){
-            let handle = contact.0;
-            let col_body = self.world.body(handle);
-            let user_data = col_body.user_data().as_ref();
-            if user_data == Some(&layer){
-                has_collided = true;
+    // Add the fixture to the body, with its own density, friction, restitution and sensor flag.
+    // What it collides with comes from the body's layer and the fixture's group
+    pub fn bind_fixture(&mut self, body_handle: &b2::BodyHandle, fixture: &PhysicsFixture, layer: LayerType) -> b2::FixtureHandle{
+        let shape = fixture.create_shape();
+        let mut fixture_def = b2::FixtureDef{
+            density: fixture.density,
+            friction: fixture.friction,
+            restitution: fixture.restitution,
+            is_sensor: fixture.is_sensor,
+            filter: self.collision_layers.filter(layer, fixture.group),
+            ..b2::FixtureDef::new()
+        };
+        self.world.body_mut(*body_handle).create_fixture(&*shape, &mut fixture_def)
+    }
+
+    // The body joints to the world are made with. It sits at the origin, so its points are world positions
+    pub fn get_ground_body(&mut self) -> b2::BodyHandle{
+        if let Some(ground) = self.ground{
+            return ground;
+        }
+        let ground = self.world.create_body(&b2::BodyDef::new());
+        self.ground = Some(ground);
+        ground
+    }
+
+    // Join the body to `other`, or to the world. The joint is made from where the bodies are now,
+    // so angles and lengths that aren't given are kept as they are. Mouse joints ignore `other`
+    pub fn create_joint(&mut self, joint: &PhysicsJoint, body: b2::BodyHandle, other: Option<b2::BodyHandle>) -> b2::JointHandle{
+        let other = match other{
+            Some(v) if joint.kind != JointKind::Mouse => v,
+            _ => self.get_ground_body(),
+        };
+        let (anchor, angle, mass) = {
+            let body = self.world.body(body);
+            (body.world_point(&joint.anchor), body.angle(), body.mass())
+        };
+        let (other_anchor, other_angle) = {
+            let other_body = self.world.body(other);
+            let other_anchor = match joint.other_anchor{
+                Some(v) => other_body.world_point(&v),
+                None if joint.kind.has_length() && Some(other) != self.ground => *other_body.position(),
+                None => anchor,
+            };
+            (other_anchor, other_body.angle())
+        };
+        let local_other_anchor = self.world.body(other).local_point(&other_anchor);
+        let distance = ((other_anchor.x - anchor.x).powi(2) + (other_anchor.y - anchor.y).powi(2)).sqrt();
+        log::info!("Creating {} joint", joint.kind.get_name());
+
+        match joint.kind{
+            JointKind::Revolute => {
+                let mut def = b2::RevoluteJointDef::new(body, other);
+                def.collide_connected = joint.collide_connected;
+                def.local_anchor_a = joint.anchor;
+                def.local_anchor_b = local_other_anchor;
+                def.reference_angle = other_angle - angle;
+                if let Some((lower, upper)) = joint.limit{
+                    def.enable_limit = true;
+                    def.lower_angle = lower;
+                    def.upper_angle = upper;
+                }
+                if let Some((speed, max_torque)) = joint.motor{
+                    def.enable_motor = true;
+                    def.motor_speed = speed;
+                    def.max_motor_torque = max_torque;
+                }
+                self.world.create_joint(&def)
+            },
+            JointKind::Prismatic => {
+                let mut def = b2::PrismaticJointDef::new(body, other);
+                def.collide_connected = joint.collide_connected;
+                def.local_anchor_a = joint.anchor;
+                def.local_anchor_b = local_other_anchor;
+                let length = (joint.axis.x * joint.axis.x + joint.axis.y * joint.axis.y).sqrt();
+                if length > f32::EPSILON{
+                    def.local_axis_a = b2::Vec2 { x: joint.axis.x / length, y: joint.axis.y / length };
+                }
+                def.reference_angle = other_angle - angle;
+                if let Some((lower, upper)) = joint.limit{
+                    def.enable_limit = true;
+                    def.lower_translation = lower;
+                    def.upper_translation = upper;
+                }
+                if let Some((speed, max_force)) = joint.motor{
+                    def.enable_motor = true;
+                    def.motor_speed = speed;
+                    def.max_motor_force = max_force;
+                }
+                self.world.create_joint(&def)
+            },
+            JointKind::Distance => {
+                let mut def = b2::DistanceJointDef::new(body, other);
+                def.collide_connected = joint.collide_connected;
+                def.local_anchor_a = joint.anchor;
+                def.local_anchor_b = local_other_anchor;
+                def.length = joint.length.unwrap_or(distance);
+                def.frequency = joint.frequency;
+                def.damping_ratio = joint.damping;
+                self.world.create_joint(&def)
+            },
+            JointKind::Weld => {
+                let mut def = b2::WeldJointDef::new(body, other);
+                def.collide_connected = joint.collide_connected;
+                def.local_anchor_a = joint.anchor;
+                def.local_anchor_b = local_other_anchor;
+                def.reference_angle = other_angle - angle;
+                def.frequency = joint.frequency;
+                def.damping_ratio = joint.damping;
+                self.world.create_joint(&def)
+            },
+            JointKind::Rope => {
+                let mut def = b2::RopeJointDef::new(body, other);
+                def.collide_connected = joint.collide_connected;
+                def.local_anchor_a = joint.anchor;
+                def.local_anchor_b = local_other_anchor;
+                def.max_length = joint.length.unwrap_or(distance);
+                self.world.create_joint(&def)
+            },
+            JointKind::Mouse => {
+                // Box2D grabs body b where the target starts, so start it on the anchor and move it afterwards
+                let mut def = b2::MouseJointDef::new(other, body);
+                def.collide_connected = joint.collide_connected;
+                def.target = anchor;
+                def.max_force = if joint.max_force > 0.0 { joint.max_force } else { 1000.0 * mass };
+                def.frequency = joint.frequency;
+                def.damping_ratio = joint.damping;
+                let handle = self.world.create_joint(&def);
+                if let b2::UnknownJoint::Mouse(mouse) = &mut **self.world.joint_mut(handle){
+                    mouse.set_target(&other_anchor);
+                }
+                handle
+            },
+        }
+    }
+
+    // False once either of the joint's bodies has been destroyed
+    pub fn has_joint(&self, handle: b2::JointHandle) -> bool{
+        self.world.joints().any(|(h, _)| h == handle)
+    }
+
+    // Written into the body's user data by PhysicsSystem before every step, so the contact listener and queries can report entities
+    pub fn set_entity(&mut self, body: b2::BodyHandle, entity: EntityHandle){
+        let mut body = self.world.body_mut(body);
+        if body.user_data().entity != Some(entity){
+            body.user_data_mut().entity = Some(entity);
+        }
+    }
+
+    // The entity a body belongs to. Bodies created since the start of the last step aren't known yet
+    pub fn get_entity(&self, body: b2::BodyHandle) -> Option<EntityHandle>{
+        self.world.body(body).user_data().entity
+    }
+
+    pub fn get_collision_layers(&self) -> &CollisionLayers{
+        &self.collision_layers
+    }
+
+    // Replace the layers and collision matrix. Bodies already in the world are filtered again, keeping their layer and groups
+    pub fn set_collision_layers(&mut self, collision_layers: CollisionLayers){
+        if collision_layers == self.collision_layers{
+            return;
+        }
+        self.collision_layers = collision_layers;
+        let handles: Vec<b2::BodyHandle> = self.world.bodies().map(|(handle, _)| handle).collect();
+        for handle in handles.into_iter(){
+            self.refilter_body(handle);
+        }
+    }
+
+    // Apply the body's layer (From its user data) to each of its fixtures. Box2D finds the contacts that start or stop on the next step
+    pub fn refilter_body(&mut self, handle: b2::BodyHandle){
+        let body = self.world.body(handle);
+        let layer = body.user_data().layer;
+        for (_, fixture) in body.fixtures(){
+            let mut fixture = fixture.borrow_mut();
+            let group = fixture.filter_data().group_index;
+            fixture.set_filter_data(&self.collision_layers.filter(layer, group));
+        }
+    }
+
+    // Hand the listener the contacts a restored snapshot had touching. Box2D makes them again on the next step, from the
+    // fixtures the restore rebuilt, and the listener gives them back their impulses instead of reporting them as new
+    pub fn set_restored_contacts(&mut self, contacts: Vec<RestoredContact>){
+        *self.restored.lock().unwrap() = contacts;
+    }
+
+    // Restored contacts Box2D looked at and found apart were touching before the step, so they end like any other.
+    // Ones between sleeping bodies aren't looked at until something wakes them
+    fn end_restored_contacts(&self){
+        let mut restored = self.restored.lock().unwrap();
+        if restored.is_empty(){
+            return;
+        }
+        for contact in self.world.contacts(){
+            let key = ((contact.fixture_a(), contact.child_index_a()), (contact.fixture_b(), contact.child_index_b()));
+            let index = match restored.iter().position(|r| (r.fixture_a, r.fixture_b) == key){
+                Some(v) => v,
+                None => continue,
+            };
+            let (body_a, body_b) = (self.world.body(contact.fixture_a().0), self.world.body(contact.fixture_b().0));
+            if contact.is_touching() || !(body_a.is_awake() || body_b.is_awake()){
+                continue;
             }
+            let fixture_a = body_a.fixture(contact.fixture_a().1);
+            let fixture_b = body_b.fixture(contact.fixture_b().1);
+            self.contacts.lock().unwrap().push(ContactRecord{
+                started: false,
+                entity_a: body_a.user_data().entity,
+                fixture_a: contact.fixture_a(),
+                entity_b: body_b.user_data().entity,
+                fixture_b: contact.fixture_b(),
+                normal: b2::Vec2 { x: 0.0, y: 0.0 },
+                point: b2::Vec2 { x: 0.0, y: 0.0 },
+                impulse: 0.0,
+                is_sensor: fixture_a.is_sensor() || fixture_b.is_sensor(),
+            });
+            restored.remove(index);
         }
-        has_collided
     }
 }
 
@@ -79,11 +363,19 @@ pub type LayerType = u32;
 pub type ObjectId = LayerType;
 
 
+// What each body carries in its user data
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct BodyData{
+    pub layer: LayerType,
+    // None until PhysicsSystem has seen the body, and for bodies that aren't on an entity
+    pub entity: Option<EntityHandle>,
+}
+
 pub struct PhysicsFilter{
 }
 impl wrapped2d::user_data::UserDataTypes for PhysicsFilter{
 
-    type BodyData = Option<ObjectId>;
+    type BodyData = BodyData;
     type JointData = ();
     type FixtureData = ();
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use wrapped2d::b2;
-use crate::{ComponentBase, Physics, LayerType};
+use crate::{ComponentBase, Physics, LayerType, PhysicsFixture};
+use crate::scene::{ComponentRegistry, PhysicsDescription};
 use std::any::Any;
 use wrapped2d::user_data::UserData;
 
@@ -9,36 +10,28 @@ const ID: u32 = 7;
 // Tag that states entity should be in physics simulation
 pub struct PhysicsComponent{
     id: u32,
-    pub shape: Box<dyn b2::Shape>,
-    pub body: b2::BodyDef,
+    // Every shape on the body, so the body can be rebuilt and saved
+    pub fixtures: Vec<PhysicsFixture>,
+    // The body's position, angle, velocity, gravity scale and enabled state aren't copied here, the methods below read the live body
     pub handle: b2::BodyHandle,
     pub body_type: b2::BodyType,
-    pub layer_type: u32,
+    pub layer_type: LayerType,
     pub allow_sleep: bool,
 }
 
 impl PhysicsComponent{
-    pub fn new_box(physics: &mut Physics, position: cgmath::Vector3::<f32>, scale: (f32, f32), mass: f32, body_type: b2::BodyType, layer_type: LayerType, allow_sleep: bool) -> Self{
-        let shape = physics.create_box_shape(scale.0, scale.1);
-        let body = physics.create_body(body_type, b2::Vec2 { x: position.x, y: position.y }, allow_sleep);
+    // A body made of any number of fixtures, each placed on the body by its own offset. `position` is in world units
+    pub fn new(physics: &mut Physics, position: cgmath::Vector3::<f32>, fixtures: Vec<PhysicsFixture>, body_type: b2::BodyType, layer_type: LayerType, allow_sleep: bool) -> Self{
+        let body = physics.create_body(body_type, physics.to_meters(position), allow_sleep);
         let handle = physics.create_handle(&body);
-        match body_type{
-            b2::BodyType::Static => {
-                physics.bind_to_world(&handle, &shape, 0.0, 0.3);
-            },
-            b2::BodyType::Kinematic => {
-                physics.bind_to_world(&handle, &shape, mass, 0.3);
-            },
-            b2::BodyType::Dynamic => {
-                physics.bind_to_world(&handle, &shape, mass, 0.3);
-            },
+        for fixture in fixtures.iter(){
+            physics.bind_fixture(&handle, fixture, layer_type);
         }
-        physics.world.body_mut(handle).set_user_data(Some(layer_type));
+        physics.world.body_mut(handle).user_data_mut().layer = layer_type;
 
         Self{
             id: ID,
-            shape,
-            body,
+            fixtures,
             handle,
             body_type,
             layer_type,
@@ -46,65 +39,157 @@ impl PhysicsComponent{
         }
     }
 
+    pub fn new_box(physics: &mut Physics, position: cgmath::Vector3::<f32>, scale: (f32, f32), mass: f32, body_type: b2::BodyType, layer_type: LayerType, allow_sleep: bool) -> Self{
+        let fixture = PhysicsFixture::new_box(scale.0, scale.1).with_density(mass);
+        PhysicsComponent::new(physics, position, vec!(fixture), body_type, layer_type, allow_sleep)
+    }
+
     pub fn new_circle(physics: &mut Physics, position: cgmath::Vector3::<f32>, scale: f32, mass: f32, body_type: b2::BodyType, layer_type: LayerType, allow_sleep: bool) -> Self{
-        let shape = physics.create_circle_shape(scale);
-        let body = physics.create_body(body_type, b2::Vec2 { x: position.x, y: position.y }, allow_sleep);
-        let handle = physics.create_handle(&body);
-        match body_type{
-            b2::BodyType::Static => {
-                physics.bind_to_world(&handle, &shape, 0.0, 0.3);
-            },
-            b2::BodyType::Kinematic => {
-                physics.bind_to_world(&handle, &shape, mass, 0.3);
-            },
-            b2::BodyType::Dynamic => {
-                physics.bind_to_world(&handle, &shape, mass, 0.3);
-            },
-        }
-        physics.world.body_mut(handle).set_user_data(Some(layer_type));
+        let fixture = PhysicsFixture::new_circle(scale).with_density(mass);
+        PhysicsComponent::new(physics, position, vec!(fixture), body_type, layer_type, allow_sleep)
+    }
 
-        Self{
-            id: ID,
-            shape,
-            body,
-            handle,
-            body_type,
-            layer_type,
-            allow_sleep,
-        }
+    // Convex, 3 to 8 points relative to the body
+    pub fn new_polygon(physics: &mut Physics, position: cgmath::Vector3::<f32>, points: Vec<b2::Vec2>, mass: f32, body_type: b2::BodyType, layer_type: LayerType, allow_sleep: bool) -> Result<Self, String>{
+        let fixture = PhysicsFixture::new_polygon(points)?.with_density(mass);
+        Ok(PhysicsComponent::new(physics, position, vec!(fixture), body_type, layer_type, allow_sleep))
+    }
+
+    // Static line for ground
+    pub fn new_edge(physics: &mut Physics, position: cgmath::Vector3::<f32>, from: b2::Vec2, to: b2::Vec2, layer_type: LayerType) -> Result<Self, String>{
+        let fixture = PhysicsFixture::new_edge(from, to)?;
+        Ok(PhysicsComponent::new(physics, position, vec!(fixture), b2::BodyType::Static, layer_type, false))
     }
 
+    // Static terrain following the points. `looped` joins the last point back to the first
+    pub fn new_chain(physics: &mut Physics, position: cgmath::Vector3::<f32>, points: Vec<b2::Vec2>, looped: bool, layer_type: LayerType) -> Result<Self, String>{
+        let fixture = PhysicsFixture::new_chain(points, looped)?;
+        Ok(PhysicsComponent::new(physics, position, vec!(fixture), b2::BodyType::Static, layer_type, false))
+    }
+
+    // Attach another shape to the live body. The body's mass is recalculated by Box2D
+    pub fn add_fixture(&mut self, physics: &mut Physics, fixture: PhysicsFixture){
+        physics.bind_fixture(&self.handle, &fixture, self.layer_type);
+        self.fixtures.push(fixture);
+    }
+
+    // Move the body to another collision layer. Its fixtures keep their groups
+    pub fn set_layer(&mut self, physics: &mut Physics, layer: LayerType){
+        self.layer_type = layer;
+        physics.world.body_mut(self.handle).user_data_mut().layer = layer;
+        physics.refilter_body(self.handle);
+    }
+
+    // Everything below acts on the live body, so contacts, spin, sleep state and mass are kept
+
+    // Move the body, keeping its angle and velocity. Wakes it up
     pub fn update_position(&mut self, physics: &mut Physics, new_pos: b2::Vec2){
-        self.body.position = new_pos;
-        self.update(physics);
-    }
-
-    pub fn update(&mut self, physics: &mut Physics){
-        physics.world.destroy_body(self.handle);
-
-        self.handle = physics.create_handle(&self.body);
-        match self.body_type{
-            b2::BodyType::Static => {
-                physics.bind_to_world(&self.handle, &self.shape, 0.0, 0.3);
-            },
-            b2::BodyType::Kinematic => {
-                physics.bind_to_world(&self.handle, &self.shape, 1.0, 0.3);
-            },
-            b2::BodyType::Dynamic => {
-                physics.bind_to_world(&self.handle, &self.shape, 1.0, 0.3);
-            },
-        }
-        physics.world.body_mut(self.handle).set_user_data(Some(self.layer_type));
+        let angle = physics.world.body(self.handle).angle();
+        self.set_transform(physics, new_pos, angle);
+    }
+
+    // Teleport the body (Angle in radians). Wakes it up, so a sleeping body falls from its new spot
+    pub fn set_transform(&mut self, physics: &mut Physics, position: b2::Vec2, angle: f32){
+        let mut body = physics.world.body_mut(self.handle);
+        body.set_transform(&position, angle);
+        body.set_awake(true);
+    }
+
+    // In meters
+    pub fn get_position(&self, physics: &Physics) -> b2::Vec2{
+        *physics.world.body(self.handle).position()
+    }
+
+    // Radians, counter clockwise
+    pub fn get_angle(&self, physics: &Physics) -> f32{
+        physics.world.body(self.handle).angle()
+    }
+
+    pub fn set_velocity(&mut self, physics: &mut Physics, velocity: b2::Vec2){
+        physics.world.body_mut(self.handle).set_linear_velocity(&velocity);
+    }
+
+    pub fn get_velocity(&self, physics: &Physics) -> b2::Vec2{
+        *physics.world.body(self.handle).linear_velocity()
+    }
+
+    // Radians per second, counter clockwise
+    pub fn set_angular_velocity(&mut self, physics: &mut Physics, velocity: f32){
+        physics.world.body_mut(self.handle).set_angular_velocity(velocity);
+    }
+
+    pub fn get_angular_velocity(&self, physics: &Physics) -> f32{
+        physics.world.body(self.handle).angular_velocity()
+    }
+
+    // Forces act over the next step, so they're for things that push continuously (Wind, thrusters).
+    // `point` is in world space, off center forces also spin the body
+    pub fn apply_force(&mut self, physics: &mut Physics, force: b2::Vec2, point: b2::Vec2){
+        physics.world.body_mut(self.handle).apply_force(&force, &point, true);
+    }
+
+    pub fn apply_force_to_center(&mut self, physics: &mut Physics, force: b2::Vec2){
+        physics.world.body_mut(self.handle).apply_force_to_center(&force, true);
+    }
+
+    // Impulses change the velocity straight away, so they're for one-off hits (Jumps, knockback).
+    // `point` is in world space
+    pub fn apply_impulse(&mut self, physics: &mut Physics, impulse: b2::Vec2, point: b2::Vec2){
+        physics.world.body_mut(self.handle).apply_linear_impulse(&impulse, &point, true);
+    }
+
+    pub fn apply_impulse_to_center(&mut self, physics: &mut Physics, impulse: b2::Vec2){
+        let mut body = physics.world.body_mut(self.handle);
+        let center = *body.world_center();
+        body.apply_linear_impulse(&impulse, &center, true);
     }
 
-    pub fn set_velocity(&mut self, physics: &mut Physics, offset: b2::Vec2){
-        self.body.linear_velocity = offset;
-        self.update(physics);
+    pub fn apply_torque(&mut self, physics: &mut Physics, torque: f32){
+        physics.world.body_mut(self.handle).apply_torque(torque, true);
+    }
+
+    pub fn apply_angular_impulse(&mut self, physics: &mut Physics, impulse: f32){
+        physics.world.body_mut(self.handle).apply_angular_impulse(impulse, true);
+    }
+
+    // 1 is normal gravity, 0 floats, negative falls upwards
+    pub fn set_gravity_scale(&mut self, physics: &mut Physics, scale: f32){
+        physics.world.body_mut(self.handle).set_gravity_scale(scale);
+    }
+
+    pub fn get_gravity_scale(&self, physics: &Physics) -> f32{
+        physics.world.body(self.handle).gravity_scale()
+    }
+
+    // A disabled body stays in the world but doesn't move or collide, e.g. for pooled or hidden entities
+    pub fn set_enabled(&mut self, physics: &mut Physics, enabled: bool){
+        physics.world.body_mut(self.handle).set_active(enabled);
+    }
+
+    pub fn is_enabled(&self, physics: &Physics) -> bool{
+        physics.world.body(self.handle).is_active()
+    }
+
+    // Mass as calculated by Box2D from the fixtures' densities
+    pub fn get_mass(&self, physics: &Physics) -> f32{
+        physics.world.body(self.handle).mass()
     }
 
     pub fn get_component_id() -> u32{
         ID
     }
+
+    // physics(type, mass, width, height, layer, allow_sleep), or the block form with fixtures - see PhysicsDescription.
+    // Layers are given by number or by a name from the physics' CollisionLayers
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.register_with_saver("PhysicsComponent", ID, "physics", |node, context| {
+            let physics_def = PhysicsDescription::from_node(node, context.physics.get_collision_layers())?;
+            Ok(Box::new(PhysicsComponent::new(context.physics, context.position, physics_def.get_fixtures(), physics_def.body_type, physics_def.layer, physics_def.allow_sleep)))
+        }, |component, physics_manager| {
+            let physics = component.as_any().downcast_ref::<PhysicsComponent>()?;
+            Some(PhysicsDescription::from_fixtures(physics.body_type, physics.layer_type, physics.allow_sleep, &physics.fixtures).to_node(physics_manager.get_collision_layers()))
+        })
+    }
 }
 
 impl ComponentBase for PhysicsComponent{
@@ -118,5 +203,8 @@ impl ComponentBase for PhysicsComponent{
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
     
 }
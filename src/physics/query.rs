@@ -0,0 +1,295 @@
+use wrapped2d::b2;
+use wrapped2d::b2::Shape;
+use wrapped2d::user_data::UserData;
+use crate::{Physics, EntityHandle, LayerType};
+use crate::physics::layers::MAX_LAYERS;
+
+// What a query can hit. Bodies that aren't on an entity (Or were created since the last step, see Physics::get_entity) are never hit
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryFilter{
+    // Bit i is set if bodies on layer i can be hit
+    pub layers: u16,
+    // Usually the entity asking, so a probe doesn't hit its own body
+    pub ignore: Option<EntityHandle>,
+    // Sensors are skipped unless this is set
+    pub sensors: bool,
+}
+
+impl QueryFilter{
+    // Every layer, no sensors
+    pub fn new() -> Self{
+        Self{
+            layers: 0xFFFF,
+            ignore: None,
+            sensors: false,
+        }
+    }
+
+    // Only bodies on these layers
+    pub fn only(layers: &[LayerType]) -> Self{
+        let mut filter = QueryFilter::new();
+        filter.layers = 0;
+        for layer in layers.iter(){
+            filter = filter.with_layer(*layer);
+        }
+        filter
+    }
+
+    pub fn with_layer(mut self, layer: LayerType) -> Self{
+        if (layer as usize) < MAX_LAYERS{
+            self.layers |= 1 << layer;
+        }
+        self
+    }
+
+    pub fn without_layer(mut self, layer: LayerType) -> Self{
+        if (layer as usize) < MAX_LAYERS{
+            self.layers &= !(1 << layer);
+        }
+        self
+    }
+
+    pub fn ignoring(mut self, entity: EntityHandle) -> Self{
+        self.ignore = Some(entity);
+        self
+    }
+
+    pub fn with_sensors(mut self, sensors: bool) -> Self{
+        self.sensors = sensors;
+        self
+    }
+
+    pub fn accepts(&self, entity: EntityHandle, layer: LayerType, is_sensor: bool) -> bool{
+        if self.ignore == Some(entity) || (is_sensor && !self.sensors){
+            return false;
+        }
+        (layer as usize) >= MAX_LAYERS || self.layers & (1 << layer) != 0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RaycastHit{
+    pub entity: EntityHandle,
+    pub point: b2::Vec2,
+    // The surface's normal at the point, facing back along the ray
+    pub normal: b2::Vec2,
+    // How far along the ray, 0 at the start and 1 at the end
+    pub fraction: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapeCastHit{
+    pub entity: EntityHandle,
+    // Where the shape's origin is when it touches
+    pub position: b2::Vec2,
+    // The surface's normal, facing the cast shape. Zero if the shape started out overlapping it
+    pub normal: b2::Vec2,
+    // How far along the cast, 0 at the start and 1 at the end
+    pub fraction: f32,
+}
+
+// Rays don't hit shapes they start inside, and only the first hit on each fixture is reported
+impl Physics{
+    // The nearest hit along the ray. For line of sight, check if it's the entity being looked at
+    pub fn raycast_closest(&self, from: b2::Vec2, to: b2::Vec2, filter: &QueryFilter) -> Option<RaycastHit>{
+        let mut closest = None;
+        self.raycast(from, to, filter, |hit| {
+            closest = Some(hit);
+            // Only look for hits nearer than this one from now on
+            hit.fraction
+        });
+        closest
+    }
+
+    // Any hit along the ray, whichever Box2D finds first. Cheaper than raycast_closest when only a yes or no is needed
+    pub fn raycast_any(&self, from: b2::Vec2, to: b2::Vec2, filter: &QueryFilter) -> Option<RaycastHit>{
+        let mut any = None;
+        self.raycast(from, to, filter, |hit| {
+            any = Some(hit);
+            0.0
+        });
+        any
+    }
+
+    // Every hit along the ray, nearest first
+    pub fn raycast_all(&self, from: b2::Vec2, to: b2::Vec2, filter: &QueryFilter) -> Vec<RaycastHit>{
+        let mut hits = Vec::<RaycastHit>::new();
+        self.raycast(from, to, filter, |hit| {
+            hits.push(hit);
+            1.0
+        });
+        hits.sort_by(|a, b| a.fraction.partial_cmp(&b.fraction).unwrap_or(std::cmp::Ordering::Equal));
+        hits
+    }
+
+    // `on_hit` returns what Box2D should do next: 0 stops, 1 carries on and a fraction clips the ray there
+    fn raycast<F: FnMut(RaycastHit) -> f32>(&self, from: b2::Vec2, to: b2::Vec2, filter: &QueryFilter, mut on_hit: F){
+        // Box2D asserts on zero length rays
+        if (to.x - from.x).powi(2) + (to.y - from.y).powi(2) <= f32::EPSILON{
+            return;
+        }
+        let mut callback = |body: b2::BodyHandle, fixture: b2::FixtureHandle, point: &b2::Vec2, normal: &b2::Vec2, fraction: f32| -> f32{
+            match self.accepted_entity(body, fixture, filter){
+                Some(entity) => on_hit(RaycastHit { entity, point: *point, normal: *normal, fraction }),
+                // Carry on as if the fixture wasn't there
+                None => -1.0,
+            }
+        };
+        self.world.ray_cast(&mut callback, &from, &to);
+    }
+
+    // Entities with a fixture overlapping the box (By its bounding box, so a circle's corners count)
+    pub fn query_aabb(&self, lower: b2::Vec2, upper: b2::Vec2, filter: &QueryFilter) -> Vec<EntityHandle>{
+        let aabb = b2::AABB { lower, upper };
+        let mut entities = Vec::<EntityHandle>::new();
+        self.for_each_fixture_in(&aabb, filter, |entity, _, _| {
+            if !entities.contains(&entity){
+                entities.push(entity);
+            }
+        });
+        entities
+    }
+
+    // Entities with a fixture touching the circle
+    pub fn overlap_circle(&self, center: b2::Vec2, radius: f32, filter: &QueryFilter) -> Vec<EntityHandle>{
+        let shape = b2::CircleShape::new_with(b2::Vec2 { x: 0.0, y: 0.0 }, radius);
+        self.overlap_shape(&shape, center, 0.0, filter)
+    }
+
+    // Entities with a fixture touching the shape, placed at `position` and turned by `angle` (Radians)
+    pub fn overlap_shape<S: b2::Shape>(&self, shape: &S, position: b2::Vec2, angle: f32, filter: &QueryFilter) -> Vec<EntityHandle>{
+        let transform = b2::Transform { pos: position, rot: b2::Rot::from_angle(angle) };
+        let aabb = shape.compute_aabb(&transform, 0);
+        let mut entities = Vec::<EntityHandle>::new();
+        self.for_each_fixture_in(&aabb, filter, |entity, body, fixture| {
+            if entities.contains(&entity){
+                return;
+            }
+            let body = self.world.body(body);
+            let fixture = body.fixture(fixture);
+            let fixture_shape = fixture.shape();
+            for child in 0..fixture_shape.child_count(){
+                if b2::test_overlap(shape, 0, &transform, &*fixture_shape, child, body.transform()){
+                    entities.push(entity);
+                    break;
+                }
+            }
+        });
+        entities
+    }
+
+    pub fn circle_cast(&self, from: b2::Vec2, to: b2::Vec2, radius: f32, filter: &QueryFilter) -> Option<ShapeCastHit>{
+        let shape = b2::CircleShape::new_with(b2::Vec2 { x: 0.0, y: 0.0 }, radius);
+        self.shape_cast(&shape, from, to, 0.0, filter)
+    }
+
+    // Half extents, like PhysicsFixture::new_box
+    pub fn box_cast(&self, from: b2::Vec2, to: b2::Vec2, half_width: f32, half_height: f32, angle: f32, filter: &QueryFilter) -> Option<ShapeCastHit>{
+        let shape = b2::PolygonShape::new_box(half_width, half_height);
+        self.shape_cast(&shape, from, to, angle, filter)
+    }
+
+    // Sweep the shape in a straight line from `from` to `to` without turning, and return the first thing it touches
+    pub fn shape_cast<S: b2::Shape>(&self, shape: &S, from: b2::Vec2, to: b2::Vec2, angle: f32, filter: &QueryFilter) -> Option<ShapeCastHit>{
+        let rot = b2::Rot::from_angle(angle);
+        let start = shape.compute_aabb(&b2::Transform { pos: from, rot }, 0);
+        let end = shape.compute_aabb(&b2::Transform { pos: to, rot }, 0);
+        let swept = b2::AABB{
+            lower: b2::Vec2 { x: start.lower.x.min(end.lower.x), y: start.lower.y.min(end.lower.y) },
+            upper: b2::Vec2 { x: start.upper.x.max(end.upper.x), y: start.upper.y.max(end.upper.y) },
+        };
+        let sweep = b2::Sweep { local_center: b2::Vec2 { x: 0.0, y: 0.0 }, c0: from, c: to, a0: angle, a: angle, alpha0: 0.0 };
+
+        let mut closest: Option<(EntityHandle, b2::BodyHandle, b2::FixtureHandle, i32, f32)> = None;
+        self.for_each_fixture_in(&swept, filter, |entity, body_handle, fixture_handle| {
+            let body = self.world.body(body_handle);
+            let fixture = body.fixture(fixture_handle);
+            let fixture_shape = fixture.shape();
+            let position = *body.position();
+            let body_sweep = b2::Sweep { local_center: b2::Vec2 { x: 0.0, y: 0.0 }, c0: position, c: position, a0: body.angle(), a: body.angle(), alpha0: 0.0 };
+            for child in 0..fixture_shape.child_count(){
+                let input = b2::time_of_impact::Input::new(b2::distance::Proxy::new(shape, 0), b2::distance::Proxy::new(&*fixture_shape, child), sweep.clone(), body_sweep.clone(), 1.0);
+                let output = input.query();
+                let fraction = match output.state{
+                    b2::time_of_impact::State::Touching => output.t,
+                    b2::time_of_impact::State::Overlapped => 0.0,
+                    _ => continue,
+                };
+                if closest.map_or(true, |c| fraction < c.4){
+                    closest = Some((entity, body_handle, fixture_handle, child, fraction));
+                }
+            }
+        });
+
+        let (entity, body_handle, fixture_handle, child, fraction) = closest?;
+        let position = b2::Vec2 { x: from.x + (to.x - from.x) * fraction, y: from.y + (to.y - from.y) * fraction };
+        // TOI stops with the shapes' skins touching, so the closest points between the shapes without their skins give the normal
+        let body = self.world.body(body_handle);
+        let fixture = body.fixture(fixture_handle);
+        let fixture_shape = fixture.shape();
+        let mut cache = b2::distance::SimplexCache { metric: 0.0, count: 0, index_a: [0; 3], index_b: [0; 3] };
+        let input = b2::distance::Input::new(b2::distance::Proxy::new(shape, 0), b2::distance::Proxy::new(&*fixture_shape, child), b2::Transform { pos: position, rot }, body.transform().clone(), false);
+        let output = input.query(&mut cache);
+        let offset = b2::Vec2 { x: output.point_a.x - output.point_b.x, y: output.point_a.y - output.point_b.y };
+        let length = (offset.x * offset.x + offset.y * offset.y).sqrt();
+        let normal = if length > f32::EPSILON { b2::Vec2 { x: offset.x / length, y: offset.y / length } } else { b2::Vec2 { x: 0.0, y: 0.0 } };
+
+        Some(ShapeCastHit { entity, position, normal, fraction })
+    }
+
+    // The smallest box around every fixture on the body, where it is now
+    pub fn get_body_aabb(&self, handle: b2::BodyHandle) -> Option<b2::AABB>{
+        let body = self.world.body(handle);
+        let mut bounds: Option<b2::AABB> = None;
+        for (_, fixture) in body.fixtures(){
+            let fixture = fixture.borrow();
+            let shape = fixture.shape();
+            for child in 0..shape.child_count(){
+                let aabb = shape.compute_aabb(body.transform(), child);
+                bounds = Some(match bounds{
+                    Some(b) => b2::AABB{
+                        lower: b2::Vec2 { x: b.lower.x.min(aabb.lower.x), y: b.lower.y.min(aabb.lower.y) },
+                        upper: b2::Vec2 { x: b.upper.x.max(aabb.upper.x), y: b.upper.y.max(aabb.upper.y) },
+                    },
+                    None => aabb,
+                });
+            }
+        }
+        bounds
+    }
+
+    // Every fixture the filter accepts whose bounding box overlaps `aabb`. Box2D's tree uses padded boxes, so they're checked again here
+    fn for_each_fixture_in<F: FnMut(EntityHandle, b2::BodyHandle, b2::FixtureHandle)>(&self, aabb: &b2::AABB, filter: &QueryFilter, mut on_fixture: F){
+        let mut found = Vec::<(EntityHandle, b2::BodyHandle, b2::FixtureHandle)>::new();
+        let mut callback = |body: b2::BodyHandle, fixture: b2::FixtureHandle| -> bool{
+            if let Some(entity) = self.accepted_entity(body, fixture, filter){
+                found.push((entity, body, fixture));
+            }
+            true
+        };
+        self.world.query_aabb(&mut callback, aabb);
+
+        for (entity, body_handle, fixture_handle) in found.into_iter(){
+            let touches = {
+                let body = self.world.body(body_handle);
+                let fixture = body.fixture(fixture_handle);
+                let shape = fixture.shape();
+                (0..shape.child_count()).any(|child| {
+                    let bounds = shape.compute_aabb(body.transform(), child);
+                    bounds.lower.x <= aabb.upper.x && bounds.upper.x >= aabb.lower.x && bounds.lower.y <= aabb.upper.y && bounds.upper.y >= aabb.lower.y
+                })
+            };
+            if touches{
+                on_fixture(entity, body_handle, fixture_handle);
+            }
+        }
+    }
+
+    fn accepted_entity(&self, body: b2::BodyHandle, fixture: b2::FixtureHandle, filter: &QueryFilter) -> Option<EntityHandle>{
+        let entity = self.get_entity(body)?;
+        let body = self.world.body(body);
+        let layer = body.user_data().layer;
+        let is_sensor = body.fixture(fixture).is_sensor();
+        if filter.accepts(entity, layer, is_sensor) { Some(entity) } else { None }
+    }
+}
@@ -0,0 +1,670 @@
+use wrapped2d::b2;
+use std::fmt::Write;
+use crate::physics::collision::RestoredContact;
+use crate::{Physics, PhysicsComponent, JointComponent, JointKind, PhysicsFixture, EntityManager, EntityHandle, Transform, LayerType, CollisionLayers};
+use crate::scene::{SceneError, SceneErrors, SceneWriter, PhysicsDescription, SCENE_FORMAT_VERSION};
+use crate::scene::parser::{Parser, ComponentNode, EntityNode, Value};
+use crate::scene::args::{error_at, describe, missing_arg, number_arg, bool_arg};
+
+// One entity's body and PhysicsComponent when the snapshot was taken. Positions are in meters
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodySnapshot{
+    pub entity: EntityHandle,
+    pub position: b2::Vec2,
+    pub angle: f32,
+    pub velocity: b2::Vec2,
+    pub spin: f32,
+    pub awake: bool,
+    pub enabled: bool,
+    pub gravity_scale: f32,
+    pub body_type: b2::BodyType,
+    pub layer: LayerType,
+    pub allow_sleep: bool,
+    pub fixtures: Vec<PhysicsFixture>,
+    // The entity's JointComponent, in the same order
+    pub joints: Vec<JointSnapshot>,
+}
+
+// What a joint was connected to, and the settings that can change while it's live (See JointComponent::set_motor and the rest)
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointSnapshot{
+    pub kind: JointKind,
+    pub connected: bool,
+    // The entity at the other end, None for the world
+    pub other: Option<EntityHandle>,
+    pub limit: Option<(f32, f32)>,
+    pub motor: Option<(f32, f32)>,
+    pub length: Option<f32>,
+    pub frequency: f32,
+    pub damping: f32,
+    // Where a mouse joint pulls to
+    pub target: Option<b2::Vec2>,
+}
+
+// The impulses of a touching contact. Box2D starts the next step's solver from them (Warm starting), so without them
+// stacks and resting bodies would come out slightly differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactSnapshot{
+    pub entity_a: EntityHandle,
+    pub entity_b: EntityHandle,
+    // Index of the fixture in each body's PhysicsComponent, and the child (Edge of a chain) that touches
+    pub fixture_a: (usize, i32),
+    pub fixture_b: (usize, i32),
+    // Box2D's id of each contact point, with its normal and tangent impulse. None for sensors, which only overlap
+    pub points: Vec<(u32, f32, f32)>,
+}
+
+// Everything that moves in the physics world, for rewinding, quick saves and rollback. Taken between steps,
+// a restore puts the bodies back so the following steps play out the same way as after the snapshot was taken.
+// Settings (PhysicsConfig, collision layers) are the scene's and aren't part of it.
+// Written in the .dbscene syntax (See to_source), one entity[...] per body
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicsSnapshot{
+    // In entity handle order
+    pub bodies: Vec<BodySnapshot>,
+    pub contacts: Vec<ContactSnapshot>,
+}
+
+impl PhysicsSnapshot{
+    // Every entity with a PhysicsComponent
+    pub fn take(entity_manager: &EntityManager, physics: &Physics) -> Self{
+        let mut bodies = Vec::<BodySnapshot>::new();
+        let mut fixtures = Vec::<((b2::BodyHandle, b2::FixtureHandle), (EntityHandle, usize))>::new();
+        for (entity, component) in entity_manager.query::<&PhysicsComponent>().iter(){
+            let body = physics.world.body(component.handle);
+            for (index, (handle, _)) in body.fixtures().enumerate(){
+                fixtures.push(((component.handle, handle), (entity, index)));
+            }
+            let joints = match entity_manager.get_component::<JointComponent>(entity){
+                Some(joints) => joints.joints.iter().map(|link| JointSnapshot{
+                    kind: link.joint.kind,
+                    connected: link.handle.map(|handle| physics.has_joint(handle)).unwrap_or(false),
+                    other: link.other,
+                    limit: link.joint.limit,
+                    motor: link.joint.motor,
+                    length: link.joint.length,
+                    frequency: link.joint.frequency,
+                    damping: link.joint.damping,
+                    target: if link.joint.kind == JointKind::Mouse { link.joint.other_anchor } else { None },
+                }).collect(),
+                None => Vec::<JointSnapshot>::new(),
+            };
+            bodies.push(BodySnapshot{
+                entity,
+                position: *body.position(),
+                angle: body.angle(),
+                velocity: *body.linear_velocity(),
+                spin: body.angular_velocity(),
+                awake: body.is_awake(),
+                enabled: body.is_active(),
+                gravity_scale: body.gravity_scale(),
+                body_type: component.body_type,
+                layer: component.layer_type,
+                allow_sleep: component.allow_sleep,
+                fixtures: component.fixtures.clone(),
+                joints,
+            });
+        }
+        bodies.sort_by_key(|body| (body.entity.index, body.entity.generation));
+
+        let find = |key: (b2::BodyHandle, b2::FixtureHandle)| fixtures.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+        let mut contacts = Vec::<ContactSnapshot>::new();
+        for contact in physics.world.contacts(){
+            // Sensors touch without any points
+            if !contact.is_touching(){
+                continue;
+            }
+            let manifold = contact.manifold();
+            // Bodies without an entity (The ground joints hold on to) have no fixtures to touch
+            let (a, b) = match (find(contact.fixture_a()), find(contact.fixture_b())){
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+            contacts.push(ContactSnapshot{
+                entity_a: a.0,
+                entity_b: b.0,
+                fixture_a: (a.1, contact.child_index_a()),
+                fixture_b: (b.1, contact.child_index_b()),
+                points: manifold.points[..manifold.count as usize].iter().map(|p| (p.id.key(), p.normal_impulse, p.tangent_impulse)).collect(),
+            });
+        }
+        contacts.sort_by_key(|c| (c.entity_a.index, c.entity_a.generation, c.fixture_a, c.entity_b.index, c.entity_b.generation, c.fixture_b));
+
+        Self{
+            bodies,
+            contacts,
+        }
+    }
+
+    // Put every body in the snapshot back, along with its PhysicsComponent, joint settings and Transform. Entities spawned since
+    // are left as they are. Fails without changing anything if a body in the snapshot is gone or its joints don't line up.
+    // Box2D's own joint impulses can't be read, so joints are warm started from where they are now rather than where they were.
+    // Scenes that have to rewind exactly turn PhysicsConfig::warm_starting off. Neither can how long a body has been still for,
+    // so awake bodies start counting towards time_to_sleep again, and scenes that rewind exactly past a pile falling asleep turn sleep off
+    pub fn restore(&self, entity_manager: &EntityManager, physics: &mut Physics) -> Result<(), String>{
+        for snapshot in self.bodies.iter(){
+            snapshot.check(entity_manager)?;
+        }
+
+        // Joints before moving the bodies, changing a joint wakes them
+        for snapshot in self.bodies.iter(){
+            let mut component = entity_manager.get_component_mut::<PhysicsComponent>(snapshot.entity).unwrap();
+            snapshot.restore_component(&mut component, physics);
+            if let Some(mut joints) = entity_manager.get_component_mut::<JointComponent>(snapshot.entity){
+                for (index, joint) in snapshot.joints.iter().enumerate(){
+                    joint.restore(&mut joints, index, physics)?;
+                }
+            }
+        }
+        for snapshot in self.bodies.iter(){
+            let mut component = entity_manager.get_component_mut::<PhysicsComponent>(snapshot.entity).unwrap();
+            snapshot.restore_body(&mut component, physics);
+            if let Some(mut transform) = entity_manager.get_component_mut::<Transform>(snapshot.entity){
+                // Like PhysicsSystem, the others are moved by whatever moves them
+                if snapshot.body_type == b2::BodyType::Dynamic{
+                    transform.set_position(physics.to_units(&snapshot.position));
+                    transform.set_rotation(cgmath::Quaternion::from(cgmath::Euler {
+                        x: cgmath::Deg(0.0),
+                        y: cgmath::Deg(0.0),
+                        z: cgmath::Rad(snapshot.angle).into(),
+                    }));
+                    transform.store_previous();
+                }
+            }
+        }
+
+        self.restore_contacts(entity_manager, physics);
+        log::info!("Restored a physics snapshot of {} bodies", self.bodies.len());
+        Ok(())
+    }
+
+    // Contacts are made and destroyed by Box2D, which makes the ones between the rebuilt fixtures on the next step.
+    // The listener matches them up with the snapshot's by fixture, see Physics::set_restored_contacts
+    fn restore_contacts(&self, entity_manager: &EntityManager, physics: &mut Physics){
+        let mut fixtures = Vec::<((EntityHandle, usize), (b2::BodyHandle, b2::FixtureHandle))>::new();
+        for snapshot in self.bodies.iter(){
+            let handle = entity_manager.get_component::<PhysicsComponent>(snapshot.entity).unwrap().handle;
+            for (index, (fixture, _)) in physics.world.body(handle).fixtures().enumerate(){
+                fixtures.push(((snapshot.entity, index), (handle, fixture)));
+            }
+        }
+        let find = |key: (EntityHandle, usize)| fixtures.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+
+        let mut restored = Vec::<RestoredContact>::new();
+        for contact in self.contacts.iter(){
+            if let (Some(a), Some(b)) = (find((contact.entity_a, contact.fixture_a.0)), find((contact.entity_b, contact.fixture_b.0))){
+                restored.push(RestoredContact{
+                    fixture_a: (a, contact.fixture_a.1),
+                    fixture_b: (b, contact.fixture_b.1),
+                    points: contact.points.clone(),
+                });
+            }
+        }
+        physics.set_restored_contacts(restored);
+    }
+
+    // The snapshot as .dbscene source. Floats use {:?} so they parse back to the same value
+    pub fn to_source(&self) -> String{
+        let mut out = String::new();
+        writeln!(out, "// Physics snapshot of {} bodies, see PhysicsSnapshot", self.bodies.len()).unwrap();
+        writeln!(out, "version {};", SCENE_FORMAT_VERSION).unwrap();
+        writeln!(out).unwrap();
+        for body in self.bodies.iter(){
+            let mut nodes = body.to_nodes();
+            for contact in self.contacts.iter().filter(|c| c.entity_a == body.entity){
+                nodes.push(contact.to_node());
+            }
+            let parts: Vec<String> = nodes.iter().map(|node| SceneWriter::write_component(node)).collect();
+            writeln!(out, "entity[{}];", parts.join(" ")).unwrap();
+        }
+        out
+    }
+
+    // Every problem in the source is reported at once, like scenes
+    pub fn parse(source: &str) -> Result<Self, SceneErrors>{
+        let (node, mut errors) = Parser::parse(source);
+        let version = node.version.unwrap_or(0);
+        if version > SCENE_FORMAT_VERSION{
+            errors.push(SceneError::new(1, 1, format!("snapshot format version {} is newer than the supported version {}", version, SCENE_FORMAT_VERSION))
+                .hint("update the engine, or take the snapshot again with this version"));
+        }
+        let mut bodies = Vec::<BodySnapshot>::new();
+        let mut contacts = Vec::<ContactSnapshot>::new();
+        for entity in node.entities.iter(){
+            match BodySnapshot::from_node(entity){
+                Ok((body, mut body_contacts)) => {
+                    bodies.push(body);
+                    contacts.append(&mut body_contacts);
+                },
+                Err(e) => errors.extend(e.errors),
+            }
+        }
+        if !node.includes.is_empty() || !node.prefabs.is_empty() || !node.settings.is_empty(){
+            errors.push(SceneError::new(0, 0, "snapshots only hold entity[...] bodies".to_string())
+                .hint("includes, prefabs and settings blocks belong in scenes"));
+        }
+        if errors.is_empty(){
+            Ok(Self{
+                bodies,
+                contacts,
+            })
+        }else{
+            errors.sort_by_key(|e| (e.line, e.column));
+            Err(SceneErrors::new(errors))
+        }
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()>{
+        std::fs::write(path, self.to_source())?;
+        log::info!("Saved a physics snapshot of {} bodies to {}", self.bodies.len(), path);
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, SceneErrors>{
+        let source = match std::fs::read_to_string(path){
+            Ok(v) => v,
+            Err(e) => return Err(SceneErrors::new(vec!(SceneError::new(0, 0, format!("could not read file: {}", e)).path(path)))),
+        };
+        PhysicsSnapshot::parse(&source).map_err(|e| {
+            SceneErrors::new(e.errors.into_iter().map(|error| error.path(path)).collect())
+        })
+    }
+}
+
+impl BodySnapshot{
+    // The entity's body, if the snapshot still fits it
+    fn check(&self, entity_manager: &EntityManager) -> Result<b2::BodyHandle, String>{
+        let handle = match entity_manager.get_component::<PhysicsComponent>(self.entity){
+            Some(component) => component.handle,
+            None => return Err(format!("Entity {}:{} in the snapshot has no physics body, it was destroyed or lost its PhysicsComponent",
+                self.entity.index, self.entity.generation)),
+        };
+        let live: Vec<JointKind> = match entity_manager.get_component::<JointComponent>(self.entity){
+            Some(joints) => joints.joints.iter().map(|link| link.joint.kind).collect(),
+            None => Vec::<JointKind>::new(),
+        };
+        let saved: Vec<JointKind> = self.joints.iter().map(|joint| joint.kind).collect();
+        if live != saved{
+            return Err(format!("Entity {}:{} had joints {:?} in the snapshot and has {:?} now, joints added or removed since can't be rewound",
+                self.entity.index, self.entity.generation, saved, live));
+        }
+        Ok(handle)
+    }
+
+    fn restore_component(&self, component: &mut PhysicsComponent, physics: &mut Physics){
+        if component.layer_type != self.layer{
+            component.set_layer(physics, self.layer);
+        }
+        // Compared as they're written, fixture angles are saved in degrees and don't always come back to the same bits
+        let written = |fixtures: &[PhysicsFixture]| PhysicsDescription::from_fixtures(b2::BodyType::Static, 0, false, fixtures).to_node(&CollisionLayers::new());
+        if written(&component.fixtures) != written(&self.fixtures){
+            component.fixtures = self.fixtures.clone();
+        }
+        // Always rebuilt, even when they're the same. Box2D only looks for new contacts after a fixture is added,
+        // and the bodies are about to be moved to where the snapshot has them
+        let fixtures: Vec<b2::FixtureHandle> = physics.world.body(component.handle).fixtures().map(|(handle, _)| handle).collect();
+        for handle in fixtures.into_iter(){
+            physics.world.body_mut(component.handle).destroy_fixture(handle);
+        }
+        for fixture in component.fixtures.iter(){
+            physics.bind_fixture(&component.handle, fixture, component.layer_type);
+        }
+        if component.is_enabled(physics) != self.enabled{
+            component.set_enabled(physics, self.enabled);
+        }
+        if physics.world.body(component.handle).gravity_scale() != self.gravity_scale{
+            component.set_gravity_scale(physics, self.gravity_scale);
+        }
+        let mut body = physics.world.body_mut(component.handle);
+        if component.body_type != self.body_type{
+            body.set_body_type(self.body_type);
+            component.body_type = self.body_type;
+        }
+        if component.allow_sleep != self.allow_sleep{
+            body.set_sleeping_allowed(self.allow_sleep);
+            component.allow_sleep = self.allow_sleep;
+        }
+    }
+
+    // Velocities go after the transform, and sleep after both, since moving a body wakes it and a body put to sleep stops
+    fn restore_body(&self, component: &mut PhysicsComponent, physics: &mut Physics){
+        component.set_transform(physics, self.position, self.angle);
+        component.set_velocity(physics, self.velocity);
+        component.set_angular_velocity(physics, self.spin);
+        if !self.awake{
+            physics.world.body_mut(component.handle).set_awake(false);
+        }
+    }
+
+    // handle(index, generation) body(x, y, angle) velocity(x, y, spin) awake(bool) enabled(bool) gravity_scale(n),
+    // the PhysicsComponent as the scene's physics{...} and a joint(kind){...} for each joint
+    fn to_nodes(&self) -> Vec<ComponentNode>{
+        let mut nodes = Vec::<ComponentNode>::new();
+        nodes.push(ComponentNode::new("handle", handle_values(self.entity)));
+        nodes.push(ComponentNode::new("body", vec!(Value::Number(self.position.x), Value::Number(self.position.y), Value::Number(self.angle))));
+        nodes.push(ComponentNode::new("velocity", vec!(Value::Number(self.velocity.x), Value::Number(self.velocity.y), Value::Number(self.spin))));
+        nodes.push(ComponentNode::new("awake", vec!(Value::Bool(self.awake))));
+        nodes.push(ComponentNode::new("enabled", vec!(Value::Bool(self.enabled))));
+        nodes.push(ComponentNode::new("gravity_scale", vec!(Value::Number(self.gravity_scale))));
+        // Only the engine's own layers are written by name, so the scene's names aren't needed to read it back
+        nodes.push(PhysicsDescription::from_fixtures(self.body_type, self.layer, self.allow_sleep, &self.fixtures).to_node(&CollisionLayers::new()));
+        for joint in self.joints.iter(){
+            nodes.push(joint.to_node());
+        }
+        nodes
+    }
+
+    // Also returns the contacts written on the entity
+    fn from_node(entity: &EntityNode) -> Result<(Self, Vec<ContactSnapshot>), SceneErrors>{
+        let mut handle = None;
+        let mut body = BodySnapshot{
+            entity: EntityHandle { index: 0, generation: 0 },
+            position: b2::Vec2 { x: 0.0, y: 0.0 },
+            angle: 0.0,
+            velocity: b2::Vec2 { x: 0.0, y: 0.0 },
+            spin: 0.0,
+            awake: true,
+            enabled: true,
+            gravity_scale: 1.0,
+            body_type: b2::BodyType::Static,
+            layer: 0,
+            allow_sleep: true,
+            fixtures: Vec::<PhysicsFixture>::new(),
+            joints: Vec::<JointSnapshot>::new(),
+        };
+        let mut contacts = Vec::<ContactSnapshot>::new();
+        for setting in entity.components.iter(){
+            match setting.name.as_str(){
+                "handle" => handle = Some(handle_arg(setting, 0)?),
+                "body" => {
+                    body.position = b2::Vec2 { x: number_arg(setting, 0)?, y: number_arg(setting, 1)? };
+                    body.angle = number_arg(setting, 2)?;
+                },
+                "velocity" => {
+                    body.velocity = b2::Vec2 { x: number_arg(setting, 0)?, y: number_arg(setting, 1)? };
+                    body.spin = number_arg(setting, 2)?;
+                },
+                "awake" => body.awake = bool_arg(setting, 0)?,
+                "enabled" => body.enabled = bool_arg(setting, 0)?,
+                "gravity_scale" => body.gravity_scale = number_arg(setting, 0)?,
+                "physics" => {
+                    let physics = PhysicsDescription::from_node(setting, &CollisionLayers::new())?;
+                    body.body_type = physics.body_type;
+                    body.layer = physics.layer;
+                    body.allow_sleep = physics.allow_sleep;
+                    body.fixtures = physics.get_fixtures();
+                },
+                "joint" => body.joints.push(JointSnapshot::from_node(setting).map_err(|e| e.component(&format!("joint.{}", setting.name)))?),
+                "contact" => contacts.push(ContactSnapshot::from_node(setting)?),
+                other => return Err(error_at(setting, format!("unknown snapshot setting '{}'", other))
+                    .hint("known settings are handle, body, velocity, awake, enabled, gravity_scale, physics, joint and contact").into()),
+            }
+        }
+        body.entity = match handle{
+            Some(v) => v,
+            None => return Err(SceneError::new(entity.line, entity.column, "missing handle(index, generation)".to_string())
+                .hint("every body in a snapshot says which entity it belongs to").into()),
+        };
+        for contact in contacts.iter_mut(){
+            contact.entity_a = body.entity;
+        }
+        Ok((body, contacts))
+    }
+}
+
+impl JointSnapshot{
+    // Only settings that differ are set, so bodies on unchanged joints aren't woken
+    fn restore(&self, component: &mut JointComponent, index: usize, physics: &mut Physics) -> Result<(), String>{
+        let live = match component.joints.get(index){
+            Some(link) if link.handle.map(|handle| physics.has_joint(handle)).unwrap_or(false) => link.joint.clone(),
+            _ => return Ok(()),
+        };
+        if self.kind.has_motor() && live.motor != self.motor{
+            component.set_motor(physics, index, self.motor)?;
+        }
+        if self.kind.has_limit() && live.limit != self.limit{
+            component.set_limit(physics, index, self.limit)?;
+        }
+        if let Some(length) = self.length{
+            if live.length != self.length{
+                component.set_length(physics, index, length)?;
+            }
+        }
+        if self.kind.has_spring() && (live.frequency != self.frequency || live.damping != self.damping){
+            component.set_spring(physics, index, self.frequency, self.damping)?;
+        }
+        if let Some(target) = self.target{
+            if live.other_anchor != self.target{
+                component.set_target(physics, index, target)?;
+            }
+        }
+        Ok(())
+    }
+
+    // joint(kind){ link(index, generation) limit(lower, upper) motor(speed, max_force) length(l) spring(hertz, damping) target(x, y) }.
+    // link(world) for joints to the world, no link for joints that aren't connected. Angles are in radians
+    fn to_node(&self) -> ComponentNode{
+        let mut node = ComponentNode::new("joint", vec!(Value::Ident(self.kind.get_name().to_string())));
+        if self.connected{
+            let link = match self.other{
+                Some(other) => handle_values(other),
+                None => vec!(Value::Ident("world".to_string())),
+            };
+            node.block.push(ComponentNode::new("link", link));
+        }
+        if let Some((lower, upper)) = self.limit{
+            node.block.push(ComponentNode::new("limit", vec!(Value::Number(lower), Value::Number(upper))));
+        }
+        if let Some((speed, max_force)) = self.motor{
+            node.block.push(ComponentNode::new("motor", vec!(Value::Number(speed), Value::Number(max_force))));
+        }
+        if let Some(length) = self.length{
+            node.block.push(ComponentNode::new("length", vec!(Value::Number(length))));
+        }
+        if self.kind.has_spring(){
+            node.block.push(ComponentNode::new("spring", vec!(Value::Number(self.frequency), Value::Number(self.damping))));
+        }
+        if let Some(target) = self.target{
+            node.block.push(ComponentNode::new("target", vec!(Value::Number(target.x), Value::Number(target.y))));
+        }
+        node
+    }
+
+    fn from_node(node: &ComponentNode) -> Result<Self, SceneError>{
+        let kind = match node.args.get(0){
+            Some(Value::Ident(name)) => match JointKind::from_name(name){
+                Some(v) => v,
+                None => return Err(error_at(node, format!("unknown joint '{}'", name))
+                    .hint("joints are revolute, prismatic, distance, weld, rope and mouse")),
+            },
+            Some(other) => return Err(error_at(node, format!("argument 1 must be a joint kind, found {}", describe(other)))),
+            None => return Err(missing_arg(node, 0)),
+        };
+        let mut joint = JointSnapshot{
+            kind,
+            connected: false,
+            other: None,
+            limit: None,
+            motor: None,
+            length: None,
+            frequency: 0.0,
+            damping: 0.0,
+            target: None,
+        };
+        for setting in node.block.iter(){
+            match setting.name.as_str(){
+                "link" => {
+                    joint.connected = true;
+                    joint.other = match setting.args.get(0){
+                        Some(Value::Ident(world)) if world == "world" => None,
+                        _ => Some(handle_arg(setting, 0)?),
+                    };
+                },
+                "limit" => joint.limit = Some((number_arg(setting, 0)?, number_arg(setting, 1)?)),
+                "motor" => joint.motor = Some((number_arg(setting, 0)?, number_arg(setting, 1)?)),
+                "length" => joint.length = Some(number_arg(setting, 0)?),
+                "spring" => {
+                    joint.frequency = number_arg(setting, 0)?;
+                    joint.damping = number_arg(setting, 1)?;
+                },
+                "target" => joint.target = Some(b2::Vec2 { x: number_arg(setting, 0)?, y: number_arg(setting, 1)? }),
+                other => return Err(error_at(setting, format!("unknown joint setting '{}'", other))
+                    .hint("known settings are link, limit, motor, length, spring and target")),
+            }
+        }
+        Ok(joint)
+    }
+}
+
+impl ContactSnapshot{
+    // contact(index, generation){ fixtures(a, child a, b, child b) point("id", normal, tangent) ... } on entity a, naming entity b.
+    // Point ids are Box2D's, in hex
+    fn to_node(&self) -> ComponentNode{
+        let mut node = ComponentNode::new("contact", handle_values(self.entity_b));
+        node.block.push(ComponentNode::new("fixtures", vec!(
+            Value::Number(self.fixture_a.0 as f32),
+            Value::Number(self.fixture_a.1 as f32),
+            Value::Number(self.fixture_b.0 as f32),
+            Value::Number(self.fixture_b.1 as f32),
+        )));
+        for (id, normal, tangent) in self.points.iter(){
+            node.block.push(ComponentNode::new("point", vec!(Value::Str(format!("{:08x}", id)), Value::Number(*normal), Value::Number(*tangent))));
+        }
+        node
+    }
+
+    // entity_a is filled in by the entity the contact is written on
+    fn from_node(node: &ComponentNode) -> Result<Self, SceneError>{
+        let mut contact = ContactSnapshot{
+            entity_a: EntityHandle { index: 0, generation: 0 },
+            entity_b: handle_arg(node, 0)?,
+            fixture_a: (0, 0),
+            fixture_b: (0, 0),
+            points: Vec::<(u32, f32, f32)>::new(),
+        };
+        for setting in node.block.iter(){
+            match setting.name.as_str(){
+                "fixtures" => {
+                    contact.fixture_a = (whole_arg(setting, 0)? as usize, whole_arg(setting, 1)? as i32);
+                    contact.fixture_b = (whole_arg(setting, 2)? as usize, whole_arg(setting, 3)? as i32);
+                },
+                "point" => {
+                    let id = match setting.args.get(0){
+                        Some(Value::Str(id)) => u32::from_str_radix(id, 16).map_err(|_| error_at(setting, format!("'{}' is not a contact point id", id))
+                            .hint("ids are 8 hex digits, e.g. \"00010002\""))?,
+                        Some(other) => return Err(error_at(setting, format!("argument 1 must be a contact point id, found {}", describe(other)))),
+                        None => return Err(missing_arg(setting, 0)),
+                    };
+                    contact.points.push((id, number_arg(setting, 1)?, number_arg(setting, 2)?));
+                },
+                other => return Err(error_at(setting, format!("unknown contact setting '{}'", other))
+                    .hint("known settings are fixtures and point")),
+            }
+        }
+        Ok(contact)
+    }
+}
+
+fn handle_values(entity: EntityHandle) -> Vec<Value>{
+    vec!(Value::Number(entity.index as f32), Value::Number(entity.generation as f32))
+}
+
+// An entity handle from two arguments, index and generation
+fn handle_arg(node: &ComponentNode, index: usize) -> Result<EntityHandle, SceneError>{
+    Ok(EntityHandle{
+        index: whole_arg(node, index)?,
+        generation: whole_arg(node, index + 1)?,
+    })
+}
+
+fn whole_arg(node: &ComponentNode, index: usize) -> Result<u32, SceneError>{
+    let value = number_arg(node, index)?;
+    if value < 0.0 || value.fract() != 0.0{
+        return Err(error_at(node, format!("argument {} must be a whole number, found {}", index + 1, value)));
+    }
+    Ok(value as u32)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{ComponentRegistry, SceneLoader};
+    use crate::scene::SceneDescription;
+
+    // A pile resting on the floor inside a sensor, a ball dropped on it and a pendulum. Sleep is off, Box2D's countdown can't be rewound
+    const SCENE: &str = r#"version 1;
+physics[ sleep(false) ];
+entity[ name("Floor") pos(0.0, 0.0, 0.0) physics(static, 0.0, 10.0, 0.5, ground, false) ];
+entity[ name("Bottom") pos(0.0, 1.0, 0.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, false) ];
+entity[ name("Top") pos(0.2, 2.5, 0.0) rot(0.0, 0.0, 20.0) physics(dynamic, 1.0, 0.5, 0.5, enemy, false) ];
+entity[ name("Trigger") pos(0.0, 1.0, 0.0) physics{ body(static) layer(ground) fixture(box(2.0, 0.5)){ sensor(true) } } ];
+entity[ name("Ball") pos(0.0, 7.0, 0.0) physics{ body(dynamic) layer(enemy) fixture(circle(0.25)){ restitution(0.5) } } ];
+entity[ name("Pivot") pos(4.0, 6.0, 0.0) physics(static, 0.0, 0.1, 0.1, ground, false) ];
+entity[ name("Bob") pos(6.0, 6.0, 0.0) physics(dynamic, 1.0, 0.25, 0.25, enemy, false) joints{ distance("Pivot") } ];
+"#;
+
+    fn spawn(source: &str) -> (EntityManager, Physics){
+        let registry = ComponentRegistry::with_engine_components().unwrap();
+        let description = SceneDescription::parse(source, Some(&registry)).unwrap();
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        SceneLoader::spawn(&description, &registry, &mut entity_manager, &mut physics, None, None).unwrap();
+        (entity_manager, physics)
+    }
+
+    fn step(physics: &mut Physics, steps: usize){
+        for _ in 0..steps{
+            physics.step(1.0 / 60.0);
+        }
+    }
+
+    #[test]
+    fn restore_then_step_matches_the_first_run(){
+        // Joints are warm started from where they are, so the scene turns it off for them to rewind exactly
+        let source = SCENE.replace("sleep(false)", "sleep(false) warm_starting(false)");
+        let (entity_manager, mut physics) = spawn(&source);
+        step(&mut physics, 40);
+        let snapshot = PhysicsSnapshot::take(&entity_manager, &physics);
+
+        step(&mut physics, 60);
+        let first = PhysicsSnapshot::take(&entity_manager, &physics);
+        snapshot.restore(&entity_manager, &mut physics).unwrap();
+        assert_eq!(PhysicsSnapshot::take(&entity_manager, &physics).bodies, snapshot.bodies);
+        step(&mut physics, 60);
+        let second = PhysicsSnapshot::take(&entity_manager, &physics);
+
+        assert!(!first.contacts.is_empty());
+        assert_eq!(first.to_source(), second.to_source());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn restore_keeps_contact_impulses_for_warm_starting(){
+        let without_joints: String = SCENE.lines().filter(|line| !line.contains("Pivot") && !line.contains("Bob")).collect::<Vec<&str>>().join("\n");
+        let (entity_manager, mut physics) = spawn(&without_joints);
+        step(&mut physics, 90);
+        let snapshot = PhysicsSnapshot::take(&entity_manager, &physics);
+        assert!(snapshot.contacts.iter().any(|contact| contact.points.iter().any(|point| point.1 != 0.0)));
+
+        step(&mut physics, 30);
+        let first = PhysicsSnapshot::take(&entity_manager, &physics);
+        snapshot.restore(&entity_manager, &mut physics).unwrap();
+        step(&mut physics, 30);
+        assert_eq!(PhysicsSnapshot::take(&entity_manager, &physics), first);
+    }
+
+    #[test]
+    fn source_round_trip(){
+        let (entity_manager, mut physics) = spawn(SCENE);
+        step(&mut physics, 70);
+        let snapshot = PhysicsSnapshot::take(&entity_manager, &physics);
+        // The sensor overlap has no points
+        assert!(snapshot.contacts.iter().any(|contact| contact.points.is_empty()));
+        assert!(snapshot.bodies.iter().any(|body| !body.joints.is_empty()));
+
+        let source = snapshot.to_source();
+        let parsed = PhysicsSnapshot::parse(&source).unwrap();
+        assert_eq!(parsed, snapshot);
+        assert_eq!(parsed.to_source(), source);
+    }
+}
@@ -0,0 +1,69 @@
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+use crate::{Renderer, UniformBuffer, UniformUtils, Camera};
+use crate::renderer::transform_sync::OPENGL_TO_WGPU_MATRIX;
+
+// The GPU copy of the camera. Call sync once per frame after the camera has moved
+pub struct CameraBuffer{
+    buffer: wgpu::Buffer,
+    uniform: CameraUniform,
+}
+
+impl CameraBuffer{
+    // The bind group goes in every entity's uniforms, the layout in the pipeline
+    pub fn new(renderer_reference: &Renderer) -> (Self, wgpu::BindGroup, wgpu::BindGroupLayout){
+        let uniform = CameraUniform::new();
+        let buffer = uniform.create_uniform_buffer(renderer_reference);
+        let layout = UniformUtils::create_bind_group_layout(renderer_reference, 0, wgpu::ShaderStage::VERTEX, Some("Transform"));
+        let bind_group = UniformUtils::create_bind_group(renderer_reference, &buffer, &layout, 0, Some("Transform"));
+        (Self{
+            buffer,
+            uniform,
+        }, bind_group, layout)
+    }
+
+    pub fn sync(&mut self, renderer_reference: &Renderer, camera: &Camera){
+        self.uniform.update_view_proj(camera);
+        renderer_reference.write_buffer(&self.buffer, 0, &[self.uniform]);
+    }
+
+    pub fn get_buffer_reference(&self) -> &wgpu::Buffer{
+        &self.buffer
+    }
+}
+
+// We need this for Rust to store our data correctly for the shaders
+#[repr(C)]
+// This is so we can store this in a buffer
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform{
+    pub proj: [[f32; 4]; 4],
+    pub view: [[f32; 4]; 4],
+}
+
+impl CameraUniform{
+    pub fn new() -> Self {
+        Self {
+            proj: cgmath::Matrix4::identity().into(),
+            view: cgmath::Matrix4::identity().into(),
+        }
+    }
+
+    pub fn update_view_proj(&mut self, camera: &Camera) {
+        let (proj, view) = camera.build_view_projection_matrix();
+        self.proj = (OPENGL_TO_WGPU_MATRIX * proj).into();
+        self.view = view.into();
+    }
+
+    pub fn create_uniform_buffer(&self, renderer_reference:&Renderer) -> wgpu::Buffer{
+        renderer_reference.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[*self]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        )
+    }
+}
+
+impl UniformBuffer for CameraUniform{}
@@ -1,2 +1,3 @@
 pub mod camera;
-pub mod cameracontroller;
\ No newline at end of file
+pub mod cameracontroller;
+pub mod camerabuffer;
\ No newline at end of file
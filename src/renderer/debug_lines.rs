@@ -0,0 +1,49 @@
+use crate::{Camera, DebugLine};
+use crate::renderer::transform_sync::OPENGL_TO_WGPU_MATRIX;
+
+// A point of a debug line, already through the camera so the shader needs no uniforms
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex{
+    // Clip space
+    pub position: [f32; 4],
+    pub color: [f32; 3],
+}
+
+impl DebugVertex{
+    pub fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float3,
+                },
+            ]
+        }
+    }
+
+    // Two vertices per line, for a line list
+    pub fn from_lines(lines: &[DebugLine], camera: &Camera) -> Vec<DebugVertex>{
+        let (proj, view) = camera.build_view_projection_matrix();
+        let view_proj = OPENGL_TO_WGPU_MATRIX * proj * view;
+        let mut vertices = Vec::<DebugVertex>::with_capacity(lines.len() * 2);
+        for line in lines.iter(){
+            for point in [line.from, line.to].iter(){
+                vertices.push(DebugVertex{
+                    position: (view_proj * point.extend(1.0)).into(),
+                    color: line.color,
+                });
+            }
+        }
+        vertices
+    }
+}
@@ -1,9 +1,9 @@
-use crate::{Texture, Renderer, UniformUtils, Rc};
+use crate::{Texture, Renderer, UniformUtils, Arc};
 use wgpu::util::DeviceExt;
 
 #[derive(std::fmt::Debug)]
 pub struct Material{
-    texture: Rc<Texture>,
+    texture: Arc<Texture>,
     color: cgmath::Vector3<f32>,
     shininess: f32,
     metallic: f32,
@@ -13,7 +13,7 @@ pub struct Material{
 }
 
 impl Material{
-    pub fn new(renderer_reference: &Renderer, texture: Rc<Texture>, color: cgmath::Vector3<f32>, shininess: f32, metallic: f32, sort: i32, shader_name: String) -> Self{
+    pub fn new(renderer_reference: &Renderer, texture: Arc<Texture>, color: cgmath::Vector3<f32>, shininess: f32, metallic: f32, sort: i32, shader_name: String) -> Self{
         Self{
             texture,
             color,
@@ -25,7 +25,7 @@ impl Material{
         }
     }
 
-    pub fn borrow_texture(&self) -> &Rc<Texture>{
+    pub fn borrow_texture(&self) -> &Arc<Texture>{
         &self.texture
     }
 
@@ -33,6 +33,14 @@ impl Material{
         &self.shader_name
     }
 
+    pub fn get_color(&self) -> cgmath::Vector3<f32>{
+        self.color
+    }
+
+    pub fn get_shininess(&self) -> f32{
+        self.shininess
+    }
+
     pub fn create_uniform_group(&mut self, renderer_reference: &Renderer) -> (wgpu::BindGroup, wgpu::BindGroupLayout, MaterialUniform){
         let material_uniform = MaterialUniform::new(self.color, self.shininess, self.metallic, self.sort);
         let buffer = material_uniform.create_uniform_buffer(renderer_reference);
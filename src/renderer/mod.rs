@@ -5,4 +5,6 @@ pub mod material;
 pub mod camera;
 pub mod uniforms;
 pub mod postprocessing;
+pub mod transform_sync;
+pub mod debug_lines;
 pub mod ui;
\ No newline at end of file
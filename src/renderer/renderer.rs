@@ -1,4 +1,5 @@
-use crate::{Vertex, RenderMesh, EntityManager, PostProcessing, BloomUniform, Texture, Material, Rc, BaseUniforms, DepthTexture, Camera, Entity, PlayerMovementComponent};
+use crate::{Vertex, RenderMesh, EntityManager, PostProcessing, BloomUniform, Texture, Material, Arc, BaseUniforms, DepthTexture, Camera, Entity, PlayerMovementComponent, DebugLine, DebugVertex};
+use wgpu::util::DeviceExt;
 use std::collections::HashMap;
 use std::any::Any;
 use winit::{
@@ -21,6 +22,8 @@ pub struct Renderer {
     depth_texture: DepthTexture,
     pub sample_count: u32,
     glyph_brush: wgpu_glyph::GlyphBrush<()>,
+    // Drawn over the finished frame with the "debug_lines" pipeline. Replaced every frame by set_debug_lines
+    debug_lines: Vec<DebugLine>,
 }
 
 impl Renderer {
@@ -99,6 +102,7 @@ impl Renderer {
             depth_texture,
             sample_count,
             glyph_brush,
+            debug_lines: Vec::<DebugLine>::new(),
        }
     }
 
@@ -106,7 +110,7 @@ impl Renderer {
     fn generate_pipeline(device: &wgpu::Device, vs_module: wgpu::ShaderModule, fs_module: wgpu::ShaderModule,
         bind_group_layouts: &[&wgpu::BindGroupLayout], color_states: &[wgpu::ColorStateDescriptor],
          vertex_descriptors: &[wgpu::VertexBufferDescriptor], sample_count: u32,
-        use_depth_buffer: bool) -> wgpu::RenderPipeline {
+        use_depth_buffer: bool, primitive_topology: wgpu::PrimitiveTopology) -> wgpu::RenderPipeline {
 
        let render_pipeline_layout =
        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -140,7 +144,7 @@ impl Renderer {
             ),
             color_states: color_states,
 
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList, // 1.
+            primitive_topology, // 1.
 
             depth_stencil_state: if use_depth_buffer {Some(wgpu::DepthStencilStateDescriptor {
                 format: DepthTexture::DEPTH_FORMAT,
@@ -218,7 +222,7 @@ impl Renderer {
    pub fn create_pipeline(&mut self, name: String, bind_group_layouts: &[&wgpu::BindGroupLayout], vertex_shader: wgpu::ShaderModuleSource, fragment_shader: wgpu::ShaderModuleSource, color_states: &[wgpu::ColorStateDescriptor], vertex_descriptors: &[wgpu::VertexBufferDescriptor], sample_count: u32, use_depth_buffer: bool){
     let vs_module = self.device.create_shader_module(vertex_shader);
     let fs_module = self.device.create_shader_module(fragment_shader);
-    let new_pipeline = Renderer::generate_pipeline(&self.device, vs_module, fs_module, bind_group_layouts, color_states, vertex_descriptors, sample_count, use_depth_buffer);
+    let new_pipeline = Renderer::generate_pipeline(&self.device, vs_module, fs_module, bind_group_layouts, color_states, vertex_descriptors, sample_count, use_depth_buffer, wgpu::PrimitiveTopology::TriangleList);
     if !self.render_pipelines.contains_key(&name){
         self.render_pipelines.insert(name, new_pipeline);
     }else{
@@ -226,6 +230,19 @@ impl Renderer {
     }
    }
 
+    // Same as create_pipeline, but every two vertices are drawn as a line. Without a depth buffer
+    pub fn create_line_pipeline(&mut self, name: String, bind_group_layouts: &[&wgpu::BindGroupLayout], vertex_shader: wgpu::ShaderModuleSource, fragment_shader: wgpu::ShaderModuleSource, color_states: &[wgpu::ColorStateDescriptor], vertex_descriptors: &[wgpu::VertexBufferDescriptor], sample_count: u32){
+        let vs_module = self.device.create_shader_module(vertex_shader);
+        let fs_module = self.device.create_shader_module(fragment_shader);
+        let new_pipeline = Renderer::generate_pipeline(&self.device, vs_module, fs_module, bind_group_layouts, color_states, vertex_descriptors, sample_count, false, wgpu::PrimitiveTopology::LineList);
+        self.render_pipelines.insert(name, new_pipeline);
+    }
+
+    // Lines in world space to draw over the next frame, e.g. from PhysicsDebugDraw. Empty draws nothing
+    pub fn set_debug_lines(&mut self, lines: Vec<DebugLine>){
+        self.debug_lines = lines;
+    }
+
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
@@ -243,7 +260,7 @@ impl Renderer {
 
 
     pub fn render(&mut self, camera: &mut Camera, entities: &EntityManager, time: &std::time::SystemTime, framerate: f32) -> Result<(), wgpu::SwapChainError> {       
-        let material = Material::new(&self, Rc::new(Texture::from_empty(&self.device).unwrap()), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, 0, "none".to_string());
+        let material = Material::new(&self, Arc::new(Texture::from_empty(&self.device).unwrap()), cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 }, 1.0, 0.0, 0, "none".to_string());
         let framebuffer = RenderMesh::new(&self, material);
 
         let frame = self
@@ -282,10 +299,11 @@ impl Renderer {
 
         let mut points: Section;
         let mut points_text: String;
-        let entity = entities.get_entities_with_type(PlayerMovementComponent::get_component_id())[0];
-        let mut component = entity.get_component::<PlayerMovementComponent>(PlayerMovementComponent::get_component_id()).unwrap();
-
-        points_text = format!("Points: {:?}", component.points as i32);
+        // The score belongs to whichever entity is tagged as the player. Without one there's nothing to show
+        points_text = match entities.find_by_tag("player").and_then(|x| entities.get_component::<PlayerMovementComponent>(x.handle)){
+            Some(component) => format!("Points: {:?}", component.points as i32),
+            None => String::new(),
+        };
 
         points = Section {
             screen_position: (self.sc_desc.width as f32 / 2.0, self.sc_desc.height as f32),
@@ -293,7 +311,14 @@ impl Renderer {
             layout: Layout::default().h_align(HorizontalAlign::Center).v_align(VerticalAlign::Bottom),
             ..Section::default()
         };
-        
+
+        // The query keeps the meshes borrowed until the render pass that uses their buffers is done
+        let mut mesh_query = entities.query::<&RenderMesh>();
+        let mut entities_to_draw: Vec<(&Entity, &RenderMesh)> = mesh_query.iter().map(|(handle, mesh)| (entities.get_entity(handle).unwrap(), mesh)).collect();
+        // Highest sort first, entities with the same sort are drawn in the order they were created
+        entities_to_draw.sort_by(|a, b| b.1.borrow_material().sort.cmp(&a.1.borrow_material().sort)
+            .then(entities.find_entity(a.0.handle).unwrap().cmp(&entities.find_entity(b.0.handle).unwrap())));
+
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
@@ -339,20 +364,7 @@ impl Renderer {
                 }),
             });
             for pipeline in self.render_pipelines.iter(){
-                let mut entities_to_draw = Vec::<&Entity>::new();
-                for entity in entities.get_entities_with_type(RenderMesh::get_component_id()){
-                    entities_to_draw.push(&entity);
-                }
-
-                entities_to_draw.sort_by(|a, b| b.get_component::<RenderMesh>(RenderMesh::get_component_id()).unwrap().borrow_material().sort.cmp(&a.get_component::<RenderMesh>(RenderMesh::get_component_id()).unwrap().borrow_material().sort));
-
-
-                for entity in entities_to_draw.into_iter(){
-                    let mesh = match entity.get_component::<RenderMesh>(RenderMesh::get_component_id()){
-                        Ok(rm) => { rm }
-                        Err(e) => panic!("{:?}", e)
-                    };
-
+                for (entity, mesh) in entities_to_draw.iter(){
                     if mesh.borrow_material().get_shader_name() == pipeline.0{
                         {
                             render_pass.set_pipeline(pipeline.1); // 2.
@@ -465,6 +477,17 @@ impl Renderer {
                 wgpu::TextureCopyView{ texture: &self.postprocessing.framebuffer_render_texture, mip_level: 0, origin: wgpu::Origin3d::ZERO}, 
                 self.postprocessing.size);            
         }
+        // Made before the pass that draws it, the pass borrows the buffer
+        let debug_vertices = DebugVertex::from_lines(&self.debug_lines, camera);
+        let debug_buffer = if debug_vertices.is_empty() || !self.render_pipelines.contains_key("debug_lines"){
+            None
+        }else{
+            Some(self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Debug Line Buffer"),
+                contents: bytemuck::cast_slice(&debug_vertices),
+                usage: wgpu::BufferUsage::VERTEX,
+            }))
+        };
         {
             let mut render_pass;
             if self.sample_count > 1{
@@ -513,6 +536,13 @@ impl Renderer {
             render_pass.set_bind_group(1, &self.postprocessing.hdr_render_texture_group, &[]);
             render_pass.set_bind_group(2, &bind_group.0, &[]);
             render_pass.draw(0..3, 0..1);
+
+            // Over everything, after post processing so bloom and tone mapping don't change the colors
+            if let Some(buffer) = &debug_buffer{
+                render_pass.set_pipeline(&self.render_pipelines["debug_lines"]);
+                render_pass.set_vertex_buffer(0, buffer.slice(..));
+                render_pass.draw(0..debug_vertices.len() as u32, 0..1);
+            }
         }
         {
             self.glyph_brush.queue(hello_world);
@@ -18,6 +18,8 @@ pub struct Texture {
 
     pub texture_bind_group: wgpu::BindGroup,
     pub texture_bind_group_layout: wgpu::BindGroupLayout,
+    // Set when loaded from disk, so scenes can be saved back out
+    pub path: Option<String>,
 }
 
 impl Texture {
@@ -91,17 +93,15 @@ impl Texture {
         use std::fs::File;
         use std::io::{BufReader, Read};
 
-        let file = File::open(path);
-        let file = match file{
-            Ok(v) => v,
-            Err(e) => panic!("Error opening file: {:?}", e),
-        };
+        let file = File::open(path).with_context(|| format!("Error opening file: {:?}", path))?;
         let mut buf_reader = BufReader::new(file);
         let mut contents = Vec::<u8>::new();
-        buf_reader.read_to_end(&mut contents).unwrap();
+        buf_reader.read_to_end(&mut contents)?;
 
         let img = image::load_from_memory(&contents)?;
-        Self::from_image(renderer_reference, &img, Some(path), tex_mode)
+        let mut texture = Self::from_image(renderer_reference, &img, Some(path), tex_mode)?;
+        texture.path = Some(path.to_string());
+        Ok(texture)
     }
 
     pub fn from_bytes(
@@ -192,7 +192,7 @@ impl Texture {
         );
         log::info!("Texture {:?} loaded", label);
         
-        Ok(Self { texture, view, sampler, texture_bind_group, texture_bind_group_layout })
+        Ok(Self { texture, view, sampler, texture_bind_group, texture_bind_group_layout, path: None })
     }    
 
     pub fn from_empty(
@@ -250,7 +250,7 @@ impl Texture {
             }
         );
         
-        Ok(Self { texture, view, sampler, texture_bind_group, texture_bind_group_layout })
+        Ok(Self { texture, view, sampler, texture_bind_group, texture_bind_group_layout, path: None })
     }    
 }
 
@@ -0,0 +1,133 @@
+use crate::{ComponentBase, UniformBuffer, Renderer, UniformUtils, EntityManager, Transform, TransformHierarchy};
+use crate::scene::ComponentRegistry;
+use wgpu::util::DeviceExt;
+use cgmath::SquareMatrix;
+use std::any::Any;
+
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const ID: u32 = 8;
+
+// The GPU copy of an entity's Transform. Transform itself is plain math, TransformSync keeps this up to date
+pub struct TransformBuffer{
+    buffer: wgpu::Buffer,
+    // What was last written to the buffer
+    uniform: TransformUniform,
+    id: u32,
+}
+
+impl ComponentBase for TransformBuffer{
+    fn get_id(&self) -> u32{
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl TransformBuffer{
+    // Starts out holding the transform as it is now. The bind group goes in the entity's uniforms
+    pub fn new(renderer_reference: &Renderer, transform: &Transform) -> (Self, wgpu::BindGroup){
+        let mut uniform = TransformUniform::new();
+        uniform.update(OPENGL_TO_WGPU_MATRIX * transform.get_matrix());
+        let buffer = uniform.create_uniform_buffer(renderer_reference);
+        let layout = UniformUtils::create_bind_group_layout(renderer_reference, 0, wgpu::ShaderStage::VERTEX, Some("Transform"));
+        let bind_group = UniformUtils::create_bind_group(renderer_reference, &buffer, &layout, 0, Some("Transform"));
+        (Self{
+            buffer,
+            uniform,
+            id: ID,
+        }, bind_group)
+    }
+
+    pub fn get_component_id() -> u32{
+        ID
+    }
+
+    // Made by the loader for every entity with a renderer, so it has no keyword of its own
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.reserve("TransformBuffer", ID)
+    }
+
+    pub fn get_buffer_reference(&self) -> &wgpu::Buffer{
+        &self.buffer
+    }
+}
+
+// Render-side step that copies transforms to the GPU once per frame, after the systems have run.
+// Only transforms whose matrix changed since the last upload are written
+pub struct TransformSync{
+
+}
+
+impl TransformSync{
+    // Transforms are drawn `alpha` of the way from their previous fixed step to the current one (See Time::alpha).
+    // Returns how many buffers were written
+    pub fn sync(renderer_reference: &Renderer, entity_manager: &EntityManager, alpha: f32) -> usize{
+        let mut query = entity_manager.query::<(&Transform, &mut TransformBuffer)>();
+
+        // Gather the dirty ones first, then write them all in one go. The writes are staged by the queue and
+        // reach the GPU together with the frame's draw calls
+        let mut dirty = Vec::<&TransformBuffer>::new();
+        for (handle, (transform, transform_buffer)) in query.iter(){
+            // Children are drawn relative to where their parents are drawn
+            let world = match TransformHierarchy::get_parent(entity_manager, handle){
+                Some(_) => TransformHierarchy::interpolated_world_matrix(entity_manager, handle, alpha).unwrap(),
+                None => transform.interpolated_matrix(alpha),
+            };
+            let matrix: [[f32; 4]; 4] = (OPENGL_TO_WGPU_MATRIX * world).into();
+            if matrix != transform_buffer.uniform.transform{
+                transform_buffer.uniform.transform = matrix;
+                dirty.push(transform_buffer);
+            }
+        }
+
+        for transform_buffer in dirty.iter(){
+            renderer_reference.write_buffer(&transform_buffer.buffer, 0, &[transform_buffer.uniform]);
+        }
+        dirty.len()
+    }
+}
+
+// We need this for Rust to store our data correctly for the shaders
+#[repr(C)]
+// This is so we can store this in a buffer
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TransformUniform{
+    transform: [[f32; 4]; 4] // Store our rotation as a 4x4 matrix
+}
+impl TransformUniform{
+    pub fn new() -> Self{
+        Self{
+            transform: cgmath::Matrix4::identity().into()
+        }
+    }
+
+    pub fn update(&mut self, value: cgmath::Matrix4::<f32>){
+        self.transform = value.into();
+    }
+
+    pub fn create_uniform_buffer(&self, renderer_reference: &Renderer) -> wgpu::Buffer{
+        renderer_reference.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Rotation Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[*self]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        )
+    }
+}
+
+impl UniformBuffer for TransformUniform{}
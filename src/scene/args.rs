@@ -0,0 +1,106 @@
+use wrapped2d::b2;
+use crate::{LayerType, CollisionLayers};
+use crate::physics::layers::MAX_LAYERS;
+use super::parser::{ComponentNode, Value};
+use super::error::SceneError;
+
+// Helpers for reading component arguments, shared by the loader and every registered component factory.
+// Each one reports errors against the component it was reading, with a hint where one helps
+
+pub fn error_at(node: &ComponentNode, message: String) -> SceneError{
+    SceneError::new(node.line, node.column, message).component(&node.name)
+}
+
+pub fn describe(value: &Value) -> String{
+    match value{
+        Value::Number(v) => format!("the number {}", v),
+        Value::Bool(v) => format!("{}", v),
+        Value::Ident(v) => format!("'{}'", v),
+        Value::Str(v) => format!("the string \"{}\"", v),
+        Value::Call(name, _) => format!("{}(...)", name),
+    }
+}
+
+pub fn missing_arg(node: &ComponentNode, index: usize) -> SceneError{
+    error_at(node, format!("missing argument {}", index + 1))
+        .hint(&format!("'{}' needs at least {} argument(s)", node.name, index + 1))
+}
+
+pub fn number_arg(node: &ComponentNode, index: usize) -> Result<f32, SceneError>{
+    match node.args.get(index){
+        Some(Value::Number(v)) => Ok(*v),
+        Some(other) => Err(error_at(node, format!("argument {} must be a number, found {}", index + 1, describe(other)))
+            .hint("numbers look like 1, -2.5 or .75")),
+        None => Err(missing_arg(node, index)),
+    }
+}
+
+// A layer name from `layers`, or a layer number
+pub fn layer_arg(node: &ComponentNode, index: usize, layers: &CollisionLayers) -> Result<LayerType, SceneError>{
+    let name = match node.args.get(index){
+        Some(Value::Ident(v)) | Some(Value::Str(v)) => v,
+        _ => {
+            let value = number_arg(node, index)?;
+            if value < 0.0 || value.fract() != 0.0 || value >= MAX_LAYERS as f32{
+                return Err(error_at(node, format!("argument {} must be a layer number from 0 to {}, found {}", index + 1, MAX_LAYERS - 1, value)));
+            }
+            return Ok(value as LayerType);
+        },
+    };
+    match layers.find(name){
+        Some(layer) => Ok(layer),
+        None => Err(error_at(node, format!("unknown collision layer '{}'", name))
+            .hint(&format!("known layers are {}. Name more with `collision[ layer({}) ]`", layers.get_names().join(", "), name))),
+    }
+}
+
+pub fn bool_arg(node: &ComponentNode, index: usize) -> Result<bool, SceneError>{
+    match node.args.get(index){
+        Some(Value::Bool(v)) => Ok(*v),
+        Some(other) => Err(error_at(node, format!("argument {} must be true or false, found {}", index + 1, describe(other)))),
+        None => Err(missing_arg(node, index)),
+    }
+}
+
+// Strings must be quoted from version 1 onwards. Legacy files use bare words for names and paths
+pub fn text_arg(node: &ComponentNode, index: usize, version: u32) -> Result<String, SceneError>{
+    match node.args.get(index){
+        Some(Value::Str(v)) => Ok(v.clone()),
+        Some(Value::Ident(v)) if version == 0 => Ok(v.clone()),
+        Some(Value::Ident(v)) => Err(error_at(node, format!("argument {} must be a quoted string", index + 1))
+            .hint(&format!("version {} files quote text, e.g. \"{}\"", version, v))),
+        Some(other) => Err(error_at(node, format!("argument {} must be a string, found {}", index + 1, describe(other)))),
+        None => Err(missing_arg(node, index)),
+    }
+}
+
+pub fn body_type_arg(node: &ComponentNode, index: usize) -> Result<b2::BodyType, SceneError>{
+    match node.args.get(index){
+        Some(Value::Ident(v)) => match v.as_str(){
+            "static" => Ok(b2::BodyType::Static),
+            "dynamic" => Ok(b2::BodyType::Dynamic),
+            "kinematic" => Ok(b2::BodyType::Kinematic),
+            other => Err(error_at(node, format!("unknown body type '{}'", other))
+                .hint("expected static, dynamic or kinematic")),
+        },
+        Some(other) => Err(error_at(node, format!("argument {} must be a body type, found {}", index + 1, describe(other)))
+            .hint("expected static, dynamic or kinematic")),
+        None => Err(missing_arg(node, index)),
+    }
+}
+
+// tag("enemy", "wall") - at least one
+pub fn tag_args(node: &ComponentNode, version: u32) -> Result<Vec<String>, SceneError>{
+    if node.args.is_empty(){
+        return Err(missing_arg(node, 0));
+    }
+    (0..node.args.len()).map(|i| text_arg(node, i, version)).collect()
+}
+
+pub fn vector_args(node: &ComponentNode) -> Result<cgmath::Vector3::<f32>, SceneError>{
+    if node.args.len() != 3{
+        return Err(error_at(node, format!("takes 3 numbers, found {}", node.args.len()))
+            .hint(&format!("write it as {}(x,y,z)", node.name)));
+    }
+    Ok(cgmath::Vector3::<f32> { x: number_arg(node, 0)?, y: number_arg(node, 1)?, z: number_arg(node, 2)? })
+}
@@ -0,0 +1,56 @@
+use crate::{LayerType, CollisionLayers};
+use crate::physics::layers::MAX_LAYERS;
+use super::parser::{ComponentNode, Value};
+use super::error::SceneError;
+use super::args::*;
+
+// One setting of a collision[...] block:
+// layer(name) names the next free layer, ignore(a, b) stops two layers colliding and collide(a, b) lets them again.
+// ignore(enemy, enemy) keeps enemies from colliding with each other
+pub fn collision_setting(layers: &mut CollisionLayers, node: &ComponentNode) -> Result<(), SceneError>{
+    match node.name.as_str(){
+        "layer" => {
+            let name = match node.args.get(0){
+                Some(Value::Ident(v)) | Some(Value::Str(v)) if node.args.len() == 1 => v,
+                Some(other) if node.args.len() == 1 => return Err(error_at(node, format!("argument 1 must be a layer name, found {}", describe(other)))),
+                None => return Err(missing_arg(node, 0)),
+                Some(_) => return Err(error_at(node, format!("takes 1 argument, found {}", node.args.len()))
+                    .hint("name one layer per setting, e.g. layer(bullet) layer(pickup)")),
+            };
+            layers.add(name).map(|_| ()).map_err(|e| error_at(node, e))
+        },
+        "ignore" | "collide" => {
+            if node.args.len() != 2{
+                return Err(error_at(node, format!("takes 2 layers, found {}", node.args.len()))
+                    .hint(&format!("e.g. {}(enemy, player)", node.name)));
+            }
+            let a = layer_arg(node, 0, layers)?;
+            let b = layer_arg(node, 1, layers)?;
+            layers.set_collides(a, b, node.name == "collide");
+            Ok(())
+        },
+        other => Err(error_at(node, format!("unknown collision setting '{}'", other))
+            .hint("known settings are layer, ignore and collide")),
+    }
+}
+
+// The collision[...] settings that turn the engine's layers into `layers`. Empty if they're the same
+pub fn collision_nodes(layers: &CollisionLayers) -> Vec<ComponentNode>{
+    let defaults = CollisionLayers::new();
+    let mut nodes = Vec::<ComponentNode>::new();
+    for name in layers.get_names().iter().skip(defaults.get_names().len()){
+        nodes.push(ComponentNode::new("layer", vec!(Value::Ident(name.clone()))));
+    }
+    let layer_value = |layer: LayerType| match layers.get_name(layer){
+        Some(name) => Value::Ident(name.to_string()),
+        None => Value::Number(layer as f32),
+    };
+    for a in 0..MAX_LAYERS as LayerType{
+        for b in a..MAX_LAYERS as LayerType{
+            if !layers.collides(a, b){
+                nodes.push(ComponentNode::new("ignore", vec!(layer_value(a), layer_value(b))));
+            }
+        }
+    }
+    nodes
+}
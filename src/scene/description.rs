@@ -0,0 +1,639 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use wrapped2d::b2;
+use crate::{LayerType, PhysicsFixture, ShapeKind, CollisionLayers, PhysicsConfig};
+use super::parser::{Parser, SceneNode, EntityNode, PrefabNode, SettingsNode, IncludeNode, ComponentNode, Value, same_components};
+use super::error::{SceneError, SceneErrors};
+use super::registry::ComponentRegistry;
+use super::args::*;
+use super::fixture::{fixture_setting, fixture_node};
+use super::collision_layers::collision_setting;
+use super::physics_config::physics_setting;
+
+// The version written by this engine. Files without a header are treated as version 0 (Legacy)
+pub const SCENE_FORMAT_VERSION: u32 = 1;
+
+// Typed, renderer-free description of a scene. SceneLoader turns this into real entities
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneDescription{
+    pub version: u32,
+    // Every file pulled in through `include`, directly or not
+    pub includes: Vec<String>,
+    // Prefab instances are already expanded
+    pub entities: Vec<EntityDescription>,
+    // From the collision[...] blocks of the scene and its includes, applied on top of the engine's layers
+    pub collision_layers: CollisionLayers,
+    // From the physics[...] blocks, applied on top of the engine's defaults
+    pub physics_config: PhysicsConfig,
+}
+
+// Prefabs visible to a scene: the ones it defines and the ones from every file it includes.
+// Settings blocks are gathered the same way, so shared layers and physics settings can live in an included config file
+pub struct PrefabLibrary{
+    prefabs: HashMap<String, PrefabNode>,
+    pub collision_layers: CollisionLayers,
+    pub physics_config: PhysicsConfig,
+    // Canonical paths, so a file included twice is only read once
+    loaded: Vec<PathBuf>,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityDescription{
+    pub name: Option<String>,
+    pub position: cgmath::Vector3::<f32>,
+    // Euler angles in degrees
+    pub rotation: cgmath::Vector3::<f32>,
+    pub scale: cgmath::Vector3::<f32>,
+    // From tag("a", "b", ...). Kept in the order written, without duplicates
+    pub tags: Vec<String>,
+    // Everything else is built by the ComponentRegistry, keyed by the component name
+    pub components: Vec<ComponentNode>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialDescription{
+    pub texture: String,
+    pub color: cgmath::Vector3::<f32>,
+    pub shininess: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicsDescription{
+    pub body_type: b2::BodyType,
+    // Density of the box from width and height, and of every fixture that doesn't set its own
+    pub mass: f32,
+    pub width: f32,
+    pub height: f32,
+    pub layer: LayerType,
+    pub allow_sleep: bool,
+    // From fixture(...) settings. When there are none the body is a single width x height box
+    pub fixtures: Vec<PhysicsFixture>,
+}
+
+impl SceneDescription{
+    // Includes are resolved relative to the working directory. With a registry, component names are checked
+    // against it too, so unknown components are reported along with every other error in the file
+    pub fn parse(source: &str, registry: Option<&ComponentRegistry>) -> Result<Self, SceneErrors>{
+        SceneDescription::parse_in(source, Path::new("."), &mut Vec::<PathBuf>::new(), registry)
+    }
+
+    pub fn from_file(path: &str, registry: Option<&ComponentRegistry>) -> Result<Self, SceneErrors>{
+        let source = match std::fs::read_to_string(path){
+            Ok(v) => v,
+            Err(e) => return Err(SceneErrors::new(vec!(SceneError::new(0, 0, format!("could not read file: {}", e)).path(path)))),
+        };
+        let directory = Path::new(path).parent().unwrap_or(Path::new("."));
+        // The scene itself is on the include stack, so a prefab file including the scene is caught as a cycle
+        let mut stack = Path::new(path).canonicalize().into_iter().collect::<Vec<PathBuf>>();
+        SceneDescription::parse_in(&source, directory, &mut stack, registry).map_err(|e| {
+            SceneErrors::new(e.errors.into_iter().map(|error| error.path(path)).collect())
+        })
+    }
+
+    fn parse_in(source: &str, directory: &Path, stack: &mut Vec<PathBuf>, registry: Option<&ComponentRegistry>) -> Result<Self, SceneErrors>{
+        let (node, mut errors) = Parser::parse(source);
+        let mut library = PrefabLibrary::new();
+        for include in node.includes.iter(){
+            library.include(include, directory, stack, &mut errors);
+        }
+        library.add(&node.prefabs, &mut errors);
+        library.add_settings(&node.settings, &mut errors);
+
+        // Still validate the entities that did parse, so every problem in the file is reported at once
+        let description = SceneDescription::from_node(node, &library, registry, &mut errors);
+        // Errors in the scene itself first (They have no path yet), then each included file
+        errors.sort_by_key(|e| (e.path.clone(), e.line, e.column));
+        if errors.is_empty(){
+            Ok(description)
+        }else{
+            Err(SceneErrors::new(errors))
+        }
+    }
+
+    pub fn from_node(node: SceneNode, library: &PrefabLibrary, registry: Option<&ComponentRegistry>, errors: &mut Vec<SceneError>) -> Self{
+        let version = node.version.unwrap_or(0);
+        if version > SCENE_FORMAT_VERSION{
+            errors.push(SceneError::new(1, 1, format!("scene format version {} is newer than the supported version {}", version, SCENE_FORMAT_VERSION))
+                .hint("update the engine, or re-save the scene with this version"));
+        }
+        let mut entities = Vec::<EntityDescription>::new();
+        for entity in node.entities.iter(){
+            match library.expand(entity){
+                Ok(components) => entities.push(EntityDescription::from_components(&components, version, registry, errors)),
+                Err(e) => errors.push(e),
+            }
+        }
+        Self{
+            version,
+            includes: library.files.clone(),
+            entities,
+            collision_layers: library.collision_layers.clone(),
+            physics_config: library.physics_config.clone(),
+        }
+    }
+}
+
+impl PrefabLibrary{
+    pub fn new() -> Self{
+        Self{
+            prefabs: HashMap::<String, PrefabNode>::new(),
+            collision_layers: CollisionLayers::new(),
+            physics_config: PhysicsConfig::new(),
+            loaded: Vec::<PathBuf>::new(),
+            files: Vec::<String>::new(),
+        }
+    }
+
+    // Only the prefabs of an included file are imported, its entities are ignored
+    pub fn include(&mut self, include: &IncludeNode, directory: &Path, stack: &mut Vec<PathBuf>, errors: &mut Vec<SceneError>){
+        let path = directory.join(&include.path);
+        let display = path.to_string_lossy().to_string();
+        let include_error = |message: String| SceneError::new(include.line, include.column, message).component("include");
+
+        let canonical = match path.canonicalize(){
+            Ok(v) => v,
+            Err(e) => {
+                errors.push(include_error(format!("could not read included file {:?}: {}", display, e))
+                    .hint("include paths are relative to the file that includes them"));
+                return;
+            }
+        };
+        if stack.contains(&canonical){
+            errors.push(include_error(format!("including {:?} leads back to a file that is already being loaded", display))
+                .hint("remove the include that leads back to this file"));
+            return;
+        }
+        if self.loaded.contains(&canonical){
+            return;
+        }
+        let source = match std::fs::read_to_string(&path){
+            Ok(v) => v,
+            Err(e) => {
+                errors.push(include_error(format!("could not read included file {:?}: {}", display, e)));
+                return;
+            }
+        };
+
+        let (node, mut file_errors) = Parser::parse(&source);
+        stack.push(canonical.clone());
+        let nested_directory = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        for nested in node.includes.iter(){
+            self.include(nested, &nested_directory, stack, &mut file_errors);
+        }
+        stack.pop();
+        self.add(&node.prefabs, &mut file_errors);
+        self.add_settings(&node.settings, &mut file_errors);
+        if !node.entities.is_empty(){
+            log::warn!("{}: entities in included files are ignored, only prefabs are imported", display);
+        }
+
+        errors.extend(file_errors.into_iter().map(|e| e.path(&display)));
+        self.loaded.push(canonical);
+        self.files.push(display);
+    }
+
+    pub fn add(&mut self, prefabs: &[PrefabNode], errors: &mut Vec<SceneError>){
+        for prefab in prefabs.iter(){
+            if self.prefabs.contains_key(&prefab.name){
+                errors.push(SceneError::new(prefab.line, prefab.column, format!("prefab '{}' is already defined", prefab.name))
+                    .hint("prefab names must be unique across a scene and everything it includes"));
+                continue;
+            }
+            self.prefabs.insert(prefab.name.clone(), prefab.clone());
+        }
+    }
+
+    // Applied in the order they're read: includes first, then the scene itself
+    pub fn add_settings(&mut self, settings: &[SettingsNode], errors: &mut Vec<SceneError>){
+        for block in settings.iter(){
+            if block.name == "collision"{
+                for setting in block.components.iter(){
+                    if let Err(e) = collision_setting(&mut self.collision_layers, setting){
+                        errors.push(e.component(&format!("collision.{}", setting.name)));
+                    }
+                }
+            }else if block.name == "physics"{
+                for setting in block.components.iter(){
+                    if let Err(e) = physics_setting(&mut self.physics_config, setting){
+                        errors.push(e.component(&format!("physics.{}", setting.name)));
+                    }
+                }
+                // Checked here rather than when the scene is spawned, so the error points at the block
+                if let Err(e) = self.physics_config.validate(){
+                    errors.push(SceneError::new(block.line, block.column, e).component("physics"));
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PrefabNode>{
+        self.prefabs.get(name)
+    }
+
+    // The components of an entity with its prefab applied underneath
+    pub fn expand(&self, entity: &EntityNode) -> Result<Vec<ComponentNode>, SceneError>{
+        let name = match &entity.prefab{
+            Some(v) => v,
+            None => return Ok(entity.components.clone()),
+        };
+        match self.prefabs.get(name){
+            Some(prefab) => Ok(apply_overrides(&prefab.components, &entity.components)),
+            None => {
+                let mut known: Vec<&String> = self.prefabs.keys().collect();
+                known.sort();
+                let hint = if known.is_empty(){
+                    "define it with `prefab Name[ ... ];`, or include the file that does".to_string()
+                }else{
+                    format!("known prefabs are {}", known.iter().map(|k| k.as_str()).collect::<Vec<&str>>().join(", "))
+                };
+                Err(SceneError::new(entity.line, entity.column, format!("unknown prefab '{}'", name)).hint(&hint))
+            }
+        }
+    }
+}
+
+// A component in the overrides replaces the arguments it gives by position and keeps the rest from the prefab,
+// so `physics(static)` only changes the body type. Block settings are merged the same way, by name.
+// Components the prefab doesn't have are added
+pub fn apply_overrides(base: &[ComponentNode], overrides: &[ComponentNode]) -> Vec<ComponentNode>{
+    let mut components = base.to_vec();
+    for component in overrides.iter(){
+        match components.iter_mut().find(|c| c.name == component.name){
+            Some(existing) => {
+                for (i, arg) in component.args.iter().enumerate(){
+                    if i < existing.args.len(){
+                        existing.args[i] = arg.clone();
+                    }else{
+                        existing.args.push(arg.clone());
+                    }
+                }
+                existing.block = apply_overrides(&existing.block, &component.block);
+                // Report problems at the instance, which is what the user just edited
+                existing.line = component.line;
+                existing.column = component.column;
+            },
+            None => components.push(component.clone()),
+        }
+    }
+    components
+}
+
+impl EntityDescription{
+    pub fn new() -> Self{
+        Self{
+            name: None,
+            position: cgmath::Vector3::<f32> { x: 0.0, y: 0.0, z: 0.0 },
+            rotation: cgmath::Vector3::<f32> { x: 0.0, y: 0.0, z: 0.0 },
+            scale: cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 },
+            tags: Vec::<String>::new(),
+            components: Vec::<ComponentNode>::new(),
+        }
+    }
+
+    fn from_components(components: &[ComponentNode], version: u32, registry: Option<&ComponentRegistry>, errors: &mut Vec<SceneError>) -> Self{
+        let mut entity = EntityDescription::new();
+
+        for component in components.iter(){
+            let result = match component.name.as_str(){
+                // Base Components
+                "name" => text_arg(component, 0, version).map(|v| entity.name = Some(v)),
+                "pos" => vector_args(component).map(|v| entity.position = v),
+                "rot" => vector_args(component).map(|v| entity.rotation = v),
+                "scale" => vector_args(component).map(|v| entity.scale = v),
+                "tag" => tag_args(component, version).map(|tags| {
+                    for tag in tags{
+                        if !entity.tags.contains(&tag){
+                            entity.tags.push(tag);
+                        }
+                    }
+                }),
+                // Built by the ComponentRegistry when the entity is spawned
+                _ => match registry{
+                    Some(registry) if !registry.contains_keyword(&component.name) => Err(registry.unknown_component(component)),
+                    _ => {
+                        entity.components.push(component.clone());
+                        Ok(())
+                    },
+                },
+            };
+            if let Err(e) = result{
+                errors.push(e);
+            }
+        }
+
+        entity
+    }
+
+    // Find a component by its scene keyword
+    pub fn component(&self, name: &str) -> Option<&ComponentNode>{
+        self.components.iter().find(|c| c.name == name)
+    }
+
+    // Equal apart from where the components are in the file
+    pub fn same_as(&self, other: &EntityDescription) -> bool{
+        self.name == other.name && self.position == other.position && self.rotation == other.rotation
+            && self.scale == other.scale && self.tags == other.tags && self.same_components_as(other)
+    }
+
+    // Only the transform, name or tags may differ
+    pub fn same_components_as(&self, other: &EntityDescription) -> bool{
+        same_components(&self.components, &other.components)
+    }
+}
+
+impl MaterialDescription{
+    // material(path, color(r,g,b), shininess) - shininess is optional
+    pub fn from_node(node: &ComponentNode, version: u32) -> Result<Self, SceneError>{
+        let texture = text_arg(node, 0, version)?;
+        let color = match node.args.get(1){
+            Some(Value::Call(name, args)) if name == "color" => {
+                let mut rgb = [0.0; 3];
+                for i in 0..3{
+                    rgb[i] = match args.get(i){
+                        Some(Value::Number(v)) if args.len() == 3 => *v,
+                        _ => return Err(error_at(node, "color(...) takes exactly 3 numbers".to_string())
+                            .hint("write the color as color(r,g,b), e.g. color(1.0,1.0,1.0)")),
+                    };
+                }
+                cgmath::Vector3::<f32> { x: rgb[0], y: rgb[1], z: rgb[2] }
+            },
+            None => cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 },
+            Some(other) => return Err(error_at(node, format!("argument 2 must be color(r,g,b), found {}", describe(other)))
+                .hint("write the color as color(r,g,b), e.g. color(1.0,1.0,1.0)")),
+        };
+        let shininess = if node.args.len() > 2 { number_arg(node, 2)? } else { 1.0 };
+        if node.args.len() > 3{
+            return Err(error_at(node, format!("takes at most 3 arguments, found {}", node.args.len()))
+                .hint("material(texture, color(r,g,b), shininess)"));
+        }
+
+        Ok(Self{
+            texture,
+            color,
+            shininess,
+        })
+    }
+
+    pub fn to_node(&self) -> ComponentNode{
+        ComponentNode::new("material", vec!(
+            Value::Str(self.texture.clone()),
+            Value::Call("color".to_string(), vec!(Value::Number(self.color.x), Value::Number(self.color.y), Value::Number(self.color.z))),
+            Value::Number(self.shininess),
+        ))
+    }
+}
+
+impl PhysicsDescription{
+    pub fn new() -> Self{
+        Self{
+            body_type: b2::BodyType::Static,
+            mass: 0.0,
+            width: 1.0,
+            height: 1.0,
+            layer: 0,
+            allow_sleep: true,
+            fixtures: Vec::<PhysicsFixture>::new(),
+        }
+    }
+
+    // Either positional: physics(type, mass, width, height, layer, allow_sleep)
+    // or a block: physics{ body(dynamic) mass(5.0) size(2.0,1.0) layer(enemy) sleep(false) }
+    // The block can give the body any number of shapes instead of the box:
+    // physics{ body(dynamic) fixture(box(1.0,0.5)){ offset(0.0,1.0) angle(45) density(2.0) friction(0.5) restitution(0.2) sensor(false) group(-1) } fixture(circle(0.5)) }
+    // Shapes are box(width,height), circle(radius), polygon(x,y, x,y, ...), edge(x,y, x,y), chain(x,y, ...) and loop(x,y, ...).
+    // Prefab overrides merge settings by name, so an override's fixture changes the prefab's first fixture.
+    // Layers are a number or a name from `layers`. A bad setting is reported and the rest are still read
+    pub fn from_node(node: &ComponentNode, layers: &CollisionLayers) -> Result<Self, SceneErrors>{
+        let mut physics = PhysicsDescription::new();
+        let mut errors = Vec::<SceneError>::new();
+
+        if node.args.len() > 6{
+            return Err(error_at(node, format!("takes at most 6 arguments, found {}", node.args.len()))
+                .hint("physics(type, mass, width, height, layer, allow_sleep)").into());
+        }
+        let mut record = |result: Result<(), SceneError>| if let Err(e) = result { errors.push(e); };
+        if !node.args.is_empty(){
+            record(body_type_arg(node, 0).map(|v| physics.body_type = v));
+            if node.args.len() > 1 { record(number_arg(node, 1).map(|v| physics.mass = v)); }
+            if node.args.len() > 2 { record(number_arg(node, 2).map(|v| physics.width = v)); }
+            if node.args.len() > 3 { record(number_arg(node, 3).map(|v| physics.height = v)); }
+            if node.args.len() > 4 { record(layer_arg(node, 4, layers).map(|v| physics.layer = v)); }
+            if node.args.len() > 5 { record(bool_arg(node, 5).map(|v| physics.allow_sleep = v)); }
+        }
+
+        // Fixtures without a density get the body's mass, which may come after them
+        let mut fixtures = Vec::<(PhysicsFixture, bool)>::new();
+        for setting in node.block.iter(){
+            // Report errors against the physics block, with the setting as context
+            let result = match setting.name.as_str(){
+                "body" => body_type_arg(setting, 0).map(|v| physics.body_type = v),
+                "mass" => number_arg(setting, 0).map(|v| physics.mass = v),
+                "size" => number_arg(setting, 0).and_then(|w| number_arg(setting, 1).map(|h| {
+                    physics.width = w;
+                    physics.height = h;
+                })),
+                "layer" => layer_arg(setting, 0, layers).map(|v| physics.layer = v),
+                "sleep" => bool_arg(setting, 0).map(|v| physics.allow_sleep = v),
+                "fixture" => match fixture_setting(setting){
+                    Ok(v) => {
+                        fixtures.push(v);
+                        Ok(())
+                    },
+                    // Already reported against the fixture's own settings
+                    Err(e) => {
+                        errors.extend(e.errors);
+                        continue;
+                    },
+                },
+                other => Err(error_at(setting, format!("unknown physics setting '{}'", other))
+                    .hint("known settings are body, mass, size, layer, sleep and fixture")),
+            };
+            if let Err(e) = result{
+                errors.push(e.component(&format!("physics.{}", setting.name)));
+            }
+        }
+        if !errors.is_empty(){
+            return Err(SceneErrors::new(errors));
+        }
+        for (fixture, has_density) in fixtures.into_iter(){
+            physics.fixtures.push(if has_density { fixture } else { fixture.with_density(physics.mass) });
+        }
+
+        Ok(physics)
+    }
+
+    // The description of a live body. Plain single boxes are kept in the short positional form
+    pub fn from_fixtures(body_type: b2::BodyType, layer: LayerType, allow_sleep: bool, fixtures: &[PhysicsFixture]) -> Self{
+        let mut physics = PhysicsDescription::new();
+        physics.body_type = body_type;
+        physics.layer = layer;
+        physics.allow_sleep = allow_sleep;
+        match fixtures{
+            [fixture] if PhysicsDescription::is_plain_box(fixture) => {
+                if let ShapeKind::Box(width, height) = fixture.shape{
+                    physics.width = width;
+                    physics.height = height;
+                }
+                physics.mass = fixture.density;
+            },
+            _ => physics.fixtures = fixtures.to_vec(),
+        }
+        physics
+    }
+
+    fn is_plain_box(fixture: &PhysicsFixture) -> bool{
+        match fixture.shape{
+            ShapeKind::Box(width, height) => *fixture == PhysicsFixture::new_box(width, height).with_density(fixture.density),
+            _ => false,
+        }
+    }
+
+    // What the body is built from
+    pub fn get_fixtures(&self) -> Vec<PhysicsFixture>{
+        if self.fixtures.is_empty(){
+            vec!(PhysicsFixture::new_box(self.width, self.height).with_density(self.mass))
+        }else{
+            self.fixtures.clone()
+        }
+    }
+
+    // The layer is written by its name in `layers`, or as a number if it has none
+    pub fn to_node(&self, layers: &CollisionLayers) -> ComponentNode{
+        let layer = match layers.get_name(self.layer){
+            Some(name) => Value::Ident(name.to_string()),
+            None => Value::Number(self.layer as f32),
+        };
+        let body_type = match self.body_type{
+            b2::BodyType::Static => "static",
+            b2::BodyType::Kinematic => "kinematic",
+            b2::BodyType::Dynamic => "dynamic",
+        };
+        if self.fixtures.is_empty(){
+            return ComponentNode::new("physics", vec!(
+                Value::Ident(body_type.to_string()),
+                Value::Number(self.mass),
+                Value::Number(self.width),
+                Value::Number(self.height),
+                layer,
+                Value::Bool(self.allow_sleep),
+            ));
+        }
+
+        let mut node = ComponentNode::new("physics", Vec::<Value>::new());
+        node.block.push(ComponentNode::new("body", vec!(Value::Ident(body_type.to_string()))));
+        node.block.push(ComponentNode::new("layer", vec!(layer)));
+        node.block.push(ComponentNode::new("sleep", vec!(Value::Bool(self.allow_sleep))));
+        for fixture in self.fixtures.iter(){
+            node.block.push(fixture_node(fixture));
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn locations(errors: &SceneErrors) -> Vec<(usize, usize)>{
+        errors.errors.iter().map(|e| (e.line, e.column)).collect()
+    }
+
+    #[test]
+    fn unknown_components_are_reported_with_syntax_errors(){
+        let registry = ComponentRegistry::with_engine_components().unwrap();
+        let source = "entity[ pos(1, 2, 3) wobble(1) ]\nentity[ pos(1, 2 ]";
+        let errors = SceneDescription::parse(source, Some(&registry)).unwrap_err();
+        assert_eq!(locations(&errors), vec!((1, 22), (2, 18)));
+        assert_eq!(errors.errors[0].message, "unknown component 'wobble'");
+        // Without a registry the names aren't checked
+        assert!(SceneDescription::parse("entity[ wobble(1) ]", None).is_ok());
+    }
+
+    #[test]
+    fn physics_block_reports_every_bad_setting(){
+        let (node, errors) = Parser::parse("entity[ physics{ body(sideways) mass(heavy)\n fixture(box(1.0)){ friction(x) } size(1.0, 2.0) } ]");
+        assert!(errors.is_empty());
+        let physics = &node.entities[0].components[0];
+        let errors = PhysicsDescription::from_node(physics, &CollisionLayers::new()).unwrap_err();
+        assert_eq!(locations(&errors), vec!((1, 18), (1, 33), (2, 2), (2, 21)));
+        assert_eq!(errors.errors[3].component, Some("physics.fixture.friction".to_string()));
+    }
+
+    #[test]
+    fn prefab_component_is_overridden_by_name(){
+        let source = "prefab Crate[ name(\"Crate\") scale(2.0, 2.0, 1.0) physics(dynamic, 5.0, 1.0, 1.0) enemy_movement(3.0) ];\n\
+                      entity Crate[ pos(1.0, 2.0, 0.0) physics(static) ];";
+        let description = SceneDescription::parse(source, None).unwrap();
+        let entity = &description.entities[0];
+        assert_eq!(entity.name, Some("Crate".to_string()));
+        assert_eq!(entity.position, cgmath::Vector3::<f32> { x: 1.0, y: 2.0, z: 0.0 });
+        assert_eq!(entity.scale, cgmath::Vector3::<f32> { x: 2.0, y: 2.0, z: 1.0 });
+        // Only the body type was overridden, the rest of the arguments come from the prefab
+        let physics = entity.component("physics").unwrap();
+        assert_eq!(physics.args, vec!(Value::Ident("static".to_string()), Value::Number(5.0), Value::Number(1.0), Value::Number(1.0)));
+        assert_eq!((physics.line, physics.column), (2, 34));
+        assert_eq!(entity.components.iter().filter(|c| c.name == "physics").count(), 1);
+        assert!(entity.component("enemy_movement").is_some());
+    }
+
+    #[test]
+    fn prefab_blocks_merge_by_setting(){
+        let source = "prefab Ball[ physics{ body(dynamic) mass(2.0) fixture(circle(0.5)){ friction(0.1) restitution(0.8) } } ];\n\
+                      entity Ball[ physics{ mass(4.0) fixture(circle(0.5)){ friction(0.9) } layer(2) } ];";
+        let description = SceneDescription::parse(source, None).unwrap();
+        let physics = PhysicsDescription::from_node(description.entities[0].component("physics").unwrap(), &description.collision_layers).unwrap();
+        assert_eq!(physics.body_type, b2::BodyType::Dynamic);
+        assert_eq!(physics.mass, 4.0);
+        assert_eq!(physics.layer, 2);
+        assert_eq!(physics.fixtures.len(), 1);
+        assert_eq!(physics.fixtures[0].friction, 0.9);
+        assert_eq!(physics.fixtures[0].restitution, 0.8);
+    }
+
+    #[test]
+    fn include_cycle_is_reported(){
+        let directory = std::env::temp_dir().join("knock_the_enemy_include_cycle");
+        std::fs::create_dir_all(&directory).unwrap();
+        let scene = directory.join("scene.dbscene");
+        std::fs::write(&scene, "include \"props.dbscene\";\nentity Crate[ pos(1.0, 0.0, 0.0) ];").unwrap();
+        std::fs::write(directory.join("props.dbscene"), "include \"scene.dbscene\";\nprefab Crate[ scale(2.0, 2.0, 1.0) ];").unwrap();
+
+        let errors = SceneDescription::from_file(scene.to_str().unwrap(), None).unwrap_err();
+        assert_eq!(errors.errors.len(), 1);
+        let error = &errors.errors[0];
+        assert!(error.message.starts_with("including "), "{}", error.message);
+        assert!(error.message.ends_with("leads back to a file that is already being loaded"), "{}", error.message);
+        // Reported at the include in the prefab file, the prefabs it does define are still imported
+        assert!(error.path.ends_with("props.dbscene"), "{}", error.path);
+        assert_eq!((error.line, error.column), (1, 1));
+    }
+
+    #[test]
+    fn unknown_prefab_lists_the_known_ones(){
+        let errors = SceneDescription::parse("entity Crate[ pos(1.0, 0.0, 0.0) ];", None).unwrap_err();
+        assert_eq!(errors.errors[0].message, "unknown prefab 'Crate'");
+        assert_eq!(errors.errors[0].hint, Some("define it with `prefab Name[ ... ];`, or include the file that does".to_string()));
+
+        let source = "prefab Wall[ scale(1.0, 4.0, 1.0) ];\nprefab Barrel[ scale(1.0, 1.0, 1.0) ];\nentity Crate[ pos(1.0, 0.0, 0.0) ];";
+        let errors = SceneDescription::parse(source, None).unwrap_err();
+        assert_eq!(locations(&errors), vec!((3, 1)));
+        assert_eq!(errors.errors[0].hint, Some("known prefabs are Barrel, Wall".to_string()));
+    }
+
+    #[test]
+    fn duplicate_prefab_is_reported(){
+        let source = "prefab Crate[ scale(2.0, 2.0, 1.0) ];\nprefab Crate[ scale(3.0, 3.0, 1.0) ];\nentity Crate[ ];";
+        let errors = SceneDescription::parse(source, None).unwrap_err();
+        assert_eq!(locations(&errors), vec!((2, 1)));
+        assert_eq!(errors.errors[0].message, "prefab 'Crate' is already defined");
+        // The first definition is the one that's kept
+        let (node, _) = Parser::parse(source);
+        let mut library = PrefabLibrary::new();
+        let mut errors = Vec::<SceneError>::new();
+        library.add(&node.prefabs, &mut errors);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(library.expand(&node.entities[0]).unwrap()[0].args[0], Value::Number(2.0));
+    }
+}
+
@@ -0,0 +1,99 @@
+use std::fmt;
+
+// A single problem found while loading a scene. Line and column are 1-based, 0 means "no location"
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneError{
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    // The component the error was found in, e.g. "pos" or "physics"
+    pub component: Option<String>,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl SceneError{
+    pub fn new(line: usize, column: usize, message: String) -> Self{
+        Self{
+            path: String::new(),
+            line,
+            column,
+            component: None,
+            message,
+            hint: None,
+        }
+    }
+
+    pub fn component(mut self, component: &str) -> Self{
+        self.component = Some(component.to_string());
+        self
+    }
+
+    pub fn hint(mut self, hint: &str) -> Self{
+        self.hint = Some(hint.to_string());
+        self
+    }
+
+    // Keeps an existing path, so errors from included files still point at the file they came from
+    pub fn path(mut self, path: &str) -> Self{
+        if self.path.is_empty(){
+            self.path = path.to_string();
+        }
+        self
+    }
+}
+
+impl fmt::Display for SceneError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        if !self.path.is_empty(){
+            write!(f, "{}:", self.path)?;
+        }
+        if self.line > 0{
+            write!(f, "{}:{}:", self.line, self.column)?;
+        }
+        write!(f, " ")?;
+        if let Some(component) = &self.component{
+            write!(f, "in '{}': ", component)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(hint) = &self.hint{
+            write!(f, "\n    hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SceneError{}
+
+// Every error found in a scene file, so a whole file can be fixed in one pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneErrors{
+    pub errors: Vec<SceneError>,
+}
+
+impl SceneErrors{
+    pub fn new(errors: Vec<SceneError>) -> Self{
+        Self{
+            errors
+        }
+    }
+}
+
+// So `?` on a single error works in code that reports several
+impl From<SceneError> for SceneErrors{
+    fn from(error: SceneError) -> Self{
+        SceneErrors::new(vec!(error))
+    }
+}
+
+impl fmt::Display for SceneErrors{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{} error(s) in scene", self.errors.len())?;
+        for error in self.errors.iter(){
+            write!(f, "\n{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SceneErrors{}
@@ -0,0 +1,124 @@
+use wrapped2d::b2;
+use crate::{PhysicsFixture, ShapeKind};
+use super::parser::{ComponentNode, Value};
+use super::error::{SceneError, SceneErrors};
+use super::args::*;
+
+// The fixture(shape){ settings } entries of a physics{ } block, see PhysicsDescription::from_node
+
+// fixture(shape){ settings } - returns the fixture and whether it set its own density
+pub fn fixture_setting(node: &ComponentNode) -> Result<(PhysicsFixture, bool), SceneErrors>{
+    let mut errors = Vec::<SceneError>::new();
+    // Settings are still checked when the shape is wrong, so a placeholder stands in for it
+    let mut fixture = match fixture_shape(node){
+        Ok(v) => v,
+        Err(e) => {
+            errors.push(e.component("physics.fixture"));
+            PhysicsFixture::new_box(1.0, 1.0)
+        },
+    };
+
+    let mut has_density = false;
+    for setting in node.block.iter(){
+        let result = match setting.name.as_str(){
+            "offset" => number_arg(setting, 0).and_then(|x| number_arg(setting, 1).map(|y| fixture.offset = b2::Vec2 { x, y })),
+            "angle" => number_arg(setting, 0).map(|v| fixture.angle = v.to_radians()),
+            "density" => number_arg(setting, 0).map(|v| {
+                fixture.density = v;
+                has_density = true;
+            }),
+            "friction" => number_arg(setting, 0).map(|v| fixture.friction = v),
+            "restitution" => number_arg(setting, 0).map(|v| fixture.restitution = v),
+            "sensor" => bool_arg(setting, 0).map(|v| fixture.is_sensor = v),
+            "group" => number_arg(setting, 0).and_then(|v| {
+                if v.fract() != 0.0 || v < i16::MIN as f32 || v > i16::MAX as f32{
+                    return Err(error_at(setting, format!("argument 1 must be a whole number from {} to {}, found {}", i16::MIN, i16::MAX, v)));
+                }
+                fixture.group = v as i16;
+                Ok(())
+            }),
+            other => Err(error_at(setting, format!("unknown fixture setting '{}'", other))
+                .hint("known settings are offset, angle, density, friction, restitution, sensor and group")),
+        };
+        if let Err(e) = result{
+            errors.push(e.component(&format!("physics.fixture.{}", setting.name)));
+        }
+    }
+    if !errors.is_empty(){
+        return Err(SceneErrors::new(errors));
+    }
+    Ok((fixture, has_density))
+}
+
+// The shape(...) of fixture(shape)
+pub fn fixture_shape(node: &ComponentNode) -> Result<PhysicsFixture, SceneError>{
+    let shape_hint = "shapes are box(width,height), circle(radius), polygon(x,y, ...), edge(x,y, x,y), chain(x,y, ...) and loop(x,y, ...)";
+    let (shape, args) = match node.args.get(0){
+        Some(Value::Call(name, args)) if node.args.len() == 1 => (name.as_str(), args),
+        Some(other) if node.args.len() == 1 => return Err(error_at(node, format!("argument 1 must be a shape, found {}", describe(other))).hint(shape_hint)),
+        None => return Err(missing_arg(node, 0).hint(shape_hint)),
+        Some(_) => return Err(error_at(node, format!("takes 1 argument, found {}", node.args.len()))
+            .hint("other fixture settings go in a block, e.g. fixture(circle(0.5)){ density(2.0) }")),
+    };
+    let mut numbers = Vec::<f32>::new();
+    for (i, value) in args.iter().enumerate(){
+        match value{
+            Value::Number(v) => numbers.push(*v),
+            other => return Err(error_at(node, format!("{}(...) argument {} must be a number, found {}", shape, i + 1, describe(other)))),
+        }
+    }
+    let count = |expected: usize| -> Result<(), SceneError>{
+        if numbers.len() != expected{
+            return Err(error_at(node, format!("{}(...) takes {} numbers, found {}", shape, expected, numbers.len())));
+        }
+        Ok(())
+    };
+    let points = || -> Result<Vec<b2::Vec2>, SceneError>{
+        if numbers.len() % 2 != 0{
+            return Err(error_at(node, format!("{}(...) takes x,y pairs, found {} numbers", shape, numbers.len())));
+        }
+        Ok(numbers.chunks(2).map(|p| b2::Vec2 { x: p[0], y: p[1] }).collect())
+    };
+
+    match shape{
+        "box" => count(2).map(|_| PhysicsFixture::new_box(numbers[0], numbers[1])),
+        "circle" => count(1).map(|_| PhysicsFixture::new_circle(numbers[0])),
+        "polygon" => points().and_then(|p| PhysicsFixture::new_polygon(p).map_err(|e| error_at(node, e))),
+        "edge" => count(4).and_then(|_| PhysicsFixture::new_edge(b2::Vec2 { x: numbers[0], y: numbers[1] }, b2::Vec2 { x: numbers[2], y: numbers[3] }).map_err(|e| error_at(node, e))),
+        "chain" => points().and_then(|p| PhysicsFixture::new_chain(p, false).map_err(|e| error_at(node, e))),
+        "loop" => points().and_then(|p| PhysicsFixture::new_chain(p, true).map_err(|e| error_at(node, e))),
+        other => Err(error_at(node, format!("unknown shape '{}'", other)).hint(shape_hint)),
+    }
+}
+
+// Density is always written, since a fixture without one would take the body's mass when loaded
+pub fn fixture_node(fixture: &PhysicsFixture) -> ComponentNode{
+    let flatten = |points: &[b2::Vec2]| -> Vec<Value>{
+        points.iter().flat_map(|p| vec!(Value::Number(p.x), Value::Number(p.y))).collect()
+    };
+    let shape = match &fixture.shape{
+        ShapeKind::Box(width, height) => Value::Call("box".to_string(), vec!(Value::Number(*width), Value::Number(*height))),
+        ShapeKind::Circle(radius) => Value::Call("circle".to_string(), vec!(Value::Number(*radius))),
+        ShapeKind::Polygon(points) => Value::Call("polygon".to_string(), flatten(points)),
+        ShapeKind::Edge(from, to) => Value::Call("edge".to_string(), flatten(&[*from, *to])),
+        ShapeKind::Chain(points, looped) => Value::Call(if *looped { "loop" } else { "chain" }.to_string(), flatten(points)),
+    };
+
+    let mut node = ComponentNode::new("fixture", vec!(shape));
+    if fixture.offset.x != 0.0 || fixture.offset.y != 0.0{
+        node.block.push(ComponentNode::new("offset", vec!(Value::Number(fixture.offset.x), Value::Number(fixture.offset.y))));
+    }
+    if fixture.angle != 0.0{
+        node.block.push(ComponentNode::new("angle", vec!(Value::Number(fixture.angle.to_degrees()))));
+    }
+    node.block.push(ComponentNode::new("density", vec!(Value::Number(fixture.density))));
+    node.block.push(ComponentNode::new("friction", vec!(Value::Number(fixture.friction))));
+    node.block.push(ComponentNode::new("restitution", vec!(Value::Number(fixture.restitution))));
+    if fixture.is_sensor{
+        node.block.push(ComponentNode::new("sensor", vec!(Value::Bool(true))));
+    }
+    if fixture.group != 0{
+        node.block.push(ComponentNode::new("group", vec!(Value::Number(fixture.group as f32))));
+    }
+    node
+}
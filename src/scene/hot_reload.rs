@@ -0,0 +1,295 @@
+use std::time::{Duration, Instant, SystemTime};
+use crate::{EntityManager, EntityHandle, Physics, Renderer, Arc, Transform, PhysicsComponent, CollisionLayers, PhysicsConfig};
+use super::{SceneLoader, SceneDescription, EntityDescription, ComponentRegistry, SceneError, SceneErrors};
+
+// How often the scene files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Loads a scene and keeps it in sync with its file while the game runs. Changes are found by polling
+// modification times, so no file system notification service is needed.
+// Only entities spawned from the scene are touched, entities created in code are left alone
+pub struct SceneWatcher{
+    path: String,
+    // None when the scene is loaded headless
+    camera_bind_group: Option<Arc<wgpu::BindGroup>>,
+    description: SceneDescription,
+    // spawned[i] is the entity built from description.entities[i]
+    spawned: Vec<EntityHandle>,
+    // The scene and every file it includes, with the modification time they had when last read
+    modified: Vec<(String, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl SceneWatcher{
+    // Nothing is spawned until the first reload. Without a camera bind group (and renderer) the scene is loaded headless, like SceneLoader::spawn
+    pub fn new(path: &str, camera_bind_group: Option<Arc<wgpu::BindGroup>>) -> Self{
+        Self{
+            path: path.to_string(),
+            camera_bind_group,
+            description: SceneDescription{
+                version: 0,
+                includes: Vec::<String>::new(),
+                entities: Vec::<EntityDescription>::new(),
+                collision_layers: CollisionLayers::new(),
+                physics_config: PhysicsConfig::new(),
+            },
+            spawned: Vec::<EntityHandle>::new(),
+            // Watch the file even if the first load fails, so fixing it gets picked up by poll
+            modified: vec!((path.to_string(), SceneWatcher::modified_time(path))),
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn get_path(&self) -> &str{
+        &self.path
+    }
+
+    // The entities currently spawned from the scene
+    pub fn get_spawned(&self) -> &Vec<EntityHandle>{
+        &self.spawned
+    }
+
+    // Read the scene again and apply the differences. If the file fails to parse the world is left as it was
+    pub fn reload(&mut self, registry: &ComponentRegistry, entity_manager: &mut EntityManager, physics_manager: &mut Physics, renderer_reference: Option<&Renderer>) -> Result<(), SceneErrors>{
+        let description = SceneDescription::from_file(&self.path, Some(registry))?;
+        if description.version < super::SCENE_FORMAT_VERSION{
+            log::warn!("{} uses scene format version {}, loading in compatibility mode", self.path, description.version);
+        }
+        let errors = self.apply(description, registry, entity_manager, physics_manager, renderer_reference);
+        if errors.is_empty(){
+            Ok(())
+        }else{
+            Err(SceneErrors::new(errors.into_iter().map(|error| error.path(&self.path)).collect()))
+        }
+    }
+
+    // Call once per frame. Reloads the scene if it or one of its includes changed, and returns true if it did
+    pub fn poll(&mut self, registry: &ComponentRegistry, entity_manager: &mut EntityManager, physics_manager: &mut Physics, renderer_reference: Option<&Renderer>) -> bool{
+        if self.last_poll.elapsed() < POLL_INTERVAL{
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let changed = self.modified.iter().any(|(path, time)| SceneWatcher::modified_time(path) != *time);
+        if !changed{
+            return false;
+        }
+        // Record the new times first, so a broken file is only reported once per save
+        for (path, time) in self.modified.iter_mut(){
+            *time = SceneWatcher::modified_time(path);
+        }
+
+        log::info!("{} changed, reloading", self.path);
+        if let Err(e) = self.reload(registry, entity_manager, physics_manager, renderer_reference){
+            eprintln!("{}", e);
+            log::error!("{}", e);
+        }
+        true
+    }
+
+    fn modified_time(path: &str) -> Option<SystemTime>{
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    // Diff the new description against what's spawned and bring the world in line with it
+    // Returns the components that failed to build. Their entities are still spawned without them
+    fn apply(&mut self, description: SceneDescription, registry: &ComponentRegistry, entity_manager: &mut EntityManager, physics_manager: &mut Physics, renderer_reference: Option<&Renderer>) -> Vec<SceneError>{
+        let mut errors = Vec::<SceneError>::new();
+        // Before spawning, so layer names resolve. Bodies that are kept are filtered again
+        physics_manager.set_collision_layers(description.collision_layers.clone());
+        if let Err(e) = physics_manager.set_config(description.physics_config.clone()){
+            errors.push(SceneError::new(0, 0, e).component("physics"));
+        }
+        let old = std::mem::replace(&mut self.description.entities, Vec::<EntityDescription>::new());
+        // Taken as they're paired up, so whatever is left at the end was deleted from the file
+        let mut old_ids: Vec<Option<EntityHandle>> = self.spawned.drain(..).map(|id| Some(id)).collect();
+
+        // Pair every new entity with the old one it came from: first identical entities, then entities with the same name.
+        // This keeps the pairing stable when entities are added or removed in the middle of the file
+        let mut matches: Vec<Option<usize>> = vec!(None; description.entities.len());
+        for (i, def) in description.entities.iter().enumerate(){
+            let found = (0..old.len()).find(|j| !matches.contains(&Some(*j)) && old[*j].same_as(def));
+            matches[i] = found;
+        }
+        for (i, def) in description.entities.iter().enumerate(){
+            if matches[i].is_none() && def.name.is_some(){
+                let found = (0..old.len()).find(|j| !matches.contains(&Some(*j)) && old[*j].name == def.name);
+                matches[i] = found;
+            }
+        }
+
+        let (mut added, mut updated, mut removed) = (0, 0, 0);
+        let mut spawned = Vec::<EntityHandle>::new();
+        for (i, def) in description.entities.iter().enumerate(){
+            let id = match matches[i]{
+                Some(j) if old[j].same_as(def) => old_ids[j].take().unwrap(),
+                // Only the transform, name or tags changed, so update the entity instead of rebuilding it
+                Some(j) if old[j].same_components_as(def) => {
+                    let id = old_ids[j].take().unwrap();
+                    SceneWatcher::update_entity(id, def, entity_manager, physics_manager);
+                    updated += 1;
+                    id
+                },
+                Some(j) => {
+                    let id = old_ids[j].take().unwrap();
+                    if let Err(e) = entity_manager.destroy_entity(id, physics_manager){
+                        log::warn!("Scene entity {:?} was already gone: {}", id, e);
+                    }
+                    updated += 1;
+                    SceneLoader::spawn_entity(def, description.version, registry, entity_manager, renderer_reference, physics_manager, self.camera_bind_group.clone(), &mut errors)
+                },
+                None => {
+                    added += 1;
+                    SceneLoader::spawn_entity(def, description.version, registry, entity_manager, renderer_reference, physics_manager, self.camera_bind_group.clone(), &mut errors)
+                },
+            };
+            spawned.push(id);
+        }
+
+        // Whatever wasn't paired was deleted from the file
+        for id in old_ids.into_iter().flatten(){
+            if let Err(e) = entity_manager.destroy_entity(id, physics_manager){
+                log::warn!("Scene entity {:?} was already gone: {}", id, e);
+            }
+            removed += 1;
+        }
+        // Rebuilt entities and the entities joined to them
+        SceneLoader::connect_joints(entity_manager, physics_manager, &description, &spawned, &mut errors);
+        log::info!("Scene {}: {} added, {} updated, {} removed", self.path, added, updated, removed);
+
+        self.modified = vec!((self.path.clone(), SceneWatcher::modified_time(&self.path)));
+        for include in description.includes.iter(){
+            self.modified.push((include.clone(), SceneWatcher::modified_time(include)));
+        }
+        self.description = description;
+        self.spawned = spawned;
+
+        errors
+    }
+
+    fn update_entity(id: EntityHandle, def: &EntityDescription, entity_manager: &mut EntityManager, physics_manager: &mut Physics){
+        let entity = match entity_manager.get_entity_mut(id){
+            Some(v) => v,
+            None => return,
+        };
+        entity.name = def.name.clone();
+        entity.tags = def.tags.iter().cloned().collect();
+        let rotation = cgmath::Quaternion::from(cgmath::Euler {
+            x: cgmath::Deg(def.rotation.x),
+            y: cgmath::Deg(def.rotation.y),
+            z: cgmath::Deg(def.rotation.z),
+        });
+
+        if let Some(mut transform) = entity_manager.get_component_mut::<Transform>(id){
+            transform.set_position(def.position);
+            transform.set_rotation(rotation);
+            transform.set_scale(def.scale);
+            // Teleport, don't interpolate from the old spot. TransformSync uploads it next frame
+            transform.store_previous();
+        }
+        if let Some(mut physics) = entity_manager.get_component_mut::<PhysicsComponent>(id){
+            let position = physics_manager.to_meters(def.position);
+            let angle = cgmath::Rad::from(cgmath::Deg(def.rotation.z)).0;
+            physics.set_transform(physics_manager, position, angle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::MovementComponent;
+
+    const SCENE: &str = "version 1;\n\
+                         entity[ name(\"Crate\") pos(1.0, 2.0, 0.0) physics(dynamic, 1.0) ];\n\
+                         entity[ name(\"Wall\") pos(-4.0, 0.0, 0.0) physics(static, 0.0, 1.0, 4.0) ];";
+
+    struct TestScene{
+        path: String,
+        registry: ComponentRegistry,
+        entity_manager: EntityManager,
+        physics: Physics,
+        watcher: SceneWatcher,
+    }
+
+    impl TestScene{
+        // Every test gets its own file, since tests run in parallel
+        fn load(file: &str) -> Self{
+            let path = std::env::temp_dir().join(file).to_string_lossy().to_string();
+            std::fs::write(&path, SCENE).unwrap();
+            let mut scene = Self{
+                registry: ComponentRegistry::with_engine_components().unwrap(),
+                entity_manager: EntityManager::new(),
+                physics: Physics::new(),
+                watcher: SceneWatcher::new(&path, None),
+                path,
+            };
+            scene.reload(SCENE);
+            scene
+        }
+
+        fn reload(&mut self, source: &str){
+            std::fs::write(&self.path, source).unwrap();
+            self.watcher.reload(&self.registry, &mut self.entity_manager, &mut self.physics, None).unwrap();
+        }
+
+        fn entity(&self, name: &str) -> EntityHandle{
+            self.entity_manager.find_by_name(name).unwrap().handle
+        }
+    }
+
+    #[test]
+    fn unchanged_reload_touches_nothing(){
+        let mut scene = TestScene::load("knock_the_enemy_reload_unchanged.dbscene");
+        let spawned = scene.watcher.get_spawned().clone();
+        let crate_entity = scene.entity("Crate");
+        // Moved by the game since it was loaded, the reload must not put it back
+        scene.entity_manager.get_component_mut::<Transform>(crate_entity).unwrap().set_position(cgmath::Vector3::<f32> { x: 10.0, y: 2.0, z: 0.0 });
+
+        // Only the locations in the file change
+        scene.reload(&format!("// A comment pushes every entity down a line\n{}", SCENE.replace("entity[ name", "entity[  name")));
+        assert_eq!(scene.watcher.get_spawned(), &spawned);
+        assert_eq!(scene.entity_manager.entities.len(), 2);
+        assert_eq!(scene.entity_manager.get_component::<Transform>(crate_entity).unwrap().get_position().x, 10.0);
+    }
+
+    #[test]
+    fn moved_entity_is_updated_in_place(){
+        let mut scene = TestScene::load("knock_the_enemy_reload_moved.dbscene");
+        let crate_entity = scene.entity("Crate");
+        scene.reload(&SCENE.replace("pos(1.0, 2.0, 0.0)", "pos(3.0, 5.0, 0.0)"));
+
+        assert!(scene.entity_manager.is_alive(crate_entity));
+        assert_eq!(scene.watcher.get_spawned()[0], crate_entity);
+        let position = scene.entity_manager.get_component::<Transform>(crate_entity).unwrap().get_position();
+        assert_eq!(position, cgmath::Vector3::<f32> { x: 3.0, y: 5.0, z: 0.0 });
+        let handle = scene.entity_manager.get_component::<PhysicsComponent>(crate_entity).unwrap().handle;
+        let expected = scene.physics.to_meters(position);
+        assert_eq!(*scene.physics.world.body(handle).position(), expected);
+    }
+
+    #[test]
+    fn removed_entity_is_removed(){
+        let mut scene = TestScene::load("knock_the_enemy_reload_removed.dbscene");
+        let (crate_entity, wall) = (scene.entity("Crate"), scene.entity("Wall"));
+        let bodies = scene.physics.world.bodies().count();
+        scene.reload(&SCENE.replace("entity[ name(\"Crate\") pos(1.0, 2.0, 0.0) physics(dynamic, 1.0) ];\n", ""));
+
+        assert!(!scene.entity_manager.is_alive(crate_entity));
+        assert!(scene.entity_manager.is_alive(wall));
+        assert_eq!(scene.watcher.get_spawned(), &vec!(wall));
+        assert_eq!(scene.physics.world.bodies().count(), bodies - 1);
+    }
+
+    #[test]
+    fn code_created_entity_is_left_alone(){
+        let mut scene = TestScene::load("knock_the_enemy_reload_code.dbscene");
+        let created = scene.entity_manager.create_entity(vec![Box::new(MovementComponent::new(2.0))], Vec::new());
+        // Every scene entity is removed
+        scene.reload("version 1;");
+
+        assert!(scene.watcher.get_spawned().is_empty());
+        assert_eq!(scene.entity_manager.entities.len(), 1);
+        assert_eq!(scene.entity_manager.get_component::<MovementComponent>(created).unwrap().speed, 2.0);
+    }
+}
@@ -0,0 +1,183 @@
+use wrapped2d::b2;
+use crate::{PhysicsJoint, JointKind};
+use super::parser::{ComponentNode, Value};
+use super::error::{SceneError, SceneErrors};
+use super::args::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointsDescription{
+    pub joints: Vec<PhysicsJoint>,
+}
+
+impl JointsDescription{
+    // joints{ revolute("Anchor"){ anchor(0.0,1.0) limit(-45,45) motor(90,100) } rope(world){ length(3.0) } mouse{ max_force(500) } }
+    // Each joint names the entity at the other end, or `world` (The default). Points are relative to each body,
+    // points on the world are world positions. Settings are:
+    // anchor(x,y) and other_anchor(x,y) for where it holds each body (other_anchor is left where the anchor is, or for distance and rope joints
+    // on the other body's origin), collide(true) to keep the bodies colliding,
+    // limit(lower,upper) and motor(speed,max_force) for revolute (Degrees) and prismatic (Meters) joints, axis(x,y) for prismatic joints,
+    // length(l) for distance and rope joints, spring(hertz,damping) for distance, weld and mouse joints and max_force(f) for mouse joints.
+    // A mouse joint's other_anchor is where it pulls the entity to
+    // Every joint is read, so one bad joint doesn't hide problems with the others
+    pub fn from_node(node: &ComponentNode) -> Result<Self, SceneErrors>{
+        let mut errors = Vec::<SceneError>::new();
+        if !node.args.is_empty(){
+            errors.push(error_at(node, format!("takes no arguments, found {}", node.args.len()))
+                .hint("the joints go in a block, e.g. joints{ revolute(\"Anchor\") }"));
+        }
+        let mut joints = Vec::<PhysicsJoint>::new();
+        for setting in node.block.iter(){
+            match joint_setting(setting){
+                Ok(joint) => joints.push(joint),
+                Err(e) => errors.extend(e.errors),
+            }
+        }
+        if !errors.is_empty(){
+            return Err(SceneErrors::new(errors));
+        }
+        Ok(Self{
+            joints,
+        })
+    }
+
+    pub fn to_node(&self) -> ComponentNode{
+        let mut node = ComponentNode::new("joints", Vec::<Value>::new());
+        for joint in self.joints.iter(){
+            node.block.push(joint_node(joint));
+        }
+        node
+    }
+}
+
+// kind(other){ settings }
+pub fn joint_setting(node: &ComponentNode) -> Result<PhysicsJoint, SceneErrors>{
+    let component = format!("joints.{}", node.name);
+    let kind = match JointKind::from_name(&node.name){
+        Some(v) => v,
+        None => return Err(error_at(node, format!("unknown joint '{}'", node.name)).component(&component)
+            .hint("joints are revolute, prismatic, distance, weld, rope and mouse").into()),
+    };
+    let mut errors = Vec::<SceneError>::new();
+    let mut joint = PhysicsJoint::new(kind);
+    match node.args.get(0){
+        Some(Value::Str(name)) if node.args.len() == 1 => joint.other = Some(name.clone()),
+        Some(Value::Ident(name)) if node.args.len() == 1 && name == "world" => {},
+        None => {},
+        Some(other) if node.args.len() == 1 => errors.push(error_at(node, format!("argument 1 must be an entity name or world, found {}", describe(other))).component(&component)
+            .hint(&format!("name the entity at the other end, e.g. {}(\"Anchor\")", node.name))),
+        Some(_) => errors.push(error_at(node, format!("takes 1 argument, found {}", node.args.len())).component(&component)
+            .hint(&format!("other joint settings go in a block, e.g. {}(\"Anchor\"){{ anchor(0.0,1.0) }}", node.name))),
+    }
+    if kind == JointKind::Mouse && joint.other.is_some(){
+        errors.push(error_at(node, "mouse joints pull towards a point in the world, they can't connect to an entity".to_string()).component(&component)
+            .hint("give the point with other_anchor(x,y)"));
+    }
+
+    // Revolute joints are written in degrees
+    let angle = |v: f32| if kind == JointKind::Revolute { v.to_radians() } else { v };
+    let pair = |setting: &ComponentNode| -> Result<(f32, f32), SceneError>{
+        if setting.args.len() != 2{
+            return Err(error_at(setting, format!("takes 2 numbers, found {}", setting.args.len())));
+        }
+        Ok((number_arg(setting, 0)?, number_arg(setting, 1)?))
+    };
+    let only = |setting: &ComponentNode, has: bool| -> Result<(), SceneError>{
+        if !has{
+            return Err(error_at(setting, format!("{} joints have no {} setting", kind.get_name(), setting.name)));
+        }
+        Ok(())
+    };
+    for setting in node.block.iter(){
+        let result = match setting.name.as_str(){
+            "anchor" => pair(setting).map(|(x, y)| joint.anchor = b2::Vec2 { x, y }),
+            "other_anchor" => pair(setting).map(|(x, y)| joint.other_anchor = Some(b2::Vec2 { x, y })),
+            "collide" => bool_arg(setting, 0).map(|v| joint.collide_connected = v),
+            "axis" => only(setting, kind == JointKind::Prismatic).and_then(|_| pair(setting)).and_then(|(x, y)| {
+                if x == 0.0 && y == 0.0{
+                    return Err(error_at(setting, "the axis can't be 0,0".to_string()).hint("e.g. axis(0.0,1.0) slides up and down"));
+                }
+                joint.axis = b2::Vec2 { x, y };
+                Ok(())
+            }),
+            "limit" => only(setting, kind.has_limit()).and_then(|_| pair(setting)).and_then(|(lower, upper)| {
+                if lower > upper{
+                    return Err(error_at(setting, format!("the lower limit {} is above the upper limit {}", lower, upper)));
+                }
+                joint.limit = Some((angle(lower), angle(upper)));
+                Ok(())
+            }),
+            "motor" => only(setting, kind.has_motor()).and_then(|_| pair(setting)).map(|(speed, max_force)| joint.motor = Some((angle(speed), max_force))),
+            "length" => only(setting, kind.has_length()).and_then(|_| number_arg(setting, 0)).map(|v| joint.length = Some(v)),
+            "spring" => only(setting, kind.has_spring()).and_then(|_| pair(setting)).map(|(frequency, damping)| {
+                joint.frequency = frequency;
+                joint.damping = damping;
+            }),
+            "max_force" => only(setting, kind == JointKind::Mouse).and_then(|_| number_arg(setting, 0)).map(|v| joint.max_force = v),
+            other => Err(error_at(setting, format!("unknown joint setting '{}'", other))
+                .hint("known settings are anchor, other_anchor, collide, axis, limit, motor, length, spring and max_force")),
+        };
+        if let Err(e) = result{
+            errors.push(e.component(&format!("{}.{}", node.name, setting.name)));
+        }
+    }
+    if !errors.is_empty(){
+        return Err(SceneErrors::new(errors));
+    }
+    Ok(joint)
+}
+
+// Only settings that aren't the defaults are written
+pub fn joint_node(joint: &PhysicsJoint) -> ComponentNode{
+    let kind = joint.kind;
+    let angle = |v: f32| if kind == JointKind::Revolute { v.to_degrees() } else { v };
+    let other = match &joint.other{
+        Some(name) => Value::Str(name.clone()),
+        None => Value::Ident("world".to_string()),
+    };
+    let mut node = ComponentNode::new(kind.get_name(), vec!(other));
+    let defaults = PhysicsJoint::new(kind);
+    if joint.anchor.x != 0.0 || joint.anchor.y != 0.0{
+        node.block.push(ComponentNode::new("anchor", vec!(Value::Number(joint.anchor.x), Value::Number(joint.anchor.y))));
+    }
+    if let Some(other_anchor) = joint.other_anchor{
+        node.block.push(ComponentNode::new("other_anchor", vec!(Value::Number(other_anchor.x), Value::Number(other_anchor.y))));
+    }
+    if joint.collide_connected{
+        node.block.push(ComponentNode::new("collide", vec!(Value::Bool(true))));
+    }
+    if kind == JointKind::Prismatic{
+        node.block.push(ComponentNode::new("axis", vec!(Value::Number(joint.axis.x), Value::Number(joint.axis.y))));
+    }
+    if let Some((lower, upper)) = joint.limit{
+        node.block.push(ComponentNode::new("limit", vec!(Value::Number(angle(lower)), Value::Number(angle(upper)))));
+    }
+    if let Some((speed, max_force)) = joint.motor{
+        node.block.push(ComponentNode::new("motor", vec!(Value::Number(angle(speed)), Value::Number(max_force))));
+    }
+    if let Some(length) = joint.length{
+        node.block.push(ComponentNode::new("length", vec!(Value::Number(length))));
+    }
+    if kind.has_spring() && (joint.frequency != defaults.frequency || joint.damping != defaults.damping){
+        node.block.push(ComponentNode::new("spring", vec!(Value::Number(joint.frequency), Value::Number(joint.damping))));
+    }
+    if joint.max_force != defaults.max_force{
+        node.block.push(ComponentNode::new("max_force", vec!(Value::Number(joint.max_force))));
+    }
+    node
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::scene::parser::Parser;
+
+    #[test]
+    fn joints_block_reports_every_bad_joint(){
+        let (node, _) = Parser::parse("entity[ joints{ spring(\"A\")\n revolute(\"A\"){ limit(30, -30) axis(0, 1) } weld(\"B\") } ]");
+        let errors = JointsDescription::from_node(&node.entities[0].components[0]).unwrap_err();
+        let locations: Vec<(usize, usize)> = errors.errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(locations, vec!((1, 17), (2, 17), (2, 32)));
+        let joints = JointsDescription::from_node(&Parser::parse("entity[ joints{ weld(\"B\") rope } ]").0.entities[0].components[0]).unwrap();
+        assert_eq!(joints.joints.len(), 2);
+    }
+}
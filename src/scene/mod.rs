@@ -1,201 +1,177 @@
+pub mod tokenizer;
+pub mod parser;
+pub mod args;
+pub mod description;
+pub mod fixture;
+pub mod joint;
+pub mod collision_layers;
+pub mod physics_config;
+pub mod error;
+pub mod registry;
+pub mod writer;
+pub mod hot_reload;
+
+pub use error::{SceneError, SceneErrors};
+pub use registry::{ComponentRegistry, SceneContext};
+pub use writer::SceneWriter;
+pub use hot_reload::SceneWatcher;
+pub use description::{SceneDescription, EntityDescription, PrefabLibrary, MaterialDescription, PhysicsDescription, SCENE_FORMAT_VERSION};
+pub use joint::JointsDescription;
+
 use crate::*;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::BufRead;
 
 pub struct SceneLoader{
 
 }
 
 impl SceneLoader{
-    pub fn load(path: &str, entity_manager: &mut EntityManager, physics_manager: &mut Physics, renderer_reference: &Renderer, camera_bind_group: Rc<wgpu::BindGroup>){
-        let entity_defs = SceneLoader::load_component(path);
-        for entity_def in entity_defs{
-            SceneLoader::parse_entity(entity_def, entity_manager, renderer_reference, physics_manager, Rc::clone(&camera_bind_group));
+    // Nothing is spawned if the file has syntax errors. Every error in the file is returned, not just the first.
+    // Without a renderer (and camera bind group) the scene is loaded headless: render components are left out and
+    // entities get no TransformBuffer
+    pub fn load(path: &str, registry: &ComponentRegistry, entity_manager: &mut EntityManager, physics_manager: &mut Physics, renderer_reference: Option<&Renderer>, camera_bind_group: Option<Arc<wgpu::BindGroup>>) -> Result<(), SceneErrors>{
+        let description = SceneDescription::from_file(path, Some(registry))?;
+        if description.version < SCENE_FORMAT_VERSION{
+            log::warn!("{} uses scene format version {}, loading in compatibility mode", path, description.version);
         }
+        SceneLoader::spawn(&description, registry, entity_manager, physics_manager, renderer_reference, camera_bind_group)
+            .map_err(|e| SceneErrors::new(e.errors.into_iter().map(|error| error.path(path)).collect()))
     }
 
-    fn load_component(path: &str) -> Vec<String>{
-        // Load all non blank and non comment lines (That include entity)
-        let file = File::open(path).unwrap();
-        let buf_reader = BufReader::new(file);
-        let mut lines = Vec::<String>::new();
-        for line in buf_reader.lines(){
-            if let Ok(ip) = line {
-                if ip != "" && ip.contains("entity") && !ip.contains("//"){
-                    lines.push(ip);
-                }
-            }
+    // Components that fail to build are left out and reported afterwards, the rest of the entity is still spawned
+    pub fn spawn(description: &SceneDescription, registry: &ComponentRegistry, entity_manager: &mut EntityManager, physics_manager: &mut Physics, renderer_reference: Option<&Renderer>, camera_bind_group: Option<Arc<wgpu::BindGroup>>) -> Result<(), SceneErrors>{
+        let mut errors = Vec::<SceneError>::new();
+        // Before spawning, so layer names resolve
+        physics_manager.set_collision_layers(description.collision_layers.clone());
+        // Also before spawning, so positions are scaled by the scene's pixels_per_meter
+        if let Err(e) = physics_manager.set_config(description.physics_config.clone()){
+            errors.push(SceneError::new(0, 0, e).component("physics"));
+        }
+        let mut spawned = Vec::<EntityHandle>::new();
+        for entity_def in description.entities.iter(){
+            spawned.push(SceneLoader::spawn_entity(entity_def, description.version, registry, entity_manager, renderer_reference, physics_manager, camera_bind_group.clone(), &mut errors));
+        }
+        // Once everything is spawned, so joints can find the entity at their other end
+        SceneLoader::connect_joints(entity_manager, physics_manager, description, &spawned, &mut errors);
+        if errors.is_empty(){
+            Ok(())
+        }else{
+            Err(SceneErrors::new(errors))
         }
-        lines
     }
 
-    
-    fn parse_entity(def: String, entity_manager: &mut EntityManager, renderer_reference: &Renderer, physics_manager: &mut Physics, camera_bind_group: Rc<wgpu::BindGroup>){
-        let mut uniforms = vec!(camera_bind_group);
-        let mut entity_components = Vec::<Box<dyn ComponentBase>>::new();
-
-
-        let _components: Vec::<&str> = def.split("[").collect();
-        let components: String = _components[1].to_string();
-        let _components: Vec::<&str> = components.split("]").collect();
-        let components: String = _components[0].to_string();
-        let components: Vec::<String> = components.split(" ").map(|x| x.to_string()).collect();
-        println!("{:?}", components);
-
-
-        let mut position: Translation = Translation::new(cgmath::Vector3::<f32> { x: 0.0, y: 0.0, z: 0.0});
-
-        let mut rotation: Rotation = Rotation::new(cgmath::Quaternion::from(cgmath::Euler {
-            x: cgmath::Deg(0.0),
-            y: cgmath::Deg(0.0),
-            z: cgmath::Deg(0.0),
-        }));
+    // Joints name the entity they connect to, so they can only be checked once everything is spawned. `spawned[i]` is the entity
+    // built from `description.entities[i]`, problems are reported at its joints{...}. Joints added in code have no location
+    pub fn connect_joints(entity_manager: &EntityManager, physics_manager: &mut Physics, description: &SceneDescription, spawned: &[EntityHandle], errors: &mut Vec<SceneError>){
+        for (entity, problem) in JointComponent::connect_all(entity_manager, physics_manager){
+            let (line, column) = spawned.iter().position(|handle| *handle == entity)
+                .and_then(|i| description.entities[i].component("joints"))
+                .map_or((0, 0), |node| (node.line, node.column));
+            errors.push(SceneError::new(line, column, problem).component("joints")
+                .hint("joints connect to entities by their name(...), and both entities need a physics body"));
+        }
+    }
 
-        let mut scale: NonUniformScale = NonUniformScale::new(cgmath::Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0});
-
-
-        for component in components{
-            let split_comp: Vec::<String> = component.split("(").map(|x| x.to_string()).collect();
-            let comp = split_comp[0].clone();
-
-            match comp.as_str(){
-                // Base Components
-                "name" => {
-                    let name: Vec<String> = split_comp[1].split(")").map(|x| x.to_string()).collect();
-                    let name = name[0].clone(); 
-                    println!("Adding component: {:?}", name);
-                },
-
-                "pos" =>  {
-                    let pos: Vec<String> = split_comp[1].split(")").map(|x| x.to_string()).collect();
-                    let pos = pos[0].clone(); 
-                    let xyz: Vec<f32> = pos.split(",").map(|x| x.parse::<f32>().unwrap()).collect();
-                    let x = xyz[0];
-                    let y = xyz[1];
-                    let z = xyz[2];
-                    position = Translation::new(cgmath::Vector3::<f32> {x, y, z});
-                },
+    pub fn spawn_entity(def: &EntityDescription, version: u32, registry: &ComponentRegistry, entity_manager: &mut EntityManager, renderer_reference: Option<&Renderer>, physics_manager: &mut Physics, camera_bind_group: Option<Arc<wgpu::BindGroup>>, errors: &mut Vec<SceneError>) -> EntityHandle{
+        let mut entity_components = Vec::<Box<dyn ComponentBase>>::new();
 
-                "rot" =>  {
-                    let rot: Vec<String> = split_comp[1].split(")").map(|x| x.to_string()).collect();
-                    let rot = rot[0].clone(); 
-                    let xyz: Vec<f32> = rot.split(",").map(|x| x.parse::<f32>().unwrap()).collect();
-
-                    let x = xyz[0];
-                    let y = xyz[1];
-                    let z = xyz[2];
-
-                    rotation = Rotation::new(cgmath::Quaternion::from(cgmath::Euler {
-                        x: cgmath::Deg(x),
-                        y: cgmath::Deg(y),
-                        z: cgmath::Deg(z),
-                    }));
-                },
-
-                "scale" =>  {
-                    let scl: Vec<String> = split_comp[1].split(")").map(|x| x.to_string()).collect();
-                    let scl = scl[0].clone(); 
-                    let xyz: Vec<f32> = scl.split(",").map(|x| x.parse::<f32>().unwrap()).collect();
-
-                    let x = xyz[0];
-                    let y = xyz[1];
-                    let z = xyz[2];
-
-                    scale = NonUniformScale::new(cgmath::Vector3::<f32> { x, y, z});
-                },
-
-                "material" =>  {
-                    let tex_path_raw: Vec<String> = split_comp[1].split(",").map(|x| x.to_string()).collect();
-                    let color_raw: Vec<String> = split_comp[2].split(")").map(|x| x.to_string()).collect();
-
-                    
-                    let tex_path: String = tex_path_raw[0].clone();
-                    println!("{:?}", tex_path);
-
-                    let color: Vec<f32> = color_raw[0].split(",").map(|x| x.parse::<f32>().unwrap()).collect();
-
-                    let x = color[0];
-                    let y = color[1];
-                    let z = color[2];
-
-                    let mut material = Material::new(&renderer_reference, Rc::new(Texture::load_texture(&renderer_reference, &tex_path, TextureMode::RGB).unwrap()), cgmath::Vector3::<f32> { x, y, z }, 1.0, 0.0, -1, "main".to_string());
-
-                    println!("{:?} -> {:?}", tex_path, color);
-
-                    let mut mesh = RenderMesh::new(&renderer_reference, material);
-                    let (bindgroup, layout, _) = mesh.generate_material_uniforms(&renderer_reference);
-
-                    entity_components.push(Box::new(mesh));
-                    uniforms.push(Rc::new(bindgroup));         
-
-                    
-                },
-
-                "physics" =>  {
-                    let phys_settings: Vec<String> = split_comp[1].split(",").map(|x| x.to_string()).collect();
-
-                    println!("{:?}", phys_settings);
-
-                    let body_type = match phys_settings[0].as_str() {
-                        "static" => {
-                            b2::BodyType::Static
-                        }
-                        "dynamic" => {
-                            b2::BodyType::Dynamic
-                        }
-                        "kinematic" => {
-                            b2::BodyType::Kinematic
-                        }
-                        _ => {
-                            b2::BodyType::Static
-                        }
-                    };
-
-                    let layer = phys_settings[4].parse::<u32>().unwrap();
-                    let mass = phys_settings[1].parse::<f32>().unwrap();
-                    let width = phys_settings[2].parse::<f32>().unwrap();
-                    let height = phys_settings[3].parse::<f32>().unwrap();
-
-                    let sleep_mode = match phys_settings[3].as_str() {
-                        "false)" => {false}
-                        "true)" => {true}
-                        _ => {true}
-                    };
-
-                    entity_components.push(Box::new(PhysicsComponent::new_box(physics_manager, position.value, (width, height), mass, body_type, layer, sleep_mode)));
-                },
-
-                // Add specific components here
-
-                "player_movement" => {
-                    let speed: Vec<String> = split_comp[1].split(")").map(|x| x.to_string()).collect();
-                    let speed = speed[0].clone(); 
-                    println!("{:?}", speed);
-                    let player_movement_comp = Box::new(PlayerMovementComponent::new(speed.parse::<f32>().unwrap()));
-                    entity_components.push(player_movement_comp);
-
-                }
-
-                "enemy_movement" => {
-                    let speed: Vec<String> = split_comp[1].split(")").map(|x| x.to_string()).collect();
-                    let speed = speed[0].clone(); 
-                    println!("{:?}", speed);
-                    let enemy_movement_comp = Box::new(MovementComponent::new(speed.parse::<f32>().unwrap()));
-                    entity_components.push(enemy_movement_comp);
-                }
-
-                _ => panic!("Not valid!"),
+        let rotation = cgmath::Quaternion::from(cgmath::Euler {
+            x: cgmath::Deg(def.rotation.x),
+            y: cgmath::Deg(def.rotation.y),
+            z: cgmath::Deg(def.rotation.z),
+        });
+
+        let mut context = SceneContext{
+            renderer: renderer_reference,
+            physics: physics_manager,
+            position: def.position,
+            rotation,
+            scale: def.scale,
+            version,
+            uniforms: camera_bind_group.into_iter().collect(),
+        };
+
+        for component in def.components.iter(){
+            match registry.create(component, &mut context){
+                Ok(Some(v)) => entity_components.push(v),
+                Ok(None) => {},
+                Err(e) => errors.extend(e.errors),
             }
         }
-        println!("Pos: {:?}\nScale: {:?}\nRot: {:?}", position.value, scale.value, rotation.value);
-        let mut transform = Transform::new(renderer_reference, position.value, rotation.value, scale.value);
-        let (trans_bind_group, layout, _) = transform.create_uniforms(&renderer_reference);
-        uniforms.push(Rc::new(trans_bind_group));
-        entity_components.push(Box::new(transform));
 
-        {
-            entity_manager.create_entity(entity_components, uniforms);
+        let mut uniforms = context.uniforms;
+        let transform = Transform::new(def.position, rotation, def.scale);
+        if let Some(renderer_reference) = renderer_reference{
+            let (transform_buffer, trans_bind_group) = TransformBuffer::new(renderer_reference, &transform);
+            uniforms.push(Arc::new(trans_bind_group));
+            entity_components.push(Box::new(transform_buffer));
         }
+        entity_components.push(Box::new(transform));
+
+        let handle = entity_manager.create_entity(entity_components, uniforms);
+        let entity = entity_manager.get_entity_mut(handle).unwrap();
+        entity.name = def.name.clone();
+        entity.tags = def.tags.iter().cloned().collect();
+        handle
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::physics::layers;
+
+    #[test]
+    fn joint_problems_point_at_the_joints(){
+        let registry = ComponentRegistry::with_engine_components().unwrap();
+        let source = "version 1;\nentity[ name(\"A\") physics(dynamic, 1.0)\n  joints{ weld(\"Nobody\") } ]";
+        let description = SceneDescription::parse(source, Some(&registry)).unwrap();
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        let errors = SceneLoader::spawn(&description, &registry, &mut entity_manager, &mut physics, None, None).unwrap_err();
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!((errors.errors[0].line, errors.errors[0].column), (3, 3));
+        assert!(errors.errors[0].message.contains("'Nobody'"), "{}", errors.errors[0].message);
+    }
+
+    // data/scene/scene.dbscene as the engine first shipped it: no version header, unquoted names and paths, numeric layers
+    const LEGACY_SCENE: &str = r#"// Example of entity scene - defines an entity, which will be read by the engine and processed. Comments MUST be on their own line.
+
+// Wall-Enemy Entities
+entity[name(WallEnemy) pos(15.0,0.0,0.0) rot(.0,0.0,45.0) scale(2.0,1.0,1.0) material(./data/textures/white.png,color(1.0,1.0,1.0),1) physics(dynamic,5.0,2.0,1.0,2,false) enemy_movement(-75.0)];
+entity[name(WallEnemy) pos(5.0,0.0,0.0) rot(0.0,0.0,90.0) scale(2.0,1.0,1.0) material(./data/textures/white.png,color(1.0,1.0,1.0),1) physics(dynamic,5.0,2.0,1.0,2,false) enemy_movement(-75.0)];
+// Regular Entities
+entity[name(plane) pos(-5.0,0.0,0.0) rot(0.0,0.0,90.0) scale(2.0,1.0,1.0) material(./data/textures/white.png,color(1.0,1.0,1.0),1) physics(dynamic,5.0,2.0,1.0,2,false) enemy_movement(-75.0)];
+entity[name(plane) pos(-5.0,0.0,0.0) rot(0.0,0.0,90.0) scale(2.0,1.0,1.0) material(./data/textures/white.png,color(1.0,1.0,1.0),1) physics(dynamic,5.0,2.0,1.0,2,false) enemy_movement(-75.0)];
+// Player
+entity[name(Player) pos(0.0,0.0,0.0) rot(0.0,0.0,0.0) scale(1.0,1.0,1.0) material(./data/textures/player.png,color(0.0,1000.0,1000.0),1) physics(dynamic,1.0,0.2,1.0,0,false) player_movement(15.0)];
+"#;
+
+    #[test]
+    fn legacy_scene_loads(){
+        let registry = ComponentRegistry::with_engine_components().unwrap();
+        let description = SceneDescription::parse(LEGACY_SCENE, Some(&registry)).unwrap();
+        assert_eq!(description.version, 0);
+        let names: Vec<Option<&str>> = description.entities.iter().map(|e| e.name.as_deref()).collect();
+        assert_eq!(names, vec!(Some("WallEnemy"), Some("WallEnemy"), Some("plane"), Some("plane"), Some("Player")));
+        assert_eq!(description.entities[0].rotation, cgmath::Vector3::<f32> { x: 0.0, y: 0.0, z: 45.0 });
+
+        let material = MaterialDescription::from_node(description.entities[4].component("material").unwrap(), description.version).unwrap();
+        assert_eq!(material.texture, "./data/textures/player.png");
+        assert_eq!(material.color, cgmath::Vector3::<f32> { x: 0.0, y: 1000.0, z: 1000.0 });
+        assert_eq!(material.shininess, 1.0);
+        let physics = PhysicsDescription::from_node(description.entities[0].component("physics").unwrap(), &description.collision_layers).unwrap();
+        assert_eq!((physics.body_type, physics.mass, physics.width, physics.height), (b2::BodyType::Dynamic, 5.0, 2.0, 1.0));
+        assert_eq!((physics.layer, physics.allow_sleep), (layers::GROUND, false));
+
+        // Headless, so the materials are left out
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        SceneLoader::spawn(&description, &registry, &mut entity_manager, &mut physics, None, None).unwrap();
+        assert_eq!(entity_manager.entities.len(), 5);
+        assert_eq!(entity_manager.query::<&PhysicsComponent>().iter().count(), 5);
+        let player = entity_manager.find_by_name("Player").unwrap().handle;
+        assert!(entity_manager.get_component::<PlayerMovementComponent>(player).is_some());
+        assert_eq!(entity_manager.get_component::<PhysicsComponent>(player).unwrap().layer_type, layers::ENEMY);
+        assert!(entity_manager.get_component::<RenderMesh>(player).is_none());
+    }
+}
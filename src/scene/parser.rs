@@ -0,0 +1,507 @@
+use super::tokenizer::{Tokenizer, Token, TokenKind};
+use super::error::SceneError;
+
+// Untyped syntax tree for a .dbscene file. The grammar is:
+//
+// scene     := header? ( include | prefab | settings | entity )*
+// header    := 'version' NUMBER ';'
+// include   := 'include' STRING ';'?
+// prefab    := 'prefab' WORD '[' component* ']' ';'?
+// settings  := SETTINGS_BLOCK '[' component* ']' ';'?
+// entity    := 'entity' WORD? '[' component* ']' ';'?
+// component := WORD ( '(' args? ')' )? ( '{' component* '}' )?
+// args      := value ( ',' value )*
+// value     := STRING | WORD ( '(' args? ')' )?
+//
+// Turning the tree into something the engine understands (including expanding prefabs) happens in description.rs
+// When a definition fails to parse the error is recorded and parsing resumes at the next `entity`, `prefab` or `include`
+
+// Scene wide settings rather than entities, e.g. `collision[ ignore(enemy, enemy) ]` or `physics[ gravity(0.0, -20.0) ]`
+pub const SETTINGS_BLOCKS: &[&str] = &["collision", "physics"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value{
+    Number(f32),
+    Bool(bool),
+    // Bare words - enum values like `dynamic`, or unquoted paths in legacy files
+    Ident(String),
+    Str(String),
+    // Nested call such as color(1.0,1.0,1.0)
+    Call(String, Vec<Value>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentNode{
+    pub name: String,
+    pub args: Vec<Value>,
+    pub block: Vec<ComponentNode>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl ComponentNode{
+    // For components built in code, e.g. by SceneWriter. They have no location in a file
+    pub fn new(name: &str, args: Vec<Value>) -> Self{
+        Self{
+            name: name.to_string(),
+            args,
+            block: Vec::<ComponentNode>::new(),
+            line: 0,
+            column: 0,
+        }
+    }
+
+    // Same name, arguments and block settings, wherever they are in the file
+    pub fn same_as(&self, other: &ComponentNode) -> bool{
+        self.name == other.name && self.args == other.args && same_components(&self.block, &other.block)
+    }
+}
+
+// Compares component lists ignoring line and column, so a definition that only moved in the file is unchanged
+pub fn same_components(a: &[ComponentNode], b: &[ComponentNode]) -> bool{
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.same_as(b))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityNode{
+    // Set for `entity Name[...]`, the components are then overrides on top of the prefab
+    pub prefab: Option<String>,
+    pub components: Vec<ComponentNode>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefabNode{
+    pub name: String,
+    pub components: Vec<ComponentNode>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsNode{
+    // One of SETTINGS_BLOCKS
+    pub name: String,
+    pub components: Vec<ComponentNode>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeNode{
+    // As written in the file, relative to the including file
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneNode{
+    // None when the file has no version header (Legacy files)
+    pub version: Option<u32>,
+    pub includes: Vec<IncludeNode>,
+    pub prefabs: Vec<PrefabNode>,
+    pub settings: Vec<SettingsNode>,
+    pub entities: Vec<EntityNode>,
+}
+
+pub struct Parser{
+    tokens: Vec<Token>,
+    position: usize,
+    pub errors: Vec<SceneError>,
+}
+
+impl Parser{
+    pub fn new(tokens: Vec<Token>) -> Self{
+        Self{
+            tokens,
+            position: 0,
+            errors: Vec::<SceneError>::new(),
+        }
+    }
+
+    // Returns every entity that parsed cleanly, along with every error found in the file
+    pub fn parse(source: &str) -> (SceneNode, Vec<SceneError>){
+        let (tokens, mut errors) = Tokenizer::tokenize(source);
+        let mut parser = Parser::new(tokens);
+        let node = parser.parse_scene();
+        errors.append(&mut parser.errors);
+        (node, errors)
+    }
+
+    fn peek(&self) -> &Token{
+        // The tokenizer always ends with Eof, so clamp to it
+        let index = std::cmp::min(self.position, self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    fn next(&mut self) -> Token{
+        let token = self.peek().clone();
+        if self.position < self.tokens.len(){
+            self.position += 1;
+        }
+        token
+    }
+
+    fn peek_second(&self) -> &Token{
+        let index = std::cmp::min(self.position + 1, self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    // Doesn't consume the token on a mismatch, so an unexpected `entity` is still there to resume from
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token, SceneError>{
+        let token = self.peek().clone();
+        if token.kind == kind{
+            Ok(self.next())
+        }else{
+            Err(SceneError::new(token.line, token.column, format!("expected {}, found {}", what, Parser::describe(&token.kind))))
+        }
+    }
+
+    // True at `entity [`, `entity Name`, `prefab Name`, `include "path"` or a settings block, which is where we resume after an error
+    fn at_definition_start(&self) -> bool{
+        match (&self.peek().kind, &self.peek_second().kind){
+            (TokenKind::Word(w), TokenKind::LBracket) if w == "entity" || SETTINGS_BLOCKS.contains(&w.as_str()) => true,
+            (TokenKind::Word(w), TokenKind::Word(_)) if w == "entity" || w == "prefab" => true,
+            (TokenKind::Word(w), TokenKind::Str(_)) if w == "include" => true,
+            _ => false,
+        }
+    }
+
+    // Skip ahead to the next definition so one typo doesn't hide errors further down the file
+    fn synchronize(&mut self, start: usize){
+        // Always make progress, otherwise a bad token would loop forever
+        if self.position == start{
+            self.next();
+        }
+        while self.peek().kind != TokenKind::Eof && !self.at_definition_start(){
+            self.next();
+        }
+    }
+
+    fn describe(kind: &TokenKind) -> String{
+        match kind{
+            TokenKind::Word(w) => format!("'{}'", w),
+            TokenKind::Str(s) => format!("string \"{}\"", s),
+            TokenKind::LBracket => "'['".to_string(),
+            TokenKind::RBracket => "']'".to_string(),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::LBrace => "'{'".to_string(),
+            TokenKind::RBrace => "'}'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::Semicolon => "';'".to_string(),
+            TokenKind::Eof => "end of file".to_string(),
+        }
+    }
+
+    pub fn parse_scene(&mut self) -> SceneNode{
+        let mut version = None;
+        if self.peek().kind == TokenKind::Word("version".to_string()){
+            match self.parse_header(){
+                Ok(v) => version = Some(v),
+                Err(e) => {
+                    self.errors.push(e);
+                    while self.peek().kind != TokenKind::Eof && !self.at_definition_start(){
+                        self.next();
+                    }
+                }
+            }
+        }
+
+        let mut includes = Vec::<IncludeNode>::new();
+        let mut prefabs = Vec::<PrefabNode>::new();
+        let mut settings = Vec::<SettingsNode>::new();
+        let mut entities = Vec::<EntityNode>::new();
+        while self.peek().kind != TokenKind::Eof{
+            let start = self.position;
+            let result = match &self.peek().kind{
+                TokenKind::Word(w) if w == "include" => self.parse_include().map(|v| includes.push(v)),
+                TokenKind::Word(w) if w == "prefab" => self.parse_prefab().map(|v| prefabs.push(v)),
+                TokenKind::Word(w) if SETTINGS_BLOCKS.contains(&w.as_str()) => self.parse_settings().map(|v| settings.push(v)),
+                _ => self.parse_entity().map(|v| entities.push(v)),
+            };
+            if let Err(e) = result{
+                self.errors.push(e);
+                self.synchronize(start);
+            }
+        }
+
+        SceneNode { version, includes, prefabs, settings, entities }
+    }
+
+    fn parse_include(&mut self) -> Result<IncludeNode, SceneError>{
+        let token = self.next();
+        let path = self.next();
+        let path = match path.kind{
+            TokenKind::Str(s) => s,
+            other => return Err(SceneError::new(path.line, path.column, format!("expected a quoted path after 'include', found {}", Parser::describe(&other)))
+                .hint("includes look like `include \"./data/scene/prefabs.dbscene\";`")),
+        };
+        if self.peek().kind == TokenKind::Semicolon{
+            self.next();
+        }
+
+        Ok(IncludeNode { path, line: token.line, column: token.column })
+    }
+
+    fn parse_prefab(&mut self) -> Result<PrefabNode, SceneError>{
+        let token = self.next();
+        let name = self.next();
+        let name = match name.kind{
+            TokenKind::Word(w) => w,
+            other => return Err(SceneError::new(name.line, name.column, format!("expected a prefab name, found {}", Parser::describe(&other)))
+                .hint("prefabs look like `prefab Name[ ... ];`")),
+        };
+        self.expect(TokenKind::LBracket, &format!("'[' after 'prefab {}'", name))?;
+        let components = self.parse_components(TokenKind::RBracket, &token)?;
+        self.expect(TokenKind::RBracket, "']' to close the prefab")?;
+        if self.peek().kind == TokenKind::Semicolon{
+            self.next();
+        }
+
+        Ok(PrefabNode { name, components, line: token.line, column: token.column })
+    }
+
+    fn parse_settings(&mut self) -> Result<SettingsNode, SceneError>{
+        let token = self.next();
+        let name = match &token.kind{
+            TokenKind::Word(w) => w.clone(),
+            other => return Err(SceneError::new(token.line, token.column, format!("expected a settings block, found {}", Parser::describe(other)))),
+        };
+        self.expect(TokenKind::LBracket, &format!("'[' after '{}'", name))?;
+        let components = self.parse_components(TokenKind::RBracket, &token)?;
+        self.expect(TokenKind::RBracket, &format!("']' to close '{}'", name))?;
+        if self.peek().kind == TokenKind::Semicolon{
+            self.next();
+        }
+
+        Ok(SettingsNode { name, components, line: token.line, column: token.column })
+    }
+
+    fn parse_header(&mut self) -> Result<u32, SceneError>{
+        self.next();
+        let token = self.next();
+        let version = match &token.kind{
+            TokenKind::Word(w) => match w.parse::<u32>(){
+                Ok(v) => v,
+                Err(_) => return Err(SceneError::new(token.line, token.column, format!("expected a version number, found '{}'", w))
+                    .hint("the header looks like `version 1;`")),
+            },
+            other => return Err(SceneError::new(token.line, token.column, format!("expected a version number, found {}", Parser::describe(other)))
+                .hint("the header looks like `version 1;`")),
+        };
+        self.expect(TokenKind::Semicolon, "';' after the version header")?;
+        Ok(version)
+    }
+
+    fn parse_entity(&mut self) -> Result<EntityNode, SceneError>{
+        let token = self.next();
+        match &token.kind{
+            TokenKind::Word(w) if w == "entity" => {},
+            other => return Err(SceneError::new(token.line, token.column, format!("expected 'entity', found {}", Parser::describe(other)))
+                .hint("every definition starts with `entity[`, `entity Prefab[`, `prefab Name[` or `include`")),
+        }
+        let prefab = match &self.peek().kind{
+            TokenKind::Word(w) => {
+                let w = w.clone();
+                self.next();
+                Some(w)
+            },
+            _ => None,
+        };
+        self.expect(TokenKind::LBracket, "'[' after 'entity'")?;
+        let components = self.parse_components(TokenKind::RBracket, &token)?;
+        self.expect(TokenKind::RBracket, "']' to close the entity")?;
+        // Trailing semicolons are optional
+        if self.peek().kind == TokenKind::Semicolon{
+            self.next();
+        }
+
+        Ok(EntityNode { prefab, components, line: token.line, column: token.column })
+    }
+
+    // Parse components until we hit the closing token (which is left for the caller)
+    fn parse_components(&mut self, close: TokenKind, opened_by: &Token) -> Result<Vec<ComponentNode>, SceneError>{
+        let mut components = Vec::<ComponentNode>::new();
+        while self.peek().kind != close{
+            // Running into the next definition or the end of the file means the closing bracket is missing
+            if self.peek().kind == TokenKind::Eof || self.at_definition_start(){
+                let token = self.peek();
+                let closing = if close == TokenKind::RBracket { "']'" } else { "'}'" };
+                return Err(SceneError::new(token.line, token.column, format!("missing {} to close the block opened at {}:{}", closing, opened_by.line, opened_by.column))
+                    .hint(&format!("add {} before this point", closing)));
+            }
+            components.push(self.parse_component()?);
+        }
+        Ok(components)
+    }
+
+    fn parse_component(&mut self) -> Result<ComponentNode, SceneError>{
+        let token = self.next();
+        let name = match &token.kind{
+            TokenKind::Word(w) => w.clone(),
+            other => return Err(SceneError::new(token.line, token.column, format!("expected a component name, found {}", Parser::describe(other)))
+                .hint("components look like `name(arguments)` or `name{ ... }`")),
+        };
+
+        let mut args = Vec::<Value>::new();
+        if self.peek().kind == TokenKind::LParen{
+            self.next();
+            args = self.parse_args().map_err(|e| e.component(&name))?;
+            self.expect(TokenKind::RParen, &format!("')' to close '{}'", name)).map_err(|e| e.component(&name))?;
+        }
+
+        let mut block = Vec::<ComponentNode>::new();
+        if self.peek().kind == TokenKind::LBrace{
+            self.next();
+            block = self.parse_components(TokenKind::RBrace, &token).map_err(|e| e.component(&name))?;
+            self.expect(TokenKind::RBrace, &format!("'}}' to close '{}'", name)).map_err(|e| e.component(&name))?;
+        }
+
+        Ok(ComponentNode { name, args, block, line: token.line, column: token.column })
+    }
+
+    fn parse_args(&mut self) -> Result<Vec<Value>, SceneError>{
+        let mut args = Vec::<Value>::new();
+        if self.peek().kind == TokenKind::RParen{
+            return Ok(args);
+        }
+        loop{
+            args.push(self.parse_value()?);
+            if self.peek().kind == TokenKind::Comma{
+                self.next();
+            }else{
+                return Ok(args);
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, SceneError>{
+        if self.at_definition_start(){
+            let token = self.peek();
+            return Err(SceneError::new(token.line, token.column, "expected a value, found the start of the next definition".to_string())
+                .hint("check for a missing ')' or ']' on the previous line"));
+        }
+        let token = self.next();
+        match token.kind{
+            TokenKind::Str(s) => Ok(Value::Str(s)),
+            TokenKind::Word(w) => {
+                if self.peek().kind == TokenKind::LParen{
+                    self.next();
+                    let args = self.parse_args()?;
+                    self.expect(TokenKind::RParen, &format!("')' to close '{}'", w))?;
+                    return Ok(Value::Call(w, args));
+                }
+                Ok(Parser::classify_word(w))
+            },
+            other => Err(SceneError::new(token.line, token.column, format!("expected a value, found {}", Parser::describe(&other)))
+                .hint("values are numbers, true/false, words or \"quoted strings\"")),
+        }
+    }
+
+    fn classify_word(word: String) -> Value{
+        match word.as_str(){
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match word.parse::<f32>(){
+                Ok(v) => Value::Number(v),
+                Err(_) => Value::Ident(word),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn parse_ok(source: &str) -> SceneNode{
+        let (node, errors) = Parser::parse(source);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        node
+    }
+
+    #[test]
+    fn header_and_entities(){
+        let node = parse_ok("version 2;\nentity[ pos(1.0, 2.0, 3.0) ];\nentity Crate[]");
+        assert_eq!(node.version, Some(2));
+        assert_eq!(node.entities.len(), 2);
+        assert_eq!(node.entities[0].prefab, None);
+        assert_eq!(node.entities[0].components[0].name, "pos");
+        assert_eq!(node.entities[0].components[0].args, vec!(Value::Number(1.0), Value::Number(2.0), Value::Number(3.0)));
+        assert_eq!((node.entities[0].components[0].line, node.entities[0].components[0].column), (2, 9));
+        assert_eq!(node.entities[1].prefab, Some("Crate".to_string()));
+        assert_eq!((node.entities[1].line, node.entities[1].column), (3, 1));
+    }
+
+    #[test]
+    fn legacy_file_has_no_version(){
+        let node = parse_ok("entity[ model(./data/models/cube.obj) ]");
+        assert_eq!(node.version, None);
+        assert_eq!(node.entities[0].components[0].args, vec!(Value::Ident("./data/models/cube.obj".to_string())));
+    }
+
+    #[test]
+    fn values(){
+        let node = parse_ok(r#"entity[ c(-1.5, true, false, dynamic, "a b", color(1, 0.5, 0)) e() ]"#);
+        let components = &node.entities[0].components;
+        assert_eq!(components[0].args, vec!(
+            Value::Number(-1.5),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Ident("dynamic".to_string()),
+            Value::Str("a b".to_string()),
+            Value::Call("color".to_string(), vec!(Value::Number(1.0), Value::Number(0.5), Value::Number(0.0))),
+        ));
+        assert!(components[1].args.is_empty());
+    }
+
+    #[test]
+    fn nested_blocks(){
+        let node = parse_ok("entity[ joints{ revolute(\"Anchor\"){ limit(-30, 30) } rope } ]");
+        let joints = &node.entities[0].components[0];
+        assert_eq!(joints.block.len(), 2);
+        assert_eq!(joints.block[0].args, vec!(Value::Str("Anchor".to_string())));
+        assert_eq!(joints.block[0].block[0].name, "limit");
+        assert_eq!(joints.block[1].name, "rope");
+    }
+
+    #[test]
+    fn includes_prefabs_and_settings(){
+        let node = parse_ok("include \"./prefabs.dbscene\";\nprefab Crate[ tag(\"crate\") ];\nphysics[ gravity(0.0, -20.0) ]");
+        assert_eq!(node.includes[0].path, "./prefabs.dbscene");
+        assert_eq!(node.prefabs[0].name, "Crate");
+        assert_eq!(node.prefabs[0].components[0].name, "tag");
+        assert_eq!(node.settings[0].name, "physics");
+        assert_eq!((node.settings[0].line, node.settings[0].column), (3, 1));
+    }
+
+    #[test]
+    fn errors_resume_at_next_definition(){
+        let (node, errors) = Parser::parse("entity[ pos(1, 2 ]\nentity[ name(\"ok\") ]\nentity[ ( ]\nentity[]");
+        assert_eq!(node.entities.len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert_eq!((errors[0].line, errors[0].column), (1, 18));
+        assert_eq!(errors[0].component, Some("pos".to_string()));
+        assert_eq!((errors[1].line, errors[1].column), (3, 9));
+    }
+
+    #[test]
+    fn missing_bracket_points_at_the_next_definition(){
+        let (node, errors) = Parser::parse("entity[ pos(1, 2, 3)\nentity[]");
+        assert_eq!(node.entities.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (2, 1));
+        assert!(errors[0].message.contains("opened at 1:1"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn bad_version(){
+        let (node, errors) = Parser::parse("version one;\nentity[]");
+        assert_eq!(node.version, None);
+        assert_eq!((errors[0].line, errors[0].column), (1, 9));
+        assert_eq!(node.entities.len(), 1);
+    }
+}
@@ -0,0 +1,109 @@
+use wrapped2d::b2;
+use crate::PhysicsConfig;
+use super::parser::{ComponentNode, Value};
+use super::error::SceneError;
+use super::args::*;
+
+// One setting of a physics[...] block:
+// gravity(x, y), iterations(velocity, position), sub_steps(n), pixels_per_meter(n), sleep(bool),
+// sleep_tolerance(meters per second, degrees per second, seconds), deterministic(bool) and warm_starting(bool)
+pub fn physics_setting(config: &mut PhysicsConfig, node: &ComponentNode) -> Result<(), SceneError>{
+    let count = |n: usize, example: &str| -> Result<(), SceneError>{
+        if node.args.len() != n{
+            return Err(error_at(node, format!("takes {} argument(s), found {}", n, node.args.len()))
+                .hint(&format!("e.g. {}", example)));
+        }
+        Ok(())
+    };
+    let whole = |index: usize| -> Result<u32, SceneError>{
+        let v = number_arg(node, index)?;
+        if v.fract() != 0.0 || v < 1.0{
+            return Err(error_at(node, format!("argument {} must be a whole number of at least 1, found {}", index + 1, v)));
+        }
+        Ok(v as u32)
+    };
+    match node.name.as_str(){
+        "gravity" => {
+            count(2, "gravity(0.0, -10.0)")?;
+            config.gravity = b2::Vec2 { x: number_arg(node, 0)?, y: number_arg(node, 1)? };
+        },
+        "iterations" => {
+            count(2, "iterations(8, 3) for 8 velocity and 3 position iterations")?;
+            config.velocity_iterations = whole(0)? as i32;
+            config.position_iterations = whole(1)? as i32;
+        },
+        "sub_steps" => {
+            count(1, "sub_steps(2)")?;
+            config.sub_steps = whole(0)?;
+        },
+        "pixels_per_meter" => {
+            count(1, "pixels_per_meter(32.0)")?;
+            let v = number_arg(node, 0)?;
+            if v <= 0.0{
+                return Err(error_at(node, format!("must be above 0, found {}", v)));
+            }
+            config.pixels_per_meter = v;
+        },
+        "sleep" => {
+            count(1, "sleep(false)")?;
+            config.allow_sleep = bool_arg(node, 0)?;
+        },
+        "sleep_tolerance" => {
+            count(3, "sleep_tolerance(0.01, 2.0, 0.5) for meters per second, degrees per second and seconds still")?;
+            let (linear, angular, time) = (number_arg(node, 0)?, number_arg(node, 1)?, number_arg(node, 2)?);
+            if linear < 0.0 || angular < 0.0 || time < 0.0{
+                return Err(error_at(node, "tolerances and time can't be negative".to_string()));
+            }
+            config.linear_sleep_tolerance = linear;
+            config.angular_sleep_tolerance = angular.to_radians();
+            config.time_to_sleep = time;
+        },
+        "deterministic" => {
+            count(1, "deterministic(true)")?;
+            config.deterministic = bool_arg(node, 0)?;
+        },
+        "warm_starting" => {
+            count(1, "warm_starting(false)")?;
+            config.warm_starting = bool_arg(node, 0)?;
+        },
+        other => return Err(error_at(node, format!("unknown physics setting '{}'", other))
+            .hint("known settings are gravity, iterations, sub_steps, pixels_per_meter, sleep, sleep_tolerance, deterministic and warm_starting")),
+    }
+    Ok(())
+}
+
+// The physics[...] settings that turn the engine's defaults into `config`. Empty if they're the same
+pub fn physics_nodes(config: &PhysicsConfig) -> Vec<ComponentNode>{
+    let defaults = PhysicsConfig::new();
+    let mut nodes = Vec::<ComponentNode>::new();
+    if config.gravity.x != defaults.gravity.x || config.gravity.y != defaults.gravity.y{
+        nodes.push(ComponentNode::new("gravity", vec!(Value::Number(config.gravity.x), Value::Number(config.gravity.y))));
+    }
+    if config.velocity_iterations != defaults.velocity_iterations || config.position_iterations != defaults.position_iterations{
+        nodes.push(ComponentNode::new("iterations", vec!(Value::Number(config.velocity_iterations as f32), Value::Number(config.position_iterations as f32))));
+    }
+    if config.sub_steps != defaults.sub_steps{
+        nodes.push(ComponentNode::new("sub_steps", vec!(Value::Number(config.sub_steps as f32))));
+    }
+    if config.pixels_per_meter != defaults.pixels_per_meter{
+        nodes.push(ComponentNode::new("pixels_per_meter", vec!(Value::Number(config.pixels_per_meter))));
+    }
+    if config.allow_sleep != defaults.allow_sleep{
+        nodes.push(ComponentNode::new("sleep", vec!(Value::Bool(config.allow_sleep))));
+    }
+    if config.linear_sleep_tolerance != defaults.linear_sleep_tolerance || config.angular_sleep_tolerance != defaults.angular_sleep_tolerance
+        || config.time_to_sleep != defaults.time_to_sleep{
+        nodes.push(ComponentNode::new("sleep_tolerance", vec!(
+            Value::Number(config.linear_sleep_tolerance),
+            Value::Number(config.angular_sleep_tolerance.to_degrees()),
+            Value::Number(config.time_to_sleep),
+        )));
+    }
+    if config.deterministic != defaults.deterministic{
+        nodes.push(ComponentNode::new("deterministic", vec!(Value::Bool(config.deterministic))));
+    }
+    if config.warm_starting != defaults.warm_starting{
+        nodes.push(ComponentNode::new("warm_starting", vec!(Value::Bool(config.warm_starting))));
+    }
+    nodes
+}
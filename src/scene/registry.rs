@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use crate::{ComponentBase, Renderer, Physics, Arc};
+use super::parser::ComponentNode;
+use super::error::{SceneError, SceneErrors};
+
+// Everything a factory may need while an entity is being spawned from a scene file
+pub struct SceneContext<'a>{
+    // None when loading headless, components registered with register_render_component are skipped then
+    pub renderer: Option<&'a Renderer>,
+    pub physics: &'a mut Physics,
+    // The entity's transform, already read from pos/rot/scale
+    pub position: cgmath::Vector3::<f32>,
+    pub rotation: cgmath::Quaternion::<f32>,
+    pub scale: cgmath::Vector3::<f32>,
+    // Format version of the file being loaded
+    pub version: u32,
+    // Bind groups the component needs at draw time (e.g. material uniforms). Pushed in pipeline order
+    pub uniforms: Vec<Arc<wgpu::BindGroup>>,
+}
+
+// Turns the arguments of a scene component into a component. Every problem with the arguments is returned, not just the first
+pub type ComponentFactory = Box<dyn Fn(&ComponentNode, &mut SceneContext) -> Result<Box<dyn ComponentBase>, SceneErrors>>;
+// Turns a live component back into scene arguments. Returning None leaves it out of the saved file.
+// The physics is there for what the component only refers to, like collision layer names
+pub type ComponentSaver = Box<dyn Fn(&dyn ComponentBase, &Physics) -> Option<ComponentNode>>;
+
+struct Registration{
+    // Type name, used in error messages
+    name: String,
+    keyword: Option<String>,
+    factory: Option<ComponentFactory>,
+    saver: Option<ComponentSaver>,
+    // Only built when there's a renderer
+    render_only: bool,
+}
+
+// Maps scene keywords and component ids to the code that builds them, so games can add their own
+// scene components without touching the loader
+pub struct ComponentRegistry{
+    registrations: HashMap<u32, Registration>,
+    keywords: HashMap<String, u32>,
+}
+
+impl ComponentRegistry{
+    pub fn new() -> Self{
+        Self{
+            registrations: HashMap::<u32, Registration>::new(),
+            keywords: HashMap::<String, u32>::new(),
+        }
+    }
+
+    // Registry with every component the engine ships with
+    pub fn with_engine_components() -> Result<Self, String>{
+        let mut registry = ComponentRegistry::new();
+        crate::RenderMesh::register(&mut registry)?;
+        crate::Translation::register(&mut registry)?;
+        crate::Rotation::register(&mut registry)?;
+        crate::NonUniformScale::register(&mut registry)?;
+        crate::Transform::register(&mut registry)?;
+        crate::MovementComponent::register(&mut registry)?;
+        crate::PlayerMovementComponent::register(&mut registry)?;
+        crate::PhysicsComponent::register(&mut registry)?;
+        crate::JointComponent::register(&mut registry)?;
+        crate::TransformBuffer::register(&mut registry)?;
+        crate::Parent::register(&mut registry)?;
+        crate::Children::register(&mut registry)?;
+        Ok(registry)
+    }
+
+    // Claim an id for a component that can't be written in scene files. Still checked for duplicates
+    pub fn reserve(&mut self, name: &str, id: u32) -> Result<(), String>{
+        self.insert(name, id, None, None, None, false)
+    }
+
+    pub fn register<F>(&mut self, name: &str, id: u32, keyword: &str, factory: F) -> Result<(), String>
+    where F: Fn(&ComponentNode, &mut SceneContext) -> Result<Box<dyn ComponentBase>, SceneErrors> + 'static{
+        self.insert(name, id, Some(keyword), Some(Box::new(factory)), None, false)
+    }
+
+    // Same as register, but the component is also written out by SceneWriter
+    pub fn register_with_saver<F, S>(&mut self, name: &str, id: u32, keyword: &str, factory: F, saver: S) -> Result<(), String>
+    where F: Fn(&ComponentNode, &mut SceneContext) -> Result<Box<dyn ComponentBase>, SceneErrors> + 'static,
+          S: Fn(&dyn ComponentBase, &Physics) -> Option<ComponentNode> + 'static{
+        self.insert(name, id, Some(keyword), Some(Box::new(factory)), Some(Box::new(saver)), false)
+    }
+
+    // Same as register_with_saver, for components that only exist to be drawn (Meshes, materials...).
+    // They're left out when a scene is loaded without a renderer, and the factory can expect context.renderer to be set
+    pub fn register_render_component<F, S>(&mut self, name: &str, id: u32, keyword: &str, factory: F, saver: S) -> Result<(), String>
+    where F: Fn(&ComponentNode, &mut SceneContext) -> Result<Box<dyn ComponentBase>, SceneErrors> + 'static,
+          S: Fn(&dyn ComponentBase, &Physics) -> Option<ComponentNode> + 'static{
+        self.insert(name, id, Some(keyword), Some(Box::new(factory)), Some(Box::new(saver)), true)
+    }
+
+    fn insert(&mut self, name: &str, id: u32, keyword: Option<&str>, factory: Option<ComponentFactory>, saver: Option<ComponentSaver>, render_only: bool) -> Result<(), String>{
+        if let Some(existing) = self.registrations.get(&id){
+            return Err(format!("Component id {} is used by both {} and {}", id, existing.name, name));
+        }
+        if let Some(keyword) = keyword{
+            if let Some(existing) = self.keywords.get(keyword){
+                return Err(format!("Scene keyword '{}' is used by both {} and {}", keyword, self.registrations[existing].name, name));
+            }
+            self.keywords.insert(keyword.to_string(), id);
+        }
+        log::info!("Registered component {} with id {}", name, id);
+        self.registrations.insert(id, Registration{
+            name: name.to_string(),
+            keyword: keyword.map(|k| k.to_string()),
+            factory,
+            saver,
+            render_only,
+        });
+        Ok(())
+    }
+
+    pub fn contains_keyword(&self, keyword: &str) -> bool{
+        self.keywords.contains_key(keyword)
+    }
+
+    pub fn keywords(&self) -> Vec<&String>{
+        let mut keywords: Vec<&String> = self.keywords.keys().collect();
+        keywords.sort();
+        keywords
+    }
+
+    pub fn get_keyword(&self, id: u32) -> Option<&String>{
+        self.registrations.get(&id)?.keyword.as_ref()
+    }
+
+    // Ok(None) when the component is render only and there's no renderer
+    pub fn create(&self, node: &ComponentNode, context: &mut SceneContext) -> Result<Option<Box<dyn ComponentBase>>, SceneErrors>{
+        let registration = self.keywords.get(&node.name).and_then(|id| self.registrations.get(id));
+        if registration.map_or(false, |r| r.render_only) && context.renderer.is_none(){
+            return Ok(None);
+        }
+        match registration.and_then(|r| r.factory.as_ref()){
+            Some(factory) => factory(node, context).map(|component| Some(component)),
+            None => Err(self.unknown_component(node).into()),
+        }
+    }
+
+    // For a component whose keyword isn't registered
+    pub fn unknown_component(&self, node: &ComponentNode) -> SceneError{
+        let known: Vec<String> = self.keywords().iter().map(|k| k.to_string()).collect();
+        SceneError::new(node.line, node.column, format!("unknown component '{}'", node.name))
+            .component(&node.name)
+            .hint(&format!("known components are name, pos, rot, scale, tag, {}", known.join(", ")))
+    }
+
+    // None when the component has no saver, or chose not to be saved
+    pub fn save(&self, component: &dyn ComponentBase, physics: &Physics) -> Option<ComponentNode>{
+        let saver = self.registrations.get(&component.get_id())?.saver.as_ref()?;
+        saver(component, physics)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{EntityManager, SceneLoader};
+    use crate::scene::SceneDescription;
+    use crate::scene::args::number_arg;
+    use std::any::Any;
+
+    // Stands in for a component a game adds on top of the engine's
+    struct Health{
+        amount: f32,
+    }
+
+    const HEALTH_ID: u32 = 1000;
+
+    impl ComponentBase for Health{
+        fn get_id(&self) -> u32{
+            HEALTH_ID
+        }
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+        fn into_any(self: Box<Self>) -> Box<dyn Any> {
+            self
+        }
+    }
+
+    fn register_health(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.register("Health", HEALTH_ID, "health", |node, _| {
+            Ok(Box::new(Health{ amount: number_arg(node, 0)? }))
+        })
+    }
+
+    #[test]
+    fn duplicate_id_is_rejected(){
+        let mut registry = ComponentRegistry::with_engine_components().unwrap();
+        let error = registry.register("Health", crate::component::movement_component::ID, "health", |node, _| {
+            Ok(Box::new(Health{ amount: number_arg(node, 0)? }))
+        }).unwrap_err();
+        assert_eq!(error, "Component id 5 is used by both MovementComponent and Health");
+        assert!(!registry.contains_keyword("health"));
+
+        let error = registry.reserve("Shield", crate::component::movement_component::ID).unwrap_err();
+        assert_eq!(error, "Component id 5 is used by both MovementComponent and Shield");
+    }
+
+    #[test]
+    fn duplicate_keyword_is_rejected(){
+        let mut registry = ComponentRegistry::with_engine_components().unwrap();
+        register_health(&mut registry).unwrap();
+        let error = registry.register("Armor", HEALTH_ID + 1, "health", |node, _| {
+            Ok(Box::new(Health{ amount: number_arg(node, 0)? }))
+        }).unwrap_err();
+        assert_eq!(error, "Scene keyword 'health' is used by both Health and Armor");
+        // The failed registration doesn't claim its id either
+        registry.reserve("Armor", HEALTH_ID + 1).unwrap();
+    }
+
+    #[test]
+    fn game_component_loads_from_a_scene(){
+        let mut registry = ComponentRegistry::with_engine_components().unwrap();
+        register_health(&mut registry).unwrap();
+        let source = "version 1;\nentity[ name(\"Player\") health(75.0) enemy_movement(3.0) ];";
+        let description = SceneDescription::parse(source, Some(&registry)).unwrap();
+
+        let mut entity_manager = EntityManager::new();
+        entity_manager.register_component::<Health>();
+        let mut physics = Physics::new();
+        SceneLoader::spawn(&description, &registry, &mut entity_manager, &mut physics, None, None).unwrap();
+        let player = entity_manager.find_by_name("Player").unwrap().handle;
+        assert_eq!(entity_manager.get_component::<Health>(player).unwrap().amount, 75.0);
+        assert_eq!(entity_manager.get_component::<crate::MovementComponent>(player).unwrap().speed, 3.0);
+
+        // Without the registration the keyword is unknown to the parser
+        let registry = ComponentRegistry::with_engine_components().unwrap();
+        let errors = SceneDescription::parse(source, Some(&registry)).unwrap_err();
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(errors.errors[0].message, "unknown component 'health'");
+    }
+}
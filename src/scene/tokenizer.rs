@@ -0,0 +1,278 @@
+use super::error::SceneError;
+
+// Splits a .dbscene source into tokens. Comments (// and /* */) are allowed anywhere and are skipped here,
+// so the parser never has to think about them.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind{
+    // Anything that isn't punctuation or a quoted string - identifiers, numbers, bools and legacy bare paths
+    Word(String),
+    // "Quoted strings", with \" \\ \n and \t escapes
+    Str(String),
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Semicolon,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token{
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub struct Tokenizer<'a>{
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    // Problems are collected rather than returned so one bad string doesn't hide the rest of the file
+    pub errors: Vec<SceneError>,
+}
+
+impl<'a> Tokenizer<'a>{
+    pub fn new(source: &'a str) -> Self{
+        Self{
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+            errors: Vec::<SceneError>::new(),
+        }
+    }
+
+    // Tokenize the whole source. The last token is always Eof
+    pub fn tokenize(source: &str) -> (Vec<Token>, Vec<SceneError>){
+        let mut tokenizer = Tokenizer::new(source);
+        let mut tokens = Vec::<Token>::new();
+        loop{
+            let token = tokenizer.next_token();
+            let is_eof = token.kind == TokenKind::Eof;
+            tokens.push(token);
+            if is_eof{
+                break;
+            }
+        }
+        (tokens, tokenizer.errors)
+    }
+
+    fn bump(&mut self) -> Option<char>{
+        let c = self.chars.next()?;
+        if c == '\n'{
+            self.line += 1;
+            self.column = 1;
+        }else{
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn peek(&mut self) -> Option<char>{
+        self.chars.peek().copied()
+    }
+
+    // Peek one char past the current one (Used to spot the start of comments)
+    fn peek_second(&self) -> Option<char>{
+        let mut chars = self.chars.clone();
+        chars.next();
+        chars.next()
+    }
+
+    fn skip_whitespace_and_comments(&mut self){
+        loop{
+            match (self.peek(), self.peek_second()){
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                },
+                (Some('/'), Some('/')) => {
+                    while let Some(c) = self.peek(){
+                        if c == '\n'{
+                            break;
+                        }
+                        self.bump();
+                    }
+                },
+                (Some('/'), Some('*')) => {
+                    let (line, column) = (self.line, self.column);
+                    self.bump();
+                    self.bump();
+                    let mut closed = false;
+                    while let Some(c) = self.bump(){
+                        if c == '*' && self.peek() == Some('/'){
+                            self.bump();
+                            closed = true;
+                            break;
+                        }
+                    }
+                    if !closed{
+                        self.errors.push(SceneError::new(line, column, "unterminated block comment".to_string())
+                            .hint("close the comment with */"));
+                    }
+                },
+                _ => return,
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Token{
+        self.skip_whitespace_and_comments();
+
+        let line = self.line;
+        let column = self.column;
+        let c = match self.peek(){
+            Some(c) => c,
+            None => return Token { kind: TokenKind::Eof, line, column },
+        };
+
+        let kind = match c{
+            '[' => { self.bump(); TokenKind::LBracket },
+            ']' => { self.bump(); TokenKind::RBracket },
+            '(' => { self.bump(); TokenKind::LParen },
+            ')' => { self.bump(); TokenKind::RParen },
+            '{' => { self.bump(); TokenKind::LBrace },
+            '}' => { self.bump(); TokenKind::RBrace },
+            ',' => { self.bump(); TokenKind::Comma },
+            ';' => { self.bump(); TokenKind::Semicolon },
+            '"' => {
+                self.bump();
+                let mut value = String::new();
+                loop{
+                    match self.bump(){
+                        Some('"') => break,
+                        Some('\\') => {
+                            match self.bump(){
+                                Some('n') => value.push('\n'),
+                                Some('t') => value.push('\t'),
+                                Some('"') => value.push('"'),
+                                Some('\\') => value.push('\\'),
+                                Some(other) => {
+                                    self.errors.push(SceneError::new(self.line, self.column - 2, format!("unknown escape sequence '\\{}'", other))
+                                        .hint("supported escapes are \\\" \\\\ \\n and \\t"));
+                                    value.push(other);
+                                },
+                                None => {
+                                    self.errors.push(SceneError::new(line, column, "unterminated string".to_string())
+                                        .hint("add a closing \" before the end of the line"));
+                                    break;
+                                },
+                            }
+                        },
+                        Some('\n') | None => {
+                            self.errors.push(SceneError::new(line, column, "unterminated string".to_string())
+                                .hint("add a closing \" before the end of the line"));
+                            break;
+                        },
+                        Some(other) => value.push(other),
+                    }
+                }
+                TokenKind::Str(value)
+            },
+            _ => {
+                let mut value = String::new();
+                while let Some(c) = self.peek(){
+                    if c.is_whitespace() || Tokenizer::is_punctuation(c){
+                        break;
+                    }
+                    // A comment can start right after a word, e.g. pos(1,2,3)// note
+                    if c == '/' && (self.peek_second() == Some('/') || self.peek_second() == Some('*')){
+                        break;
+                    }
+                    value.push(c);
+                    self.bump();
+                }
+                TokenKind::Word(value)
+            }
+        };
+
+        Token { kind, line, column }
+    }
+
+    fn is_punctuation(c: char) -> bool{
+        match c{
+            '[' | ']' | '(' | ')' | '{' | '}' | ',' | ';' | '"' => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind>{
+        let (tokens, errors) = Tokenizer::tokenize(source);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        tokens.into_iter().map(|token| token.kind).collect()
+    }
+
+    fn word(w: &str) -> TokenKind{
+        TokenKind::Word(w.to_string())
+    }
+
+    #[test]
+    fn punctuation_and_words(){
+        assert_eq!(kinds("entity[pos(1.0,-2,3e2)];"), vec!(
+            word("entity"), TokenKind::LBracket, word("pos"), TokenKind::LParen,
+            word("1.0"), TokenKind::Comma, word("-2"), TokenKind::Comma, word("3e2"),
+            TokenKind::RParen, TokenKind::RBracket, TokenKind::Semicolon, TokenKind::Eof,
+        ));
+        assert_eq!(kinds("joints{ }"), vec!(word("joints"), TokenKind::LBrace, TokenKind::RBrace, TokenKind::Eof));
+    }
+
+    #[test]
+    fn legacy_paths_are_words(){
+        assert_eq!(kinds("./data/models/cube.obj"), vec!(word("./data/models/cube.obj"), TokenKind::Eof));
+    }
+
+    #[test]
+    fn strings_and_escapes(){
+        assert_eq!(kinds(r#""a \"b\" \\ c\n\t""#), vec!(TokenKind::Str("a \"b\" \\ c\n\t".to_string()), TokenKind::Eof));
+        assert_eq!(kinds(r#""with // no comment""#), vec!(TokenKind::Str("with // no comment".to_string()), TokenKind::Eof));
+    }
+
+    #[test]
+    fn comments_are_skipped(){
+        assert_eq!(kinds("// line\nentity /* block\nover lines */ [ ]"), vec!(word("entity"), TokenKind::LBracket, TokenKind::RBracket, TokenKind::Eof));
+        // Right after a word, without a space
+        assert_eq!(kinds("pos(1)// note"), vec!(word("pos"), TokenKind::LParen, word("1"), TokenKind::RParen, TokenKind::Eof));
+        assert_eq!(kinds("a/b"), vec!(word("a/b"), TokenKind::Eof));
+    }
+
+    #[test]
+    fn positions_are_one_based(){
+        let (tokens, _) = Tokenizer::tokenize("entity[\n  pos(1)\n]");
+        let positions: Vec<(usize, usize)> = tokens.iter().map(|token| (token.line, token.column)).collect();
+        assert_eq!(positions, vec!((1, 1), (1, 7), (2, 3), (2, 6), (2, 7), (2, 8), (3, 1), (3, 2)));
+    }
+
+    #[test]
+    fn unterminated_string(){
+        let (tokens, errors) = Tokenizer::tokenize("name(\"open\nnext");
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (1, 6));
+        assert_eq!(errors[0].message, "unterminated string");
+        // Tokenizing carries on with the next line
+        assert_eq!(tokens[2].kind, TokenKind::Str("open".to_string()));
+        assert_eq!(tokens[3].kind, word("next"));
+    }
+
+    #[test]
+    fn unknown_escape(){
+        let (tokens, errors) = Tokenizer::tokenize("\"a\\qb\"");
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (1, 3));
+        assert_eq!(tokens[0].kind, TokenKind::Str("aqb".to_string()));
+    }
+
+    #[test]
+    fn unterminated_block_comment(){
+        let (tokens, errors) = Tokenizer::tokenize("entity\n  /* never closed");
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (2, 3));
+        assert_eq!(tokens.last().unwrap().kind, TokenKind::Eof);
+    }
+}
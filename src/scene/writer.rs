@@ -0,0 +1,205 @@
+use std::fmt::Write;
+use crate::{EntityManager, Entity, Transform, Physics};
+use super::parser::{ComponentNode, Value};
+use super::registry::ComponentRegistry;
+use super::description::{SceneDescription, EntityDescription, SCENE_FORMAT_VERSION};
+use super::collision_layers::collision_nodes;
+use super::physics_config::physics_nodes;
+
+// Writes a live world back out as a .dbscene file that SceneLoader can load again
+pub struct SceneWriter{
+
+}
+
+impl SceneWriter{
+    pub fn save(path: &str, registry: &ComponentRegistry, entity_manager: &EntityManager, physics: &Physics) -> std::io::Result<()>{
+        let description = SceneWriter::describe(registry, entity_manager, physics);
+        std::fs::write(path, SceneWriter::write(&description))?;
+        log::info!("Saved {} entities to {}", description.entities.len(), path);
+        Ok(())
+    }
+
+    // Read the scene components of every entity, the collision layers and the physics config into a description
+    pub fn describe(registry: &ComponentRegistry, entity_manager: &EntityManager, physics: &Physics) -> SceneDescription{
+        let mut entities = Vec::<EntityDescription>::new();
+        for entity in entity_manager.entities.iter(){
+            entities.push(SceneWriter::describe_entity(registry, entity_manager, physics, entity));
+        }
+        SceneDescription{
+            version: SCENE_FORMAT_VERSION,
+            includes: Vec::<String>::new(),
+            entities,
+            collision_layers: physics.get_collision_layers().clone(),
+            physics_config: physics.get_config().clone(),
+        }
+    }
+
+    // Components without a saver in the registry are skipped
+    pub fn describe_entity(registry: &ComponentRegistry, entity_manager: &EntityManager, physics: &Physics, entity: &Entity) -> EntityDescription{
+        let mut description = EntityDescription::new();
+        description.name = entity.name.clone();
+        // HashSet order isn't stable, sort so saving twice gives the same file
+        description.tags = entity.tags.iter().cloned().collect();
+        description.tags.sort();
+
+        if let Some(transform) = entity_manager.get_component::<Transform>(entity.handle){
+            description.position = transform.get_position();
+            let euler = cgmath::Euler::from(transform.get_rotation());
+            description.rotation = cgmath::Vector3::<f32> {
+                x: cgmath::Deg::from(euler.x).0,
+                y: cgmath::Deg::from(euler.y).0,
+                z: cgmath::Deg::from(euler.z).0,
+            };
+            description.scale = transform.get_scale();
+        }
+
+        entity_manager.visit_components(entity.handle, &mut |component| {
+            if let Some(node) = registry.save(component, physics){
+                description.components.push(node);
+            }
+        });
+
+        description
+    }
+
+    // Turn a description into .dbscene source. Floats use {:?} so they parse back to the same value
+    pub fn write(description: &SceneDescription) -> String{
+        let mut out = String::new();
+        writeln!(out, "version {};", SCENE_FORMAT_VERSION).unwrap();
+        writeln!(out).unwrap();
+        let collision = collision_nodes(&description.collision_layers);
+        if !collision.is_empty(){
+            let settings: Vec<String> = collision.iter().map(|c| SceneWriter::write_component(c)).collect();
+            writeln!(out, "collision[{}];", settings.join(" ")).unwrap();
+            writeln!(out).unwrap();
+        }
+        let physics = physics_nodes(&description.physics_config);
+        if !physics.is_empty(){
+            let settings: Vec<String> = physics.iter().map(|c| SceneWriter::write_component(c)).collect();
+            writeln!(out, "physics[{}];", settings.join(" ")).unwrap();
+            writeln!(out).unwrap();
+        }
+        for entity in description.entities.iter(){
+            writeln!(out, "{}", SceneWriter::write_entity(entity)).unwrap();
+        }
+        out
+    }
+
+    pub fn write_entity(entity: &EntityDescription) -> String{
+        let mut parts = Vec::<String>::new();
+
+        if let Some(name) = &entity.name{
+            parts.push(format!("name({})", quote(name)));
+        }
+        parts.push(format!("pos({})", vector(entity.position)));
+        parts.push(format!("rot({})", vector(entity.rotation)));
+        parts.push(format!("scale({})", vector(entity.scale)));
+        if !entity.tags.is_empty(){
+            let tags: Vec<String> = entity.tags.iter().map(|t| quote(t)).collect();
+            parts.push(format!("tag({})", tags.join(",")));
+        }
+
+        for component in entity.components.iter(){
+            parts.push(SceneWriter::write_component(component));
+        }
+
+        format!("entity[{}];", parts.join(" "))
+    }
+
+    pub fn write_component(component: &ComponentNode) -> String{
+        let mut out = component.name.clone();
+        if !component.args.is_empty() || component.block.is_empty(){
+            let args: Vec<String> = component.args.iter().map(|v| write_value(v)).collect();
+            out.push_str(&format!("({})", args.join(",")));
+        }
+        if !component.block.is_empty(){
+            let block: Vec<String> = component.block.iter().map(|c| SceneWriter::write_component(c)).collect();
+            out.push_str(&format!("{{ {} }}", block.join(" ")));
+        }
+        out
+    }
+}
+
+fn write_value(value: &Value) -> String{
+    match value{
+        Value::Number(v) => format!("{:?}", v),
+        Value::Bool(v) => format!("{}", v),
+        Value::Ident(v) => v.clone(),
+        Value::Str(v) => quote(v),
+        Value::Call(name, args) => {
+            let args: Vec<String> = args.iter().map(|v| write_value(v)).collect();
+            format!("{}({})", name, args.join(","))
+        },
+    }
+}
+
+fn vector(value: cgmath::Vector3::<f32>) -> String{
+    format!("{:?},{:?},{:?}", value.x, value.y, value.z)
+}
+
+fn quote(value: &str) -> String{
+    let mut out = String::from("\"");
+    for c in value.chars(){
+        match c{
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::scene::SceneLoader;
+
+    const SCENE: &str = r#"version 1;
+collision[ layer(bullet) ignore(bullet, bullet) ]
+physics[ gravity(0.0, -20.0) sub_steps(2) ]
+prefab Crate[ physics(dynamic, 2.0, 0.5, 0.5, bullet, true) tag("crate") ];
+entity[ name("Anchor") pos(0.0, 10.0, 0.0) physics(static, 0.0, 0.5, 0.5, ground, false) ];
+entity Crate[ name("Box") pos(1.0, 2.5, 0.0) rot(0.0, 0.0, 30.0) enemy_movement(2.0) ];
+entity[ name("Link") pos(1.0, 10.0, 0.0)
+    physics{ body(dynamic) layer(bullet) fixture(circle(0.25)){ offset(0.5, 0.0) friction(0.1) } fixture(box(1.0, 0.1)) }
+    joints{ revolute("Anchor"){ anchor(-0.5, 0.0) other_anchor(0.5, 0.0) limit(-30, 30) } } ];
+entity[ name("Player") tag("player", "hero") player_movement(15.0) physics(dynamic, 1.0, 1.0, 1.0, player) ];
+"#;
+
+    // Spawn the source headless and save it straight back
+    fn load_and_save(source: &str, registry: &ComponentRegistry) -> (SceneDescription, String){
+        let description = SceneDescription::parse(source, Some(registry)).unwrap();
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        SceneLoader::spawn(&description, registry, &mut entity_manager, &mut physics, None, None).unwrap();
+        (description, SceneWriter::write(&SceneWriter::describe(registry, &entity_manager, &physics)))
+    }
+
+    #[test]
+    fn load_save_load_round_trip(){
+        let registry = ComponentRegistry::with_engine_components().unwrap();
+        let (original, saved) = load_and_save(SCENE, &registry);
+        let (reloaded, saved_again) = load_and_save(&saved, &registry);
+        assert_eq!(saved, saved_again);
+        assert_eq!(reloaded.collision_layers, original.collision_layers);
+        assert_eq!(reloaded.physics_config, original.physics_config);
+        assert_eq!(reloaded.entities.len(), original.entities.len());
+        for (a, b) in original.entities.iter().zip(reloaded.entities.iter()){
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.position, b.position);
+            assert!((a.rotation.z - b.rotation.z).abs() < 1e-4, "{:?} {:?}", a.rotation, b.rotation);
+        }
+    }
+
+    #[test]
+    fn layers_are_saved_by_name(){
+        let registry = ComponentRegistry::with_engine_components().unwrap();
+        let (_, saved) = load_and_save(SCENE, &registry);
+        assert!(saved.contains("physics(static,0.0,0.5,0.5,ground,false)"), "{}", saved);
+        assert!(saved.contains("physics(dynamic,2.0,0.5,0.5,bullet,true)"), "{}", saved);
+        assert!(saved.contains("physics{ body(dynamic) layer(bullet) sleep(true) fixture("), "{}", saved);
+    }
+}
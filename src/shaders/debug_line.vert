@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Physics debug lines. The points are already in clip space, the camera is applied on the CPU
+layout(location=0) in vec4 position;
+layout(location=1) in vec3 color;
+
+layout(location=0) out vec3 v_color;
+
+void main() {
+    v_color = color;
+    gl_Position = position;
+}
@@ -0,0 +1,102 @@
+use std::any::TypeId;
+
+// What a system reads and writes, and which systems it has to run before or after. The scheduler puts systems
+// whose access doesn't overlap in the same stage, so declare everything the system touches:
+//
+// SystemAccess::new().read::<PhysicsComponent>().write::<Transform>().write_resource::<Physics>().main_thread().after("PhysicsSystem")
+#[derive(Debug, Clone, Default)]
+pub struct SystemAccess{
+    component_reads: Vec<(TypeId, &'static str)>,
+    component_writes: Vec<(TypeId, &'static str)>,
+    resource_reads: Vec<(TypeId, &'static str)>,
+    resource_writes: Vec<(TypeId, &'static str)>,
+    after: Vec<String>,
+    before: Vec<String>,
+    main_thread: bool,
+}
+
+fn entry<T: 'static>() -> (TypeId, &'static str){
+    (TypeId::of::<T>(), std::any::type_name::<T>())
+}
+
+// The first type one side writes and the other reads or writes
+fn overlap(writes: &[(TypeId, &'static str)], other_reads: &[(TypeId, &'static str)], other_writes: &[(TypeId, &'static str)]) -> Option<&'static str>{
+    writes.iter().find(|(id, _)| other_reads.iter().chain(other_writes.iter()).any(|(other, _)| other == id)).map(|(_, name)| *name)
+}
+
+impl SystemAccess{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    pub fn read<T: 'static>(mut self) -> Self{
+        self.component_reads.push(entry::<T>());
+        self
+    }
+
+    pub fn write<T: 'static>(mut self) -> Self{
+        self.component_writes.push(entry::<T>());
+        self
+    }
+
+    // Resources are anything outside the entity storages, e.g. Physics, Camera or the Renderer
+    pub fn read_resource<T: 'static>(mut self) -> Self{
+        self.resource_reads.push(entry::<T>());
+        self
+    }
+
+    pub fn write_resource<T: 'static>(mut self) -> Self{
+        self.resource_writes.push(entry::<T>());
+        self
+    }
+
+    // Run on the main thread instead of the rayon pool. Needed to use the renderer or a resource inserted with
+    // Resources::insert_main_thread, like Physics
+    pub fn main_thread(mut self) -> Self{
+        self.main_thread = true;
+        self
+    }
+
+    pub fn is_main_thread(&self) -> bool{
+        self.main_thread
+    }
+
+    // Run in a later stage than the named system
+    pub fn after(mut self, system: &str) -> Self{
+        self.after.push(system.to_string());
+        self
+    }
+
+    // Run in an earlier stage than the named system
+    pub fn before(mut self, system: &str) -> Self{
+        self.before.push(system.to_string());
+        self
+    }
+
+    pub fn get_after(&self) -> &Vec<String>{
+        &self.after
+    }
+
+    pub fn get_before(&self) -> &Vec<String>{
+        &self.before
+    }
+
+    // Whether the system may fetch the resource for reading. Declaring a write covers reading too
+    pub fn reads_resource<T: 'static>(&self) -> bool{
+        let id = TypeId::of::<T>();
+        self.resource_reads.iter().chain(self.resource_writes.iter()).any(|(other, _)| *other == id)
+    }
+
+    pub fn writes_resource<T: 'static>(&self) -> bool{
+        let id = TypeId::of::<T>();
+        self.resource_writes.iter().any(|(other, _)| *other == id)
+    }
+
+    // Returns the name of a type both systems use, where at least one of them writes it
+    pub fn conflicts_with(&self, other: &SystemAccess) -> Option<&'static str>{
+        overlap(&self.component_writes, &other.component_reads, &other.component_writes)
+            .or_else(|| overlap(&other.component_writes, &self.component_reads, &self.component_writes))
+            .or_else(|| overlap(&self.resource_writes, &other.resource_reads, &other.resource_writes))
+            .or_else(|| overlap(&other.resource_writes, &self.resource_reads, &self.resource_writes))
+    }
+}
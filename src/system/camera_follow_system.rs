@@ -0,0 +1,39 @@
+use crate::{SystemBase, SystemContext, SystemAccess, PlayerMovementComponent, Transform, Camera, Time};
+
+// Keeps the camera on the player. Runs every frame on the interpolated position, so the camera
+// moves as smoothly as the player is drawn
+pub struct CameraFollowSystem{
+
+}
+
+impl SystemBase for CameraFollowSystem{
+    fn get_name(&self) -> &'static str{
+        "CameraFollowSystem"
+    }
+
+    fn get_access(&self) -> SystemAccess{
+        SystemAccess::new()
+            .read::<PlayerMovementComponent>()
+            .read::<Transform>()
+            .read_resource::<Time>()
+            .write_resource::<Camera>()
+    }
+
+    fn execute(&mut self, context: &SystemContext){
+        let alpha = context.resources.get::<Time>().unwrap().alpha;
+        let mut camera = context.resources.get_mut::<Camera>().unwrap();
+
+        for (_, (_, transform)) in context.entity_manager.query::<(&PlayerMovementComponent, &Transform)>().iter(){
+            let position = transform.interpolated_position(alpha);
+            camera.move_camera(cgmath::Point3::<f32> { x: position.x, y: position.y, z: 10.0});
+        }
+    }
+}
+
+impl CameraFollowSystem{
+    pub fn new() -> Self{
+        Self{
+
+        }
+    }
+}
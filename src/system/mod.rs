@@ -3,10 +3,43 @@ pub mod movement_system;
 pub mod player_movement_system;
 pub mod systemmanager;
 pub mod physics_system;
+pub mod camera_follow_system;
+pub mod transform_composition_system;
+pub mod transform_propagation_system;
+pub mod access;
+pub mod schedule;
+pub mod resources;
+pub mod time;
 
-use crate::{Renderer, EntityManager, Rc, Physics, InputManager, Camera};
+use crate::{Renderer, EntityManager, MovementSystem, PlayerMovementSystem, PhysicsSystem, TransformCompositionSystem, TransformPropagationSystem, CameraFollowSystem};
+use access::SystemAccess;
+use resources::SystemResources;
+use systemmanager::SystemManager;
 
-pub trait SystemBase{
-    fn execute(&mut self, renderer: &Renderer, entity_manager: &mut EntityManager, input_manager: &InputManager, physics: &mut Physics, delta_time: f32, camera: &mut Camera);
+pub trait SystemBase: Send{
+    // Used by ordering constraints and in log messages, so it has to be unique
+    fn get_name(&self) -> &'static str;
+    // Everything execute reads or writes. Systems that don't conflict can run at the same time
+    fn get_access(&self) -> SystemAccess;
+    fn execute(&mut self, context: &SystemContext);
 }
 
+// What systems get to work with. Anything that isn't an entity or the renderer is fetched from resources,
+// e.g. context.resources.get_mut::<Physics>()
+pub struct SystemContext<'a>{
+    // None in headless runs, and for systems that aren't main_thread. Gameplay systems shouldn't need it
+    pub renderer: Option<&'a Renderer>,
+    pub entity_manager: &'a EntityManager,
+    pub resources: SystemResources<'a>,
+}
+
+// The systems the game runs, shared by the windowed and headless runs.
+// Anything that moves bodies runs at the fixed tick rate, the camera follows once per frame
+pub fn add_game_systems(system_manager: &mut SystemManager){
+    system_manager.add_fixed_system(Box::new(MovementSystem::new()));
+    system_manager.add_fixed_system(Box::new(PlayerMovementSystem::new()));
+    system_manager.add_fixed_system(Box::new(PhysicsSystem::new()));
+    system_manager.add_fixed_system(Box::new(TransformCompositionSystem::new()));
+    system_manager.add_fixed_system(Box::new(TransformPropagationSystem::new()));
+    system_manager.add_system(Box::new(CameraFollowSystem::new()));
+}
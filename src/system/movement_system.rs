@@ -1,4 +1,4 @@
-use crate::{SystemBase, EntityManager, MovementComponent, Transform, Rc, RefCell, Renderer, PlayerMovementComponent, InputManager, Camera, Physics, PhysicsComponent, b2};
+use crate::{SystemBase, SystemContext, SystemAccess, MovementComponent, Transform, PlayerMovementComponent, Physics, PhysicsComponent, Time, b2};
 use cgmath::InnerSpace;
 use cgmath::Rotation;
 
@@ -8,45 +8,43 @@ pub struct MovementSystem{
 }
 
 impl SystemBase for MovementSystem{
-    fn execute(&mut self, renderer: &Renderer, entity_manager: &mut EntityManager, input_manager: &InputManager, physics: &mut Physics, delta_time: f32, camera: &mut Camera){
-        for entity_ref in entity_manager.get_entities_with_types(&[PlayerMovementComponent::get_component_id()]){
-            if entity_ref.try_find_component(PlayerMovementComponent::get_component_id()).is_ok(){
+    fn get_name(&self) -> &'static str{
+        "MovementSystem"
+    }
 
-                let component = entity_ref.get_component::<PlayerMovementComponent>(PlayerMovementComponent::get_component_id()).unwrap();
-                self.move_dir = component.position;
-            }
+    fn get_access(&self) -> SystemAccess{
+        SystemAccess::new()
+            .read::<MovementComponent>()
+            .write::<PlayerMovementComponent>()
+            .read::<Transform>()
+            .write::<PhysicsComponent>()
+            .write_resource::<Physics>()
+            .main_thread()
+            .read_resource::<Time>()
+    }
+
+    fn execute(&mut self, context: &SystemContext){
+        let entity_manager = context.entity_manager;
+        let mut physics = context.resources.get_mut::<Physics>().unwrap();
+        let fixed_delta_time = context.resources.get::<Time>().unwrap().fixed_delta_time;
+        for (_, component) in entity_manager.query::<&PlayerMovementComponent>().iter(){
+            self.move_dir = component.position;
         }
         let mut points = 0;
-        for entity_ref in entity_manager.get_entities_with_types_mut(&[MovementComponent::get_component_id(), Transform::get_component_id(), PhysicsComponent::get_component_id()]){
-
-            let temp_entity = Rc::new(RefCell::new(entity_ref));
-            let temp = temp_entity.borrow();
-            let movement_component = match temp.get_component::<MovementComponent>(MovementComponent::get_component_id()){
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            let mut speed = 0.0;
-
-            speed = movement_component.speed * delta_time;
-            drop(temp);
-            
-            let mut temp = temp_entity.borrow_mut();
-            let transform = match temp.get_component_mut::<Transform>(Transform::get_component_id()){
-                Ok(transform) => transform,
-                Err(_) => continue,
-            };
+        for (_, (movement_component, transform, phys_ref)) in entity_manager.query::<(&MovementComponent, &Transform, &mut PhysicsComponent)>().iter(){
+            let speed = movement_component.speed * fixed_delta_time;
 
             let mut reset_pos = false;
-            if transform.position.y < -10.0{
+            if transform.get_position().y < -10.0{
                 reset_pos = true;
             }
 
             let mut move_dir: cgmath::Vector3::<f32> = cgmath::Vector3::<f32> { x: 0.0, y: 0.0, z: 0.0};
-            if (transform.position - self.move_dir).magnitude() > -1.0{
-                move_dir = (transform.position - self.move_dir).normalize();
+            if (transform.get_position() - self.move_dir).magnitude() > -1.0{
+                move_dir = (transform.get_position() - self.move_dir).normalize();
             }
 
-            if transform.position.y < -5.0{
+            if transform.get_position().y < -5.0{
                 move_dir.y = 0.0;
             }
             /*
@@ -58,42 +56,20 @@ impl SystemBase for MovementSystem{
                 });
                 self.x = lerp(self.x, self.x + 32.0 * delta_time, 0.25);
             }*/
-            
-            
 
-            transform.update_uniform_buffers(&renderer);
-
-            drop(temp);
-
-            let mut temp = temp_entity.borrow_mut();
-            let phys_ref = match temp.get_component_mut::<PhysicsComponent>(PhysicsComponent::get_component_id()){
-                Ok(transform) => transform,
-                Err(_) => panic!("Error - component not found!"),
-            };
             if reset_pos{
-                phys_ref.update_position(physics, b2::Vec2 { x: 0.0, y: 5.0 });
+                // Respawn at rest
+                phys_ref.set_transform(&mut physics, b2::Vec2 { x: 0.0, y: 5.0 }, 0.0);
+                phys_ref.set_velocity(&mut physics, b2::Vec2 { x: 0.0, y: 0.0 });
+                phys_ref.set_angular_velocity(&mut physics, 0.0);
                 points += 1;
             }
-            let body = physics.world.body(phys_ref.handle);
-            let lin_vel = body.linear_velocity();
-            let vel_y = lin_vel.y;
-            let vel_x = lin_vel.x;
-            let center = *body.world_center();
-            drop(body);
-            let mut body = physics.world.body_mut(phys_ref.handle);
-            body.apply_linear_impulse(&b2::Vec2{ x: (move_dir.x * speed), y: (move_dir.y * speed ) }, &center, true);
-            drop(body);
+            phys_ref.apply_impulse_to_center(&mut physics, b2::Vec2{ x: (move_dir.x * speed), y: (move_dir.y * speed ) });
             //phys_ref.set_velocity(physics, b2::Vec2{ x: (move_vec.x * speed * delta_time) + x_force, y: (move_vec.y * speed * delta_time) + gravity });
-
         }
 
-        for entity_ref in entity_manager.get_entities_with_types_mut(&[PlayerMovementComponent::get_component_id()]){
-            if entity_ref.try_find_component(PlayerMovementComponent::get_component_id()).is_ok(){
-
-                let mut component = entity_ref.get_component_mut::<PlayerMovementComponent>(PlayerMovementComponent::get_component_id()).unwrap();
-                
-                component.points += points;
-            }
+        for (_, component) in entity_manager.query::<&mut PlayerMovementComponent>().iter(){
+            component.points += points;
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::{SystemBase, EntityManager, PhysicsComponent, Transform, b2, Entity, Renderer, PhysicsFilter, InputManager, Camera, Physics};
+use crate::{SystemBase, SystemContext, SystemAccess, PhysicsComponent, Transform, b2, PhysicsFilter, Physics, Time, Collision, CollisionEvent, CollisionEvents};
 use cgmath::InnerSpace;
 use cgmath::Rotation;
 use wrapped2d::user_data::UserData;
@@ -7,33 +7,68 @@ pub struct PhysicsSystem{
 }
 
 impl SystemBase for PhysicsSystem{
-    fn execute(&mut self, renderer: &Renderer, entity_manager: &mut EntityManager, input_manager: &InputManager, physics: &mut Physics, delta_time: f32, camera: &mut Camera){
-        {
-            physics.world.step(0.016, 6, 2);
+    fn get_name(&self) -> &'static str{
+        "PhysicsSystem"
+    }
+
+    fn get_access(&self) -> SystemAccess{
+        SystemAccess::new()
+            .read::<PhysicsComponent>()
+            .write::<Transform>()
+            .write_resource::<Physics>()
+            .main_thread()
+            .write_resource::<CollisionEvents>()
+            .read_resource::<Time>()
+    }
+
+    fn execute(&mut self, context: &SystemContext){
+        let mut physics = context.resources.get_mut::<Physics>().unwrap();
+        let fixed_delta_time = context.resources.get::<Time>().unwrap().fixed_delta_time;
+        // Bodies are made before their entity, so each one is told its entity here
+        for (entity, physics_component) in context.entity_manager.query::<&PhysicsComponent>().iter(){
+            physics.set_entity(physics_component.handle, entity);
         }
+        let deterministic = physics.get_config().deterministic;
+        let contacts = physics.step(fixed_delta_time);
 
-        let mut entities = entity_manager.get_entities_with_types_mut(&[PhysicsComponent::get_component_id(), Transform::get_component_id()]);
-        for i in 0..entities.len(){
-            let mut entity_ref = &mut entities[i];
-            let body_type = entity_ref.get_component_mut::<PhysicsComponent>(PhysicsComponent::get_component_id()).unwrap().body.body_type;
-
-            if body_type != b2::BodyType::Dynamic{
-            }else{
-                
-                let phys_ref = entity_ref.get_component::<PhysicsComponent>(PhysicsComponent::get_component_id()).unwrap();
-                let body = physics.world.body(phys_ref.handle);
-                let transform = entity_ref.get_component_mut::<Transform>(Transform::get_component_id()).unwrap();
-                transform.position = cgmath::Vector3::<f32> { x: body.position().x, y: body.position().y, z: 0.0};
-                transform.rotation = cgmath::Quaternion::from(cgmath::Euler {
-                    x: cgmath::Deg(0.0),
-                    y: cgmath::Deg(0.0),
-                    z: cgmath::Rad(body.angle()).into(),
-                });
-
-                transform.update_uniform_buffers(&renderer);
+        let mut events = Vec::<CollisionEvent>::new();
+        for contact in contacts.into_iter(){
+            let (entity_a, entity_b) = match (contact.entity_a, contact.entity_b){
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+            let collision = Collision{
+                entity_a,
+                entity_b,
+                normal: contact.normal,
+                point: contact.point,
+                impulse: contact.impulse,
+                is_sensor: contact.is_sensor,
+            };
+            events.push(if contact.started { CollisionEvent::CollisionStarted(collision) } else { CollisionEvent::CollisionEnded(collision) });
+        }
+        if deterministic{
+            // Stable, so events for the same pair keep the order Box2D reported them in
+            events.sort_by_key(|event| {
+                let collision = event.collision();
+                (collision.entity_a.index, collision.entity_a.generation, collision.entity_b.index, collision.entity_b.generation)
+            });
+        }
+        context.resources.get_mut::<CollisionEvents>().unwrap().push_step(events);
 
+        for (_, (transform, physics_component)) in context.entity_manager.query::<(&mut Transform, &PhysicsComponent)>().iter(){
+            if physics_component.body_type != b2::BodyType::Dynamic{
+                continue;
             }
 
+            let body = physics.world.body(physics_component.handle);
+            transform.store_previous();
+            transform.set_position(physics.to_units(body.position()));
+            transform.set_rotation(cgmath::Quaternion::from(cgmath::Euler {
+                x: cgmath::Deg(0.0),
+                y: cgmath::Deg(0.0),
+                z: cgmath::Rad(body.angle()).into(),
+            }));
         }
     }
 }
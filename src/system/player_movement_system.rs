@@ -1,27 +1,44 @@
-use crate::{SystemBase, EntityManager, PlayerMovementComponent, Transform, PhysicsComponent, Renderer, Rc, RefCell, InputManager, Camera, Physics, b2, PhysicsFilter};
-use wrapped2d::user_data::UserData;
+use crate::{SystemBase, SystemContext, SystemAccess, PlayerMovementComponent, Transform, PhysicsComponent, InputManager, Physics, CollisionEvents, QueryFilter, EntityHandle, b2};
+use crate::physics::layers;
+
+// Speed (Meters per second) an enemy is knocked away at when the player runs into it
+const KNOCKBACK: f32 = 4.0;
+// How far below the player's feet counts as standing on something
+const GROUND_PROBE: f32 = 0.05;
+// Surfaces steeper than about 60 degrees are walls, not ground
+const MIN_GROUND_NORMAL: f32 = 0.5;
 
 pub struct PlayerMovementSystem{
 }
 
 impl SystemBase for PlayerMovementSystem{
-    fn execute(&mut self, renderer: &Renderer, entity_manager: &mut EntityManager, input_manager: &InputManager, physics: &mut Physics, delta_time: f32, camera: &mut Camera){
-        for entity_ref in entity_manager.get_entities_with_types_mut(&[PlayerMovementComponent::get_component_id(), Transform::get_component_id(), PhysicsComponent::get_component_id()]){
-            
-
-            let temp_entity = Rc::new(RefCell::new(entity_ref));
-            let mut temp = temp_entity.borrow_mut();
-            let trans_pos = match temp.get_component::<Transform>(Transform::get_component_id()){
-                Ok(transform) => transform.position,
-                Err(_) => panic!("Error - component not found!"),
-            };
-            let mut speed = 0.0;
+    fn get_name(&self) -> &'static str{
+        "PlayerMovementSystem"
+    }
+
+    fn get_access(&self) -> SystemAccess{
+        SystemAccess::new()
+            .write::<PlayerMovementComponent>()
+            .read::<Transform>()
+            .write::<PhysicsComponent>()
+            .read_resource::<InputManager>()
+            .read_resource::<CollisionEvents>()
+            .write_resource::<Physics>()
+            .main_thread()
+    }
+
+    fn execute(&mut self, context: &SystemContext){
+        let input_manager = context.resources.get::<InputManager>().unwrap();
+        let mut physics = context.resources.get_mut::<Physics>().unwrap();
+        let collision_events = context.resources.get::<CollisionEvents>().unwrap();
+        // Enemies the player ran into, with the direction to knock them and where they were hit
+        let mut hits = Vec::<(EntityHandle, b2::Vec2, b2::Vec2)>::new();
+        for (player, (movement_component, transform, phys_ref)) in context.entity_manager.query::<(&mut PlayerMovementComponent, &Transform, &mut PhysicsComponent)>().iter(){
             let mut move_vec = cgmath::Vector2::<f32> { x: 0.0, y: 0.0 };
-            let mut movement_component = temp.get_component_mut::<PlayerMovementComponent>(PlayerMovementComponent::get_component_id()).unwrap();
-            speed = movement_component.speed;
+            let speed = movement_component.speed;
             let jump = speed * 1.5;
 
-            movement_component.position = trans_pos;
+            movement_component.position = transform.get_position();
             move_vec.x = match input_manager.try_get_key_value(winit::event::VirtualKeyCode::Left){
                 Ok(v) => {
                     match v{
@@ -97,60 +114,31 @@ impl SystemBase for PlayerMovementSystem{
             };
 
 
-            drop(temp);
-            
-
-
-            let mut temp = temp_entity.borrow_mut();
-            let phys_ref = match temp.get_component_mut::<PhysicsComponent>(PhysicsComponent::get_component_id()){
-                Ok(transform) => transform,
-                Err(_) => panic!("Error - component not found!"),
-            };
-            let body = physics.world.body(phys_ref.handle);
-
-            let transform = match temp.get_component_mut::<Transform>(Transform::get_component_id()){
-                Ok(transform) => transform,
-                Err(_) => panic!("Error - component not found!"),
-            };
-
-
             let mut reset_pos = false;
-            if transform.position.y < -15.0{
+            if transform.get_position().y < -15.0{
                 reset_pos = true;
             }
-            if transform.position.y < -5.0{
+            if transform.get_position().y < -5.0{
                 move_vec.y = 0.0;
             }
             //transform.position += cgmath::Vector3::<f32> { x: move_vec.x * speed * delta_time, y: move_vec.y * speed * delta_time, z: 0.0};
-            
-            camera.move_camera(cgmath::Point3::<f32> { x: transform.position.x, y: transform.position.y, z: 10.0});
 
-            transform.update_uniform_buffers(&renderer);
 
-            drop(temp);
-            drop(body);
-
-            let mut temp = temp_entity.borrow_mut();
-            let phys_ref = match temp.get_component_mut::<PhysicsComponent>(PhysicsComponent::get_component_id()){
-                Ok(transform) => transform,
-                Err(_) => panic!("Error - component not found!"),
-            };
-
-            let mut grounded = false;
+            let grounded = PlayerMovementSystem::is_grounded(&physics, player, phys_ref.handle);
             let mut points: i32 = 0;
 
-            {   
-                // Simple layered collision detection
-                let body = physics.world.body(phys_ref.handle);
-                if physics.check_collision(0, &body){
-                    //TODO player-enemy collision
-                }
-                if physics.check_collision(2, &body){
-                    grounded = true;
+            for collision in collision_events.started_with(player){
+                if !collision.is_sensor{
+                    if let Some(other) = collision.other(player){
+                        hits.push((other, collision.normal_from(player), collision.point));
+                    }
                 }
             }
             if reset_pos{
-                phys_ref.update_position(physics, b2::Vec2 { x: 0.0, y: 2.0 });
+                // Respawn at rest
+                phys_ref.set_transform(&mut physics, b2::Vec2 { x: 0.0, y: 2.0 }, 0.0);
+                phys_ref.set_velocity(&mut physics, b2::Vec2 { x: 0.0, y: 0.0 });
+                phys_ref.set_angular_velocity(&mut physics, 0.0);
                 points -= 1;
             }
 
@@ -165,14 +153,21 @@ impl SystemBase for PlayerMovementSystem{
             body.apply_linear_impulse(&b2::Vec2{ x: ((move_vec.x * speed)) - (if move_vec.x == 0.0 { vel_x } else {vel_x}), y: (if grounded {(move_vec.y * jump) - (if move_vec.y == 0.0  { 1.0 } else {vel_y})} else {-1.0}) }, &center, true);
 
             drop(body);
-            drop(temp);
             //phys_ref.set_velocity(physics, b2::Vec2{ x: (move_vec.x * speed * delta_time) + x_force, y: (move_vec.y * speed * delta_time) + gravity });
 
-            let mut temp = temp_entity.borrow_mut();
-            let mut movement_component = temp.get_component_mut::<PlayerMovementComponent>(PlayerMovementComponent::get_component_id()).unwrap();
-
             movement_component.points += points;
         }
+
+        // The query is done with the PhysicsComponents, so the enemies can be pushed now
+        for (enemy, direction, point) in hits.into_iter(){
+            if let Some(mut enemy_physics) = context.entity_manager.get_component_mut::<PhysicsComponent>(enemy){
+                if enemy_physics.layer_type != layers::ENEMY{
+                    continue;
+                }
+                let mass = enemy_physics.get_mass(&physics);
+                enemy_physics.apply_impulse(&mut physics, b2::Vec2 { x: direction.x * KNOCKBACK * mass, y: direction.y * KNOCKBACK * mass }, point);
+            }
+        }
     }
 }
 
@@ -181,4 +176,22 @@ impl PlayerMovementSystem{
         Self{
         }
     }
+
+    // Sweep a thin box the width of the player down from their feet, and see if it lands on something flat enough to stand on
+    fn is_grounded(physics: &Physics, player: EntityHandle, body: b2::BodyHandle) -> bool{
+        let bounds = match physics.get_body_aabb(body){
+            Some(v) => v,
+            None => return false,
+        };
+        // A little narrower than the player, so walls they're pressed against aren't ground
+        let half_width = (bounds.upper.x - bounds.lower.x) * 0.5 * 0.9;
+        let x = (bounds.lower.x + bounds.upper.x) * 0.5;
+        let from = b2::Vec2 { x, y: bounds.lower.y + GROUND_PROBE };
+        let to = b2::Vec2 { x, y: bounds.lower.y - GROUND_PROBE };
+        match physics.box_cast(from, to, half_width, GROUND_PROBE * 0.5, 0.0, &QueryFilter::new().ignoring(player)){
+            // Already overlapping something at the feet counts as standing on it
+            Some(hit) => hit.normal.y >= MIN_GROUND_NORMAL || hit.fraction == 0.0,
+            None => false,
+        }
+    }
 }
\ No newline at end of file
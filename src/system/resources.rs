@@ -0,0 +1,281 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use super::access::SystemAccess;
+
+// Anything systems share that isn't a component, one value per type: Physics, InputManager, Audio, Time, game state...
+// Each resource is locked on its own, so systems that use different resources can run at the same time.
+// Resources that aren't thread safe (Physics holds Box2D's raw pointers, Audio the output stream) are inserted with
+// insert_main_thread, and only systems declared with SystemAccess::main_thread can fetch them
+pub struct Resources{
+    shared: ThreadSafeResources,
+    main_thread: HashMap<TypeId, (&'static str, RwLock<Box<dyn Any>>)>,
+}
+
+// The thread safe resources, which systems on other threads get a reference to
+pub struct ThreadSafeResources{
+    resources: HashMap<TypeId, (&'static str, RwLock<Box<dyn Any + Send + Sync>>)>,
+}
+
+// The resources one running system can fetch. Systems running on the rayon pool only see the thread safe ones
+#[derive(Clone, Copy)]
+pub struct SystemResources<'a>{
+    shared: &'a ThreadSafeResources,
+    main_thread: Option<&'a HashMap<TypeId, (&'static str, RwLock<Box<dyn Any>>)>>,
+    // The running system and the access it declared, if fetches are checked against it
+    declared: Option<(&'static str, &'a SystemAccess)>,
+}
+
+pub enum ResourceError{
+    Missing(&'static str),
+    // Locked the other way by a system (Or a reference still held) that didn't declare its access
+    Borrowed(&'static str),
+    // Not among the thread safe resources, fetched by a system that isn't declared main_thread
+    MainThreadOnly(&'static str),
+}
+
+impl std::fmt::Display for ResourceError{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result{
+        match self{
+            ResourceError::Missing(name) => write!(f, "Resource {} is missing, insert it into SystemManager::resources before running systems", name),
+            ResourceError::Borrowed(name) => write!(f, "Resource {} is already in use, declare it in get_access so systems using it don't run together", name),
+            ResourceError::MainThreadOnly(name) => write!(f, "Resource {} is missing or isn't thread safe, declare main_thread() in get_access to use main-thread resources", name),
+        }
+    }
+}
+
+// So unwrap and expect print the same message
+impl std::fmt::Debug for ResourceError{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result{
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for ResourceError{}
+
+enum ReadGuard<'a>{
+    Shared(RwLockReadGuard<'a, Box<dyn Any + Send + Sync>>),
+    MainThread(RwLockReadGuard<'a, Box<dyn Any>>),
+}
+
+enum WriteGuard<'a>{
+    Shared(RwLockWriteGuard<'a, Box<dyn Any + Send + Sync>>),
+    MainThread(RwLockWriteGuard<'a, Box<dyn Any>>),
+}
+
+pub struct ResourceRef<'a, T>{
+    guard: ReadGuard<'a>,
+    _marker: PhantomData<T>,
+}
+
+pub struct ResourceRefMut<'a, T>{
+    guard: WriteGuard<'a>,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: 'static> Deref for ResourceRef<'a, T>{
+    type Target = T;
+    fn deref(&self) -> &T{
+        match &self.guard{
+            ReadGuard::Shared(guard) => guard.downcast_ref::<T>(),
+            ReadGuard::MainThread(guard) => guard.downcast_ref::<T>(),
+        }.unwrap()
+    }
+}
+
+impl<'a, T: 'static> Deref for ResourceRefMut<'a, T>{
+    type Target = T;
+    fn deref(&self) -> &T{
+        match &self.guard{
+            WriteGuard::Shared(guard) => guard.downcast_ref::<T>(),
+            WriteGuard::MainThread(guard) => guard.downcast_ref::<T>(),
+        }.unwrap()
+    }
+}
+
+impl<'a, T: 'static> DerefMut for ResourceRefMut<'a, T>{
+    fn deref_mut(&mut self) -> &mut T{
+        match &mut self.guard{
+            WriteGuard::Shared(guard) => guard.downcast_mut::<T>(),
+            WriteGuard::MainThread(guard) => guard.downcast_mut::<T>(),
+        }.unwrap()
+    }
+}
+
+impl Resources{
+    pub fn new() -> Self{
+        Self{
+            shared: ThreadSafeResources { resources: HashMap::<TypeId, (&'static str, RwLock<Box<dyn Any + Send + Sync>>)>::new() },
+            main_thread: HashMap::<TypeId, (&'static str, RwLock<Box<dyn Any>>)>::new(),
+        }
+    }
+
+    // Returns the resource it replaced, if there was one of this type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, resource: T) -> Option<T>{
+        let old = self.remove::<T>();
+        self.shared.resources.insert(TypeId::of::<T>(), (std::any::type_name::<T>(), RwLock::new(Box::new(resource))));
+        old
+    }
+
+    // For resources that can't be sent to other threads. Only main_thread systems can fetch them
+    pub fn insert_main_thread<T: 'static>(&mut self, resource: T) -> Option<T>{
+        let old = self.remove::<T>();
+        self.main_thread.insert(TypeId::of::<T>(), (std::any::type_name::<T>(), RwLock::new(Box::new(resource))));
+        old
+    }
+
+    // A resource a system panicked while writing is still returned
+    pub fn remove<T: 'static>(&mut self) -> Option<T>{
+        if let Some((_, resource)) = self.shared.resources.remove(&TypeId::of::<T>()){
+            let resource = resource.into_inner().unwrap_or_else(|e| e.into_inner());
+            return Some(*resource.downcast::<T>().unwrap());
+        }
+        let (_, resource) = self.main_thread.remove(&TypeId::of::<T>())?;
+        let resource = resource.into_inner().unwrap_or_else(|e| e.into_inner());
+        Some(*resource.downcast::<T>().unwrap())
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool{
+        self.shared.resources.contains_key(&TypeId::of::<T>()) || self.main_thread.contains_key(&TypeId::of::<T>())
+    }
+
+    // The part that can be sent to other threads
+    pub fn thread_safe(&self) -> &ThreadSafeResources{
+        &self.shared
+    }
+
+    pub fn get<T: 'static>(&self) -> Result<ResourceRef<'_, T>, ResourceError>{
+        SystemResources::new(&self.shared, Some(self)).get::<T>()
+    }
+
+    pub fn get_mut<T: 'static>(&self) -> Result<ResourceRefMut<'_, T>, ResourceError>{
+        SystemResources::new(&self.shared, Some(self)).get_mut::<T>()
+    }
+}
+
+impl<'a> SystemResources<'a>{
+    // `main_thread` is the Resources `shared` came from, given only to systems running on the main thread
+    pub fn new(shared: &'a ThreadSafeResources, main_thread: Option<&'a Resources>) -> Self{
+        Self { shared, main_thread: main_thread.map(|resources| &resources.main_thread), declared: None }
+    }
+
+    // Panic on fetching a resource the system didn't declare in get_access. The schedule only keeps systems apart by
+    // what they declare, so an undeclared fetch can fail (Or block a system it runs next to) depending on timing.
+    // SystemManager does this in debug builds
+    pub fn with_declared(mut self, system: &'static str, access: &'a SystemAccess) -> Self{
+        self.declared = Some((system, access));
+        self
+    }
+
+    fn check_declared<T: 'static>(&self, write: bool){
+        if let Some((system, access)) = self.declared{
+            if write && !access.writes_resource::<T>(){
+                panic!("{} wrote resource {} without declaring write_resource::<{}>() in get_access", system, std::any::type_name::<T>(), std::any::type_name::<T>());
+            }
+            if !write && !access.reads_resource::<T>(){
+                panic!("{} read resource {} without declaring read_resource::<{}>() in get_access", system, std::any::type_name::<T>(), std::any::type_name::<T>());
+            }
+        }
+    }
+
+    // Whether main-thread resources can be fetched
+    pub fn is_main_thread(&self) -> bool{
+        self.main_thread.is_some()
+    }
+
+    fn find_main_thread<T: 'static>(&self) -> Result<&'a (&'static str, RwLock<Box<dyn Any>>), ResourceError>{
+        let name = std::any::type_name::<T>();
+        match self.main_thread{
+            Some(main_thread) => main_thread.get(&TypeId::of::<T>()).ok_or(ResourceError::Missing(name)),
+            None => Err(ResourceError::MainThreadOnly(name)),
+        }
+    }
+
+    pub fn get<T: 'static>(&self) -> Result<ResourceRef<'a, T>, ResourceError>{
+        self.check_declared::<T>(false);
+        if let Some((name, resource)) = self.shared.resources.get(&TypeId::of::<T>()){
+            return match resource.try_read(){
+                Ok(guard) => Ok(ResourceRef { guard: ReadGuard::Shared(guard), _marker: PhantomData }),
+                Err(_) => Err(ResourceError::Borrowed(name)),
+            };
+        }
+        let (name, resource) = self.find_main_thread::<T>()?;
+        match resource.try_read(){
+            Ok(guard) => Ok(ResourceRef { guard: ReadGuard::MainThread(guard), _marker: PhantomData }),
+            Err(_) => Err(ResourceError::Borrowed(name)),
+        }
+    }
+
+    // Takes &self so systems running in parallel can each get the resources they write
+    pub fn get_mut<T: 'static>(&self) -> Result<ResourceRefMut<'a, T>, ResourceError>{
+        self.check_declared::<T>(true);
+        if let Some((name, resource)) = self.shared.resources.get(&TypeId::of::<T>()){
+            return match resource.try_write(){
+                Ok(guard) => Ok(ResourceRefMut { guard: WriteGuard::Shared(guard), _marker: PhantomData }),
+                Err(_) => Err(ResourceError::Borrowed(name)),
+            };
+        }
+        let (name, resource) = self.find_main_thread::<T>()?;
+        match resource.try_write(){
+            Ok(guard) => Ok(ResourceRefMut { guard: WriteGuard::MainThread(guard), _marker: PhantomData }),
+            Err(_) => Err(ResourceError::Borrowed(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    struct Score(u32);
+    struct Lives(u32);
+    // Not Send or Sync, like Physics
+    struct Local(std::rc::Rc<u32>);
+
+    fn resources() -> Resources{
+        let mut resources = Resources::new();
+        resources.insert(Score(1));
+        resources.insert(Lives(3));
+        resources.insert_main_thread(Local(std::rc::Rc::new(5)));
+        resources
+    }
+
+    #[test]
+    fn declared_fetches_pass(){
+        let resources = resources();
+        let access = SystemAccess::new().write_resource::<Score>().read_resource::<Lives>();
+        let view = SystemResources::new(resources.thread_safe(), None).with_declared("Test", &access);
+        view.get_mut::<Score>().unwrap().0 += view.get::<Lives>().unwrap().0;
+        // A write covers reading
+        assert_eq!(view.get::<Score>().unwrap().0, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Test wrote resource")]
+    fn undeclared_write_panics(){
+        let resources = resources();
+        let access = SystemAccess::new().read_resource::<Score>();
+        let view = SystemResources::new(resources.thread_safe(), None).with_declared("Test", &access);
+        let _ = view.get_mut::<Score>();
+    }
+
+    #[test]
+    #[should_panic(expected = "Test read resource")]
+    fn undeclared_read_panics(){
+        let resources = resources();
+        let access = SystemAccess::new().write_resource::<Score>();
+        let view = SystemResources::new(resources.thread_safe(), None).with_declared("Test", &access);
+        let _ = view.get::<Lives>();
+    }
+
+    #[test]
+    fn main_thread_resources_stay_on_the_main_thread(){
+        let resources = resources();
+        assert!(matches!(SystemResources::new(resources.thread_safe(), None).get::<Local>(), Err(ResourceError::MainThreadOnly(_))));
+        assert_eq!(*SystemResources::new(resources.thread_safe(), Some(&resources)).get::<Local>().unwrap().0, 5);
+        assert_eq!(*resources.get::<Local>().unwrap().0, 5);
+        assert!(matches!(resources.get_mut::<u64>(), Err(ResourceError::Missing(_))));
+    }
+}
@@ -0,0 +1,198 @@
+use super::access::SystemAccess;
+use rayon::prelude::*;
+
+// Systems grouped into stages from their declared access. Stages run one after another, and the systems in a stage
+// run in parallel on the rayon pool, apart from main-thread systems. Doesn't know about the engine, so it can be built
+// and run with any system type
+#[derive(Debug, Clone)]
+pub struct Schedule{
+    // Indices into the system list the schedule was built from
+    stages: Vec<Vec<usize>>,
+    // Per system, whether it has to run on the thread that runs the schedule
+    main_thread: Vec<bool>,
+}
+
+impl Schedule{
+    // `systems` is every system's name and access, in the order they were added. Systems that conflict and have
+    // no ordering constraint between them run in the order they were added.
+    // Constraints naming one of the `external` systems (e.g. systems of another phase) are ignored
+    pub fn build(systems: &[(&str, &SystemAccess)], external: &[&str]) -> Result<Self, String>{
+        let count = systems.len();
+        for (i, (name, _)) in systems.iter().enumerate(){
+            if systems[..i].iter().any(|(other, _)| other == name){
+                return Err(format!("Two systems are named {:?}, system names have to be unique", name));
+            }
+        }
+        let find = |name: &str, by: &str| -> Result<Option<usize>, String>{
+            match systems.iter().position(|(other, _)| *other == name){
+                Some(v) => Ok(Some(v)),
+                None if external.contains(&name) => Ok(None),
+                None => Err(format!("{} is ordered against {:?}, but no system has that name", by, name)),
+            }
+        };
+
+        // predecessors[i] are the systems that have to be in an earlier stage than i
+        let mut predecessors = vec!(Vec::<usize>::new(); count);
+        for (i, (name, access)) in systems.iter().enumerate(){
+            for after in access.get_after().iter(){
+                if let Some(j) = find(after, name)?{
+                    predecessors[i].push(j);
+                }
+            }
+            for before in access.get_before().iter(){
+                if let Some(j) = find(before, name)?{
+                    predecessors[j].push(i);
+                }
+            }
+        }
+
+        // Topological order, always taking the earliest added system that's ready so the result is stable
+        let mut order = Vec::<usize>::new();
+        let mut placed = vec!(false; count);
+        while order.len() < count{
+            let next = (0..count).find(|i| !placed[*i] && predecessors[*i].iter().all(|p| placed[*p]));
+            match next{
+                Some(i) => {
+                    placed[i] = true;
+                    order.push(i);
+                },
+                None => {
+                    let stuck: Vec<&str> = (0..count).filter(|i| !placed[*i]).map(|i| systems[i].0).collect();
+                    return Err(format!("The ordering constraints between {} form a cycle", stuck.join(", ")));
+                },
+            }
+        }
+
+        // A system goes in the first stage after everything it's ordered after, and after every system it conflicts with
+        // that comes before it in the order
+        let mut stage_of = vec!(0usize; count);
+        for (position, i) in order.iter().enumerate(){
+            let mut stage = 0;
+            for p in predecessors[*i].iter(){
+                stage = stage.max(stage_of[*p] + 1);
+            }
+            for earlier in order[..position].iter(){
+                if systems[*i].1.conflicts_with(systems[*earlier].1).is_some(){
+                    stage = stage.max(stage_of[*earlier] + 1);
+                }
+            }
+            stage_of[*i] = stage;
+        }
+
+        let stage_count = stage_of.iter().max().map(|s| s + 1).unwrap_or(0);
+        let mut stages = vec!(Vec::<usize>::new(); stage_count);
+        for i in order.into_iter(){
+            stages[stage_of[i]].push(i);
+        }
+        let main_thread = systems.iter().map(|(_, access)| access.is_main_thread()).collect();
+        Ok(Self { stages, main_thread })
+    }
+
+    pub fn get_stages(&self) -> &Vec<Vec<usize>>{
+        &self.stages
+    }
+
+    // Run every system in one stage. The main-thread systems run one after another on the calling thread and are the only
+    // ones given `main`, which doesn't have to be thread safe. Then the others run in parallel, or on the calling thread if
+    // there's only one
+    pub fn run_stage<S: ?Sized + Send, C: Sync, M: ?Sized>(&self, stage: usize, systems: &mut [Box<S>], context: &C, main: &M, run: impl Fn(&mut S, &C, Option<&M>) + Sync){
+        let indices = &self.stages[stage];
+        let mut parallel = Vec::<&mut Box<S>>::new();
+        for (i, system) in systems.iter_mut().enumerate().filter(|(i, _)| indices.contains(i)){
+            if self.main_thread[i]{
+                run(&mut **system, context, Some(main));
+            }else{
+                parallel.push(system);
+            }
+        }
+        if parallel.len() == 1{
+            run(&mut **parallel[0], context, None);
+        }else{
+            parallel.into_par_iter().for_each(|system| run(&mut **system, context, None));
+        }
+    }
+
+    // Every stage, in order
+    pub fn run<S: ?Sized + Send, C: Sync, M: ?Sized>(&self, systems: &mut [Box<S>], context: &C, main: &M, run: impl Fn(&mut S, &C, Option<&M>) + Sync){
+        for stage in 0..self.stages.len(){
+            self.run_stage(stage, systems, context, main, &run);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::thread::ThreadId;
+
+    struct A;
+    struct B;
+    struct C;
+
+    // Stands in for a system, remembering where it ran
+    struct MockSystem{
+        ran_on: Option<ThreadId>,
+        got_main: bool,
+    }
+
+    fn stages(systems: &[(&str, &SystemAccess)]) -> Vec<Vec<usize>>{
+        Schedule::build(systems, &[]).unwrap().get_stages().clone()
+    }
+
+    #[test]
+    fn conflicting_access_lands_in_different_stages(){
+        let first = SystemAccess::new().write::<A>();
+        let second = SystemAccess::new().read::<A>().read::<B>();
+        let third = SystemAccess::new().write::<C>();
+        assert_eq!(stages(&[("First", &first), ("Second", &second), ("Third", &third)]), vec![vec![0, 2], vec![1]]);
+
+        // Conflicts chain, a system goes after every earlier system it conflicts with
+        let third = SystemAccess::new().write::<B>();
+        assert_eq!(stages(&[("First", &first), ("Second", &second), ("Third", &third)]), vec![vec![0], vec![1], vec![2]]);
+
+        let first = SystemAccess::new().write_resource::<C>();
+        let second = SystemAccess::new().write_resource::<C>();
+        assert_eq!(stages(&[("First", &first), ("Second", &second)]), vec![vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn disjoint_access_shares_a_stage(){
+        let first = SystemAccess::new().write::<A>().read_resource::<C>();
+        let second = SystemAccess::new().write::<B>().read_resource::<C>();
+        let third = SystemAccess::new().read::<C>();
+        assert_eq!(stages(&[("First", &first), ("Second", &second), ("Third", &third)]), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn ordering_constraints_are_kept(){
+        let first = SystemAccess::new().read::<A>().after("Second");
+        let second = SystemAccess::new().read::<A>();
+        assert_eq!(stages(&[("First", &first), ("Second", &second)]), vec![vec![1], vec![0]]);
+
+        let cycle = SystemAccess::new().after("First");
+        let first = SystemAccess::new().after("Cycle");
+        assert!(Schedule::build(&[("First", &first), ("Cycle", &cycle)], &[]).is_err());
+        assert!(Schedule::build(&[("First", &first)], &[]).is_err());
+        assert!(Schedule::build(&[("First", &first)], &["Cycle"]).is_ok());
+    }
+
+    #[test]
+    fn main_thread_systems_run_on_the_calling_thread(){
+        let parallel = SystemAccess::new().write::<A>();
+        let main = SystemAccess::new().write::<B>().main_thread();
+        let other = SystemAccess::new().write::<C>();
+        let schedule = Schedule::build(&[("Parallel", &parallel), ("Main", &main), ("Other", &other)], &[]).unwrap();
+        assert_eq!(schedule.get_stages().len(), 1);
+
+        let mut systems: Vec<Box<MockSystem>> = (0..3).map(|_| Box::new(MockSystem { ran_on: None, got_main: false })).collect();
+        schedule.run(&mut systems, &(), &(), |system, _, main| {
+            system.ran_on = Some(std::thread::current().id());
+            system.got_main = main.is_some();
+        });
+
+        let this_thread = std::thread::current().id();
+        assert!(systems.iter().all(|system| system.ran_on.is_some()));
+        assert_eq!(systems[1].ran_on, Some(this_thread));
+        assert_eq!(systems.iter().map(|system| system.got_main).collect::<Vec<_>>(), vec![false, true, false]);
+    }
+}
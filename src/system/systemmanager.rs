@@ -1,26 +1,120 @@
-use crate::{Entity, ComponentBase, Rc, Renderer, SystemBase, Physics, EntityManager, InputManager, Camera};
-use std::collections::HashMap;
-use rayon::prelude::*;
+use crate::{Renderer, SystemBase, Physics, EntityManager, CollisionEvents};
+use super::SystemContext;
+use super::access::SystemAccess;
+use super::schedule::Schedule;
+use super::resources::{Resources, SystemResources};
+use super::time::Time;
 
+// Each frame runs fixed_update as many times as the frame's time allows (Using Time::fixed_delta_time),
+// then update once. Physics and anything that pushes bodies around belongs in fixed_update
 pub struct SystemManager{
     systems: Vec<Box<dyn SystemBase>>,
-    pub delta_time: f32,
+    fixed_systems: Vec<Box<dyn SystemBase>>,
+    // Built the next time systems run after one is added
+    schedule: Option<Schedule>,
+    fixed_schedule: Option<Schedule>,
+    // Passed to every system. Starts out with Time and CollisionEvents, the game inserts the rest
+    pub resources: Resources,
 }
 
 impl SystemManager{
     pub fn new() -> Self{
         Self{
             systems: Vec::<Box<dyn SystemBase>>::new(),
-            delta_time: 0.0
+            fixed_systems: Vec::<Box<dyn SystemBase>>::new(),
+            schedule: None,
+            fixed_schedule: None,
+            resources: SystemManager::default_resources(),
         }
     }
+
+    fn default_resources() -> Resources{
+        let mut resources = Resources::new();
+        resources.insert(Time::new());
+        resources.insert(CollisionEvents::new());
+        resources
+    }
+
+    // Runs once per frame, in the update phase
     pub fn add_system(&mut self, system: Box<dyn SystemBase>){
         self.systems.push(system);
+        self.schedule = None;
+    }
+
+    // Runs once per fixed step, in the fixed_update phase
+    pub fn add_fixed_system(&mut self, system: Box<dyn SystemBase>){
+        self.fixed_systems.push(system);
+        self.fixed_schedule = None;
+    }
+
+    // Group the systems of one phase into stages. Ordering constraints against systems in the other phase are ignored,
+    // since fixed_update always runs before update
+    fn build_phase(phase: &str, systems: &Vec<Box<dyn SystemBase>>, other: &Vec<Box<dyn SystemBase>>) -> Result<Schedule, String>{
+        let access: Vec<SystemAccess> = systems.iter().map(|system| system.get_access()).collect();
+        let named: Vec<(&str, &SystemAccess)> = systems.iter().zip(access.iter()).map(|(system, access)| (system.get_name(), access)).collect();
+        let external: Vec<&str> = other.iter().map(|system| system.get_name()).collect();
+        let schedule = Schedule::build(&named, &external)?;
+
+        for (i, stage) in schedule.get_stages().iter().enumerate(){
+            let names: Vec<&str> = stage.iter().map(|s| systems[*s].get_name()).collect();
+            log::info!("{} stage {}: {}", phase, i, names.join(", "));
+        }
+        Ok(schedule)
+    }
+
+    // Fails if an ordering constraint names a missing system or constraints form a cycle
+    pub fn build_schedule(&mut self) -> Result<(), String>{
+        self.fixed_schedule = Some(SystemManager::build_phase("fixed_update", &self.fixed_systems, &self.systems)?);
+        self.schedule = Some(SystemManager::build_phase("update", &self.systems, &self.fixed_systems)?);
+        Ok(())
     }
 
-    pub fn update_systems(&mut self, renderer_reference: &Renderer, entity_manager: &mut EntityManager, input_manager: &InputManager, physics: &mut Physics, camera: &mut Camera){
-        for system in self.systems.iter_mut(){
-            system.execute(renderer_reference, entity_manager, input_manager, physics, self.delta_time, camera);
+    fn run_phase(schedule: &Schedule, systems: &mut Vec<Box<dyn SystemBase>>, resources: &Resources, renderer_reference: Option<&Renderer>, entity_manager: &mut EntityManager){
+        for stage in 0..schedule.get_stages().len(){
+            // Systems on the rayon pool only get what's thread safe. The renderer and main-thread resources stay on this thread
+            let shared = (&*entity_manager, resources.thread_safe());
+            schedule.run_stage(stage, systems, &shared, &(resources, renderer_reference), |system, (entity_manager, shared), main_thread| {
+                let mut resources = SystemResources::new(shared, main_thread.map(|(resources, _)| *resources));
+                // Debug builds check every resource fetch against the system's declared access
+                let access = if cfg!(debug_assertions) { Some(system.get_access()) } else { None };
+                if let Some(access) = access.as_ref(){
+                    resources = resources.with_declared(system.get_name(), access);
+                }
+                let context = SystemContext{
+                    renderer: main_thread.and_then(|(_, renderer)| *renderer),
+                    entity_manager,
+                    resources,
+                };
+                system.execute(&context);
+            });
+            // Entities a stage queued for despawning are gone before the next stage runs
+            if !entity_manager.get_despawn_queue().is_empty(){
+                let mut physics = resources.get_mut::<Physics>().expect("Can't despawn entities");
+                entity_manager.flush_despawn_queue(&mut physics);
+            }
+        }
+    }
+
+    // Call once per frame, after Time::advance. Pass no renderer to run without a GPU
+    pub fn update_systems(&mut self, renderer_reference: Option<&Renderer>, entity_manager: &mut EntityManager){
+        if self.schedule.is_none() || self.fixed_schedule.is_none(){
+            if let Err(e) = self.build_schedule(){
+                panic!("Couldn't schedule systems: {}", e);
+            }
+        }
+
+        // Last frame's collisions have been seen by the update systems
+        if let Ok(mut events) = self.resources.get_mut::<CollisionEvents>(){
+            events.begin_frame();
+        }
+
+        let mut steps = 0;
+        while self.resources.get_mut::<Time>().expect("Can't run fixed steps").consume_fixed_step(steps){
+            SystemManager::run_phase(self.fixed_schedule.as_ref().unwrap(), &mut self.fixed_systems, &self.resources, renderer_reference, entity_manager);
+            steps += 1;
         }
+        self.resources.get_mut::<Time>().unwrap().update_alpha();
+
+        SystemManager::run_phase(self.schedule.as_ref().unwrap(), &mut self.systems, &self.resources, renderer_reference, entity_manager);
     }
-}
\ No newline at end of file
+}
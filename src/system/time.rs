@@ -0,0 +1,140 @@
+// Simulation runs at this many fixed steps per second unless changed with set_tick_rate
+pub const DEFAULT_TICK_RATE: f32 = 60.0;
+// Most fixed steps run in one frame. After a long stall the rest of the backlog is dropped, so the game
+// slows down for a moment instead of spending every frame catching up
+pub const DEFAULT_MAX_FIXED_STEPS: u32 = 5;
+
+// Frame and fixed-step timing, kept in Resources so any system can read it.
+// Systems in fixed_update should use fixed_delta_time, systems in update should use delta_time
+pub struct Time{
+    // Seconds the last frame took
+    pub delta_time: f32,
+    // Seconds since the game started, added up from delta_time
+    pub elapsed: f64,
+    pub frame: u64,
+    // Length of one fixed step in seconds
+    pub fixed_delta_time: f32,
+    pub max_fixed_steps: u32,
+    // Fixed steps run since the game started
+    pub tick: u64,
+    // How far the frame is between the last fixed step and the next one, from 0 to 1. Transforms are drawn
+    // this far between their previous and current state
+    pub alpha: f32,
+    // Frame time that hasn't been simulated yet
+    accumulator: f32,
+}
+
+impl Time{
+    pub fn new() -> Self{
+        Self{
+            delta_time: 0.0,
+            elapsed: 0.0,
+            frame: 0,
+            fixed_delta_time: 1.0 / DEFAULT_TICK_RATE,
+            max_fixed_steps: DEFAULT_MAX_FIXED_STEPS,
+            tick: 0,
+            alpha: 0.0,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn set_tick_rate(&mut self, ticks_per_second: f32){
+        self.fixed_delta_time = 1.0 / ticks_per_second.max(1.0);
+    }
+
+    pub fn get_tick_rate(&self) -> f32{
+        1.0 / self.fixed_delta_time
+    }
+
+    // Call once per frame with how long the frame took
+    pub fn advance(&mut self, delta_time: f32){
+        self.delta_time = delta_time;
+        self.elapsed += delta_time as f64;
+        self.frame += 1;
+        self.accumulator += delta_time;
+    }
+
+    // True if another fixed step should run this frame, taking its time off the accumulator.
+    // `steps` is how many already ran this frame
+    pub fn consume_fixed_step(&mut self, steps: u32) -> bool{
+        if self.accumulator < self.fixed_delta_time{
+            return false;
+        }
+        if steps >= self.max_fixed_steps{
+            log::warn!("Simulation fell {:.3}s behind, skipping it", self.accumulator);
+            self.accumulator %= self.fixed_delta_time;
+            return false;
+        }
+        self.accumulator -= self.fixed_delta_time;
+        self.tick += 1;
+        true
+    }
+
+    // Call after the fixed steps of a frame
+    pub fn update_alpha(&mut self){
+        self.alpha = (self.accumulator / self.fixed_delta_time).min(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Runs a frame the way SystemManager does and returns how many fixed steps it took
+    fn frame(time: &mut Time, delta_time: f32) -> u32{
+        time.advance(delta_time);
+        let mut steps = 0;
+        while time.consume_fixed_step(steps){
+            steps += 1;
+        }
+        time.update_alpha();
+        steps
+    }
+
+    // A quarter of a second is exact in binary, so the accumulator can be compared exactly
+    fn quarter_second_steps() -> Time{
+        let mut time = Time::new();
+        time.set_tick_rate(4.0);
+        time
+    }
+
+    #[test]
+    fn long_frame_clamps_to_max_fixed_steps(){
+        let mut time = quarter_second_steps();
+        // A ten second stall is 40 steps behind
+        assert_eq!(frame(&mut time, 10.0), DEFAULT_MAX_FIXED_STEPS);
+        assert_eq!(time.tick, DEFAULT_MAX_FIXED_STEPS as u64);
+        // The rest of the backlog was dropped, so the next frame is back to normal
+        assert_eq!(frame(&mut time, 0.25), 1);
+        assert_eq!(time.alpha, 0.0);
+
+        time.max_fixed_steps = 2;
+        assert_eq!(frame(&mut time, 1.125), 2);
+        // The part of a step left over is kept
+        assert_eq!(time.alpha, 0.5);
+    }
+
+    #[test]
+    fn partial_step_sets_alpha(){
+        let mut time = quarter_second_steps();
+        assert_eq!(frame(&mut time, 0.375), 1);
+        assert_eq!(time.alpha, 0.5);
+        assert_eq!(frame(&mut time, 0.0625), 0);
+        assert_eq!(time.alpha, 0.75);
+        assert_eq!(frame(&mut time, 0.0625), 1);
+        assert_eq!(time.alpha, 0.0);
+    }
+
+    #[test]
+    fn short_frames_add_up_to_one_step(){
+        let mut time = quarter_second_steps();
+        for _ in 0..3{
+            assert_eq!(frame(&mut time, 0.0625), 0);
+        }
+        assert_eq!(time.tick, 0);
+        assert_eq!(frame(&mut time, 0.0625), 1);
+        assert_eq!(time.tick, 1);
+        assert_eq!(time.frame, 4);
+        assert_eq!(time.elapsed, 0.25);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{SystemBase, SystemContext, SystemAccess, Transform, Translation, Rotation, NonUniformScale, PhysicsComponent, EntityHandle};
+
+// Copies Translation, Rotation and NonUniformScale into the entity's Transform, so they can be changed
+// on their own. Entities only need the ones they use, the rest of the Transform is left alone.
+// Bodies own their Transform (See PhysicsSystem), so entities with a PhysicsComponent are skipped
+pub struct TransformCompositionSystem{
+
+}
+
+impl SystemBase for TransformCompositionSystem{
+    fn get_name(&self) -> &'static str{
+        "TransformCompositionSystem"
+    }
+
+    fn get_access(&self) -> SystemAccess{
+        SystemAccess::new()
+            .read::<Translation>()
+            .read::<Rotation>()
+            .read::<NonUniformScale>()
+            .read::<PhysicsComponent>()
+            .write::<Transform>()
+            .after("MovementSystem")
+            .after("PlayerMovementSystem")
+            .before("TransformPropagationSystem")
+    }
+
+    fn execute(&mut self, context: &SystemContext){
+        let entity_manager = context.entity_manager;
+
+        let mut handles = Vec::<EntityHandle>::new();
+        handles.extend(entity_manager.query::<&Translation>().iter().map(|(handle, _)| handle));
+        handles.extend(entity_manager.query::<&Rotation>().iter().map(|(handle, _)| handle));
+        handles.extend(entity_manager.query::<&NonUniformScale>().iter().map(|(handle, _)| handle));
+        handles.sort_by_key(|h| h.index);
+        handles.dedup();
+
+        for handle in handles.into_iter(){
+            if entity_manager.has_component::<PhysicsComponent>(handle){
+                continue;
+            }
+            let position = entity_manager.get_component::<Translation>(handle).map(|t| t.value);
+            let rotation = entity_manager.get_component::<Rotation>(handle).map(|r| r.value);
+            let scale = entity_manager.get_component::<NonUniformScale>(handle).map(|s| s.value);
+
+            let mut transform = match entity_manager.get_component_mut::<Transform>(handle){
+                Some(v) => v,
+                None => continue,
+            };
+            // Every step, even if nothing changed, so the entity isn't drawn sliding from an old position
+            transform.store_previous();
+            if let Some(position) = position{
+                transform.set_position(position);
+            }
+            if let Some(rotation) = rotation{
+                transform.set_rotation(rotation);
+            }
+            if let Some(scale) = scale{
+                transform.set_scale(scale);
+            }
+            // The matrix itself is rebuilt by TransformPropagationSystem, the setters mark the transform dirty
+        }
+    }
+}
+
+impl TransformCompositionSystem{
+    pub fn new() -> Self{
+        Self{
+
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{SystemBase, SystemContext, SystemAccess, Transform, Parent, Children, TransformHierarchy};
+
+// Brings every world matrix up to date once the fixed step has moved things, so gameplay code
+// reading Transform::get_world_matrix sees where attached entities really are
+pub struct TransformPropagationSystem{
+
+}
+
+impl SystemBase for TransformPropagationSystem{
+    fn get_name(&self) -> &'static str{
+        "TransformPropagationSystem"
+    }
+
+    fn get_access(&self) -> SystemAccess{
+        SystemAccess::new()
+            .write::<Transform>()
+            .read::<Parent>()
+            .read::<Children>()
+            .after("PhysicsSystem")
+            .after("MovementSystem")
+            .after("PlayerMovementSystem")
+    }
+
+    fn execute(&mut self, context: &SystemContext){
+        TransformHierarchy::propagate(context.entity_manager);
+    }
+}
+
+impl TransformPropagationSystem{
+    pub fn new() -> Self{
+        Self{
+
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{ComponentBase, EntityManager, EntityHandle, Transform};
+use crate::scene::ComponentRegistry;
+use cgmath::SquareMatrix;
+use std::any::Any;
+
+const PARENT_ID: u32 = 9;
+const CHILDREN_ID: u32 = 10;
+
+// The entity this one is attached to. The entity's Transform is then relative to the parent's.
+// Set with TransformHierarchy::set_parent so the parent's Children stay in sync
+pub struct Parent{
+    pub entity: EntityHandle,
+    id: u32,
+}
+
+impl ComponentBase for Parent{
+    fn get_id(&self) -> u32{
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl Parent{
+    pub fn new(entity: EntityHandle) -> Self{
+        Self{
+            entity,
+            id: PARENT_ID,
+        }
+    }
+
+    pub fn get_component_id() -> u32{
+        PARENT_ID
+    }
+
+    // Set up in code, not in scene files yet
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.reserve("Parent", PARENT_ID)
+    }
+}
+
+// The entities attached to this one, in the order they were attached
+pub struct Children{
+    pub entities: Vec<EntityHandle>,
+    id: u32,
+}
+
+impl ComponentBase for Children{
+    fn get_id(&self) -> u32{
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl Children{
+    pub fn new() -> Self{
+        Self{
+            entities: Vec::<EntityHandle>::new(),
+            id: CHILDREN_ID,
+        }
+    }
+
+    pub fn get_component_id() -> u32{
+        CHILDREN_ID
+    }
+
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.reserve("Children", CHILDREN_ID)
+    }
+}
+
+// Parent/child relations between entities and world matrices for them.
+// Destroying an entity destroys its children too (See EntityManager::destroy_entity)
+pub struct TransformHierarchy{
+
+}
+
+impl TransformHierarchy{
+    // Attach child to parent, detaching it from its old parent first. The child's transform is kept as it is,
+    // so it's now relative to the new parent
+    pub fn set_parent(entity_manager: &mut EntityManager, child: EntityHandle, parent: EntityHandle) -> Result<(), &'static str>{
+        entity_manager.find_entity(child)?;
+        entity_manager.find_entity(parent)?;
+        if TransformHierarchy::is_ancestor(entity_manager, child, parent){
+            return Err("Can't parent an entity to itself or one of its descendants");
+        }
+
+        TransformHierarchy::remove_parent(entity_manager, child);
+        if !entity_manager.has_component::<Children>(parent){
+            entity_manager.add_component(parent, Children::new())?;
+        }
+        entity_manager.get_component_mut::<Children>(parent).unwrap().entities.push(child);
+        entity_manager.add_component(child, Parent::new(parent))?;
+        TransformHierarchy::mark_dirty(entity_manager, child);
+        Ok(())
+    }
+
+    // Detach the entity from its parent, making it a root. Its transform is kept, so it's now in world space
+    pub fn remove_parent(entity_manager: &mut EntityManager, child: EntityHandle){
+        let parent = match entity_manager.remove_component::<Parent>(child){
+            Some(v) => v.entity,
+            None => return,
+        };
+        let empty = match entity_manager.get_component_mut::<Children>(parent){
+            Some(mut children) => {
+                children.entities.retain(|e| *e != child);
+                children.entities.is_empty()
+            },
+            None => false,
+        };
+        if empty{
+            entity_manager.remove_component::<Children>(parent);
+        }
+        TransformHierarchy::mark_dirty(entity_manager, child);
+    }
+
+    pub fn get_parent(entity_manager: &EntityManager, entity: EntityHandle) -> Option<EntityHandle>{
+        entity_manager.get_component::<Parent>(entity).map(|p| p.entity)
+    }
+
+    pub fn get_children(entity_manager: &EntityManager, entity: EntityHandle) -> Vec<EntityHandle>{
+        match entity_manager.get_component::<Children>(entity){
+            Some(children) => children.entities.clone(),
+            None => Vec::<EntityHandle>::new(),
+        }
+    }
+
+    // True if `ancestor` is `entity` or any entity above it
+    pub fn is_ancestor(entity_manager: &EntityManager, ancestor: EntityHandle, entity: EntityHandle) -> bool{
+        let mut current = Some(entity);
+        while let Some(handle) = current{
+            if handle == ancestor{
+                return true;
+            }
+            current = TransformHierarchy::get_parent(entity_manager, handle);
+        }
+        false
+    }
+
+    fn mark_dirty(entity_manager: &EntityManager, entity: EntityHandle){
+        if let Some(mut transform) = entity_manager.get_component_mut::<Transform>(entity){
+            transform.mark_dirty();
+        }
+    }
+
+    // Recalculate the world matrices that are out of date. Each walk starts at a dirty transform with no dirty
+    // ancestor and only goes down while transforms change, since a changed parent changes every child.
+    // A dirty transform under one that didn't change starts its own walk. Returns how many were recalculated
+    pub fn propagate(entity_manager: &EntityManager) -> usize{
+        let dirty: Vec<EntityHandle> = entity_manager.query::<&Transform>().iter()
+            .filter(|(_, transform)| transform.is_dirty())
+            .map(|(handle, _)| handle)
+            .collect();
+        let mut updated = 0;
+        for entity in dirty.into_iter(){
+            // Already recalculated by the walk of one of its ancestors
+            match entity_manager.get_component::<Transform>(entity){
+                Some(transform) if transform.is_dirty() => {},
+                _ => continue,
+            }
+            let (parent_world, parent_dirty) = TransformHierarchy::parent_world(entity_manager, entity);
+            // The ancestor's walk will get here
+            if parent_dirty{
+                continue;
+            }
+            TransformHierarchy::propagate_from(entity_manager, entity, parent_world, false, &mut updated);
+        }
+        updated
+    }
+
+    // The world matrix of the closest ancestor with a transform, and whether any ancestor is dirty
+    fn parent_world(entity_manager: &EntityManager, entity: EntityHandle) -> (Option<cgmath::Matrix4::<f32>>, bool){
+        let mut world = None;
+        let mut dirty = false;
+        let mut parent = TransformHierarchy::get_parent(entity_manager, entity);
+        while let Some(handle) = parent{
+            if let Some(transform) = entity_manager.get_component::<Transform>(handle){
+                if world.is_none(){
+                    world = Some(transform.get_world_matrix());
+                }
+                dirty |= transform.is_dirty();
+            }
+            parent = TransformHierarchy::get_parent(entity_manager, handle);
+        }
+        (world, dirty)
+    }
+
+    fn propagate_from(entity_manager: &EntityManager, entity: EntityHandle, parent_world: Option<cgmath::Matrix4::<f32>>, parent_changed: bool, updated: &mut usize){
+        // The transform is released before going down, since the children's transforms are in the same storage
+        let (world, changed) = match entity_manager.get_component_mut::<Transform>(entity){
+            Some(mut transform) => {
+                let changed = transform.update_world(parent_world, parent_changed);
+                if changed{
+                    *updated += 1;
+                }
+                (transform.get_world_matrix(), changed)
+            },
+            // Children without a transform pass their parent's matrix straight through
+            None => (parent_world.unwrap_or(cgmath::Matrix4::identity()), parent_changed),
+        };
+        // Nothing below can be out of date because of this one. Dirty transforms further down start their own walk
+        if !changed{
+            return;
+        }
+        for child in TransformHierarchy::get_children(entity_manager, entity).into_iter(){
+            TransformHierarchy::propagate_from(entity_manager, child, Some(world), true, updated);
+        }
+    }
+
+    // The world matrix the entity should be drawn with, blending every transform up the hierarchy
+    // between its previous and current fixed step (See Transform::interpolated_matrix)
+    pub fn interpolated_world_matrix(entity_manager: &EntityManager, entity: EntityHandle, alpha: f32) -> Option<cgmath::Matrix4::<f32>>{
+        let mut matrix = entity_manager.get_component::<Transform>(entity)?.interpolated_matrix(alpha);
+        let mut parent = TransformHierarchy::get_parent(entity_manager, entity);
+        while let Some(handle) = parent{
+            if let Some(transform) = entity_manager.get_component::<Transform>(handle){
+                matrix = transform.interpolated_matrix(alpha) * matrix;
+            }
+            parent = TransformHierarchy::get_parent(entity_manager, handle);
+        }
+        Some(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::Physics;
+    use cgmath::{Vector3, Point3, Quaternion, Rotation3, Deg, EuclideanSpace};
+
+    fn spawn(entity_manager: &mut EntityManager, position: Vector3::<f32>) -> EntityHandle{
+        let transform = Transform::new(position, Quaternion::from_angle_z(Deg(0.0)), Vector3::<f32> { x: 1.0, y: 1.0, z: 1.0 });
+        entity_manager.create_entity(vec![Box::new(transform)], Vec::new())
+    }
+
+    fn world_position(entity_manager: &EntityManager, entity: EntityHandle) -> Vector3::<f32>{
+        entity_manager.get_component::<Transform>(entity).unwrap().get_world_position()
+    }
+
+    fn assert_close(a: Vector3::<f32>, b: Vector3::<f32>){
+        assert!((a - b).x.abs() < 1e-5 && (a - b).y.abs() < 1e-5 && (a - b).z.abs() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn propagation_only_recalculates_what_changed(){
+        let mut entity_manager = EntityManager::new();
+        let root = spawn(&mut entity_manager, Vector3::<f32> { x: 10.0, y: 0.0, z: 0.0 });
+        let child = spawn(&mut entity_manager, Vector3::<f32> { x: 0.0, y: 2.0, z: 0.0 });
+        let grandchild = spawn(&mut entity_manager, Vector3::<f32> { x: 1.0, y: 0.0, z: 0.0 });
+        let other = spawn(&mut entity_manager, Vector3::<f32> { x: -3.0, y: 0.0, z: 0.0 });
+        TransformHierarchy::set_parent(&mut entity_manager, child, root).unwrap();
+        TransformHierarchy::set_parent(&mut entity_manager, grandchild, child).unwrap();
+
+        assert_eq!(TransformHierarchy::propagate(&entity_manager), 4);
+        assert_close(world_position(&entity_manager, grandchild), Vector3::<f32> { x: 11.0, y: 2.0, z: 0.0 });
+        assert_close(world_position(&entity_manager, other), Vector3::<f32> { x: -3.0, y: 0.0, z: 0.0 });
+        // Nothing changed
+        assert_eq!(TransformHierarchy::propagate(&entity_manager), 0);
+
+        // Moving the root moves everything under it, and only that
+        entity_manager.get_component_mut::<Transform>(root).unwrap().set_rotation(Quaternion::from_angle_z(Deg(90.0)));
+        assert_eq!(TransformHierarchy::propagate(&entity_manager), 3);
+        assert_close(world_position(&entity_manager, child), Vector3::<f32> { x: 8.0, y: 0.0, z: 0.0 });
+        assert_close(world_position(&entity_manager, grandchild), Vector3::<f32> { x: 8.0, y: 1.0, z: 0.0 });
+
+        // A change under an unchanged parent is still found
+        entity_manager.get_component_mut::<Transform>(grandchild).unwrap().set_position(Vector3::<f32> { x: 0.0, y: 0.0, z: 0.0 });
+        assert_eq!(TransformHierarchy::propagate(&entity_manager), 1);
+        assert_close(world_position(&entity_manager, grandchild), Vector3::<f32> { x: 8.0, y: 0.0, z: 0.0 });
+
+        // Setting the value it already has isn't a change
+        entity_manager.get_component_mut::<Transform>(child).unwrap().set_position(Vector3::<f32> { x: 0.0, y: 2.0, z: 0.0 });
+        assert_eq!(TransformHierarchy::propagate(&entity_manager), 0);
+
+        // Detaching keeps the local values, which are now in world space
+        TransformHierarchy::remove_parent(&mut entity_manager, child);
+        assert_eq!(TransformHierarchy::propagate(&entity_manager), 2);
+        assert_close(world_position(&entity_manager, grandchild), Vector3::<f32> { x: 0.0, y: 2.0, z: 0.0 });
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles(){
+        let mut entity_manager = EntityManager::new();
+        let mut physics = Physics::new();
+        let origin = Vector3::<f32> { x: 0.0, y: 0.0, z: 0.0 };
+        let (a, b, c) = (spawn(&mut entity_manager, origin), spawn(&mut entity_manager, origin), spawn(&mut entity_manager, origin));
+        TransformHierarchy::set_parent(&mut entity_manager, b, a).unwrap();
+        TransformHierarchy::set_parent(&mut entity_manager, c, b).unwrap();
+
+        assert!(TransformHierarchy::set_parent(&mut entity_manager, a, a).is_err());
+        assert!(TransformHierarchy::set_parent(&mut entity_manager, a, c).is_err());
+        assert!(TransformHierarchy::set_parent(&mut entity_manager, b, c).is_err());
+        // Nothing was changed by the rejected calls
+        assert_eq!(TransformHierarchy::get_parent(&entity_manager, a), None);
+        assert_eq!(TransformHierarchy::get_children(&entity_manager, b), vec!(c));
+        assert_eq!(TransformHierarchy::get_parent(&entity_manager, c), Some(b));
+
+        // Moving c up to a is fine, and leaves b with no children
+        TransformHierarchy::set_parent(&mut entity_manager, c, a).unwrap();
+        assert_eq!(TransformHierarchy::get_children(&entity_manager, a), vec!(b, c));
+        assert!(TransformHierarchy::get_children(&entity_manager, b).is_empty());
+
+        let gone = spawn(&mut entity_manager, origin);
+        entity_manager.destroy_entity(gone, &mut physics).unwrap();
+        assert!(TransformHierarchy::set_parent(&mut entity_manager, c, gone).is_err());
+    }
+
+    #[test]
+    fn local_and_world_points_round_trip(){
+        let mut entity_manager = EntityManager::new();
+        let parent = spawn(&mut entity_manager, Vector3::<f32> { x: 5.0, y: 0.0, z: 0.0 });
+        let child = entity_manager.create_entity(vec![Box::new(Transform::new(
+            Vector3::<f32> { x: 0.0, y: 1.0, z: 0.0 },
+            Quaternion::from_angle_z(Deg(90.0)),
+            Vector3::<f32> { x: 2.0, y: 2.0, z: 1.0 },
+        ))], Vec::new());
+        TransformHierarchy::set_parent(&mut entity_manager, child, parent).unwrap();
+        TransformHierarchy::propagate(&entity_manager);
+
+        let transform = entity_manager.get_component::<Transform>(child).unwrap();
+        // One unit along the child's x is two world units along y, after the scale and the turn
+        let world = transform.local_to_world(Point3::<f32> { x: 1.0, y: 0.0, z: 0.0 });
+        assert_close(world.to_vec(), Vector3::<f32> { x: 5.0, y: 3.0, z: 0.0 });
+        let local = transform.world_to_local(world);
+        assert_close(local.to_vec(), Vector3::<f32> { x: 1.0, y: 0.0, z: 0.0 });
+        assert_close(transform.world_to_local(Point3::<f32> { x: 5.0, y: 1.0, z: 0.0 }).to_vec(), Vector3::<f32> { x: 0.0, y: 0.0, z: 0.0 });
+    }
+}
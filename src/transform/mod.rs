@@ -5,4 +5,6 @@ pub use rotation::{Rotation};
 pub mod non_uniform;
 pub use non_uniform::{NonUniformScale};
 pub mod transform;
-pub use transform::{Transform, TransformUniform};
+pub use transform::{Transform};
+pub mod hierarchy;
+pub use hierarchy::{Parent, Children, TransformHierarchy};
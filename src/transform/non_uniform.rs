@@ -1,10 +1,10 @@
-use crate::{ComponentBase, UniformBuffer, Renderer};
-use wgpu::util::DeviceExt;
-use cgmath::{InnerSpace, SquareMatrix};
+use crate::ComponentBase;
+use crate::scene::ComponentRegistry;
 use std::any::Any;
 
 const ID: u32 = 3;
 
+// Sets the scale of the entity's Transform every fixed step (See TransformCompositionSystem)
 pub struct NonUniformScale{
     pub value: cgmath::Vector3::<f32>,
     id: u32,
@@ -20,11 +20,13 @@ impl ComponentBase for NonUniformScale{
     fn as_any_mut(&mut self) -> &mut Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 
 impl NonUniformScale{
     pub fn new(value: cgmath::Vector3::<f32>) -> Self{
-        log::warn!("Make sure to add this data to a Transform component");
         Self{
             value,
             id: ID
@@ -33,6 +35,11 @@ impl NonUniformScale{
     pub fn get_component_id() -> u32{
         ID
     }
+
+    // Not a scene component yet, but the id still has to be unique
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.reserve("NonUniformScale", ID)
+    }
     pub fn set_scale(&mut self, value: cgmath::Vector3::<f32>){
         self.value = value;
     }
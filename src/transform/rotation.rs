@@ -1,11 +1,12 @@
-use crate::{ComponentBase, UniformBuffer, Renderer};
-use wgpu::util::DeviceExt;
-use cgmath::{InnerSpace, SquareMatrix, VectorSpace};
+use crate::ComponentBase;
+use cgmath::{InnerSpace, Rotation3};
 use cgmath::Rotation as rotation;
+use crate::scene::ComponentRegistry;
 use std::any::Any;
 
 const ID: u32 = 2;
 
+// Sets the rotation of the entity's Transform every fixed step (See TransformCompositionSystem)
 pub struct Rotation{
     pub value: cgmath::Quaternion::<f32>,
     id: u32,
@@ -21,11 +22,13 @@ impl ComponentBase for Rotation{
     fn as_any_mut(&mut self) -> &mut Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 
 impl Rotation{
     pub fn new(value: cgmath::Quaternion::<f32>) -> Self{
-        log::warn!("Make sure to add this data to a Transform component");
         Self{
             value,
             id: ID
@@ -34,15 +37,135 @@ impl Rotation{
     pub fn get_component_id() -> u32{
         ID
     }
-    pub fn rotate_vector(&mut self, value: cgmath::Vector3::<f32>){
-        self.value.rotate_vector(value);
+
+    // Not a scene component yet, but the id still has to be unique
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.reserve("Rotation", ID)
+    }
+
+    // Apply `by` on top of the current rotation, in world space
+    pub fn rotate(&mut self, by: cgmath::Quaternion::<f32>){
+        self.value = (by * self.value).normalize();
+    }
+
+    // Spin around the Z axis, which is the only rotation a 2D body has
+    pub fn rotate_z<A: Into<cgmath::Rad<f32>>>(&mut self, angle: A){
+        self.rotate(cgmath::Quaternion::from_angle_z(angle));
     }
 
-    pub fn rotate_point(&mut self, value: cgmath::Point3::<f32>){
-        self.value.rotate_point(value);
+    // The vector rotated by this rotation. Doesn't change the rotation
+    pub fn rotate_vector(&self, value: cgmath::Vector3::<f32>) -> cgmath::Vector3::<f32>{
+        self.value.rotate_vector(value)
     }
 
+    // The point rotated around the origin by this rotation. Doesn't change the rotation
+    pub fn rotate_point(&self, value: cgmath::Point3::<f32>) -> cgmath::Point3::<f32>{
+        self.value.rotate_point(value)
+    }
+
+    // Move `t` of the way towards `to` (0 - 1), taking the shortest way round. Cheaper than slerp,
+    // but the speed isn't constant, so it's best for small steps
     pub fn lerp(&mut self, to: cgmath::Quaternion::<f32>, t: f32){
-        self.value.lerp(to, t);
+        self.value = self.value.nlerp(to, t);
+    }
+
+    // Move `t` of the way towards `to` (0 - 1) at a constant angular speed, taking the shortest way round
+    pub fn slerp(&mut self, to: cgmath::Quaternion::<f32>, t: f32){
+        self.value = self.value.slerp(to, t);
+    }
+
+    // Turn so the local +Z axis points along `direction`, with +Y as close to `up` as it can be.
+    // Does nothing if `direction` is zero
+    pub fn look_at(&mut self, direction: cgmath::Vector3::<f32>, up: cgmath::Vector3::<f32>){
+        if direction.magnitude2() == 0.0{
+            return;
+        }
+        // Looking straight up (or down) leaves no way to tell which way is up, so take the shortest turn there instead
+        if up.cross(direction).magnitude2() <= f32::EPSILON * direction.magnitude2() * up.magnitude2(){
+            self.value = cgmath::Quaternion::from_arc(cgmath::Vector3::unit_z(), direction.normalize(), None);
+            return;
+        }
+        // look_at gives the rotation that turns `direction` onto +Z, which is the opposite of what we want
+        self.value = cgmath::Quaternion::look_at(direction, up).invert();
+    }
+
+    // Turn around Z so the local +X axis points along `direction`, for sprites. Does nothing if `direction` is zero
+    pub fn look_at_2d(&mut self, direction: cgmath::Vector2::<f32>){
+        if direction.magnitude2() == 0.0{
+            return;
+        }
+        self.value = cgmath::Quaternion::from_angle_z(cgmath::Rad(direction.y.atan2(direction.x)));
+    }
+
+    // The angle around Z, the inverse of look_at_2d
+    pub fn get_angle_z(&self) -> cgmath::Rad::<f32>{
+        let x_axis = self.value.rotate_vector(cgmath::Vector3::unit_x());
+        cgmath::Rad(x_axis.y.atan2(x_axis.x))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use cgmath::{Deg, Vector3, Quaternion};
+
+    fn close(a: Vector3::<f32>, b: Vector3::<f32>){
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn identity() -> Rotation{
+        Rotation::new(Quaternion::new(1.0, 0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn rotate_90_degrees_about_z(){
+        let mut rotation = identity();
+        rotation.rotate_z(Deg(90.0));
+        close(rotation.rotate_vector(Vector3::unit_x()), Vector3::unit_y());
+        close(rotation.rotate_vector(Vector3::unit_y()), -Vector3::unit_x());
+        close(rotation.rotate_vector(Vector3::unit_z()), Vector3::unit_z());
+        assert!((rotation.get_angle_z().0 - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+
+        // Rotations stack on top of each other
+        rotation.rotate_z(Deg(90.0));
+        close(rotation.rotate_vector(Vector3::unit_x()), -Vector3::unit_x());
+    }
+
+    #[test]
+    fn slerp_ends_and_middle(){
+        let to = Quaternion::from_angle_z(Deg(90.0));
+        for (t, angle) in [(0.0, 0.0), (0.5, 45.0), (1.0, 90.0)].iter(){
+            let mut rotation = identity();
+            rotation.slerp(to, *t);
+            let expected = Quaternion::from_angle_z(Deg(*angle)).rotate_vector(Vector3::unit_x());
+            close(rotation.rotate_vector(Vector3::unit_x()), expected);
+        }
+    }
+
+    #[test]
+    fn look_at_along_the_axes(){
+        let directions = [Vector3::unit_x(), -Vector3::unit_x(), Vector3::unit_z(), -Vector3::unit_z(), Vector3::unit_y(), -Vector3::unit_y()];
+        for direction in directions.iter(){
+            let mut rotation = identity();
+            rotation.look_at(*direction * 3.0, Vector3::unit_y());
+            close(rotation.rotate_vector(Vector3::unit_z()), *direction);
+        }
+
+        // Up is kept where it can be
+        let mut rotation = identity();
+        rotation.look_at(Vector3::unit_x(), Vector3::unit_y());
+        close(rotation.rotate_vector(Vector3::unit_y()), Vector3::unit_y());
+
+        // A zero direction leaves the rotation alone
+        rotation.look_at(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_y());
+        close(rotation.rotate_vector(Vector3::unit_z()), Vector3::unit_x());
+    }
+
+    #[test]
+    fn look_at_2d_turns_x_onto_the_direction(){
+        let mut rotation = identity();
+        rotation.look_at_2d(cgmath::Vector2::new(0.0, -2.0));
+        close(rotation.rotate_vector(Vector3::unit_x()), -Vector3::unit_y());
+        assert!((rotation.get_angle_z().0 + std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+    }
+}
@@ -1,25 +1,25 @@
-use crate::{ComponentBase, UniformBuffer, Renderer, UniformUtils, Rc, RefCell};
-use wgpu::util::DeviceExt;
-use cgmath::{InnerSpace, SquareMatrix};
+use crate::ComponentBase;
+use cgmath::{VectorSpace, SquareMatrix, Transform as _};
+use crate::scene::ComponentRegistry;
 use std::any::Any;
 
-#[rustfmt::skip]
-pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.0,
-    0.0, 0.0, 0.5, 1.0,
-);
-
 const ID: u32 = 4;
 
+// Position, rotation and scale relative to the entity's Parent, or to the world if it has none.
+// They're changed through the setters, which mark the transform dirty so the next propagation picks it up
 pub struct Transform{
-    pub position: cgmath::Vector3::<f32>,
-    pub rotation: cgmath::Quaternion::<f32>,
-    pub scale: cgmath::Vector3::<f32>,
+    position: cgmath::Vector3::<f32>,
+    rotation: cgmath::Quaternion::<f32>,
+    scale: cgmath::Vector3::<f32>,
+    // Local matrix, as of the last generate_matrix or propagation
     pub value: cgmath::Matrix4::<f32>,
-    buffer: wgpu::Buffer,
-    uniform: TransformUniform,
+    // Local matrix with every parent's applied, kept up to date by TransformHierarchy::propagate
+    world: cgmath::Matrix4::<f32>,
+    // Set when the local values change, cleared when the world matrix is recalculated
+    dirty: bool,
+    // Where the transform was at the previous fixed step, so it can be drawn part way towards the current one
+    pub previous_position: cgmath::Vector3::<f32>,
+    pub previous_rotation: cgmath::Quaternion::<f32>,
     id: u32
 }
 impl ComponentBase for Transform{
@@ -29,21 +29,25 @@ impl ComponentBase for Transform{
     fn as_any(&self) -> &dyn Any {
         self
     }
-    fn as_any_mut(&mut self) -> &mut Any {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
         self
     }
 }
 impl Transform{
-    pub fn new(renderer_reference: &Renderer, position: cgmath::Vector3::<f32>, rotation: cgmath::Quaternion::<f32>, scale: cgmath::Vector3::<f32>) -> Self{
+    pub fn new(position: cgmath::Vector3::<f32>, rotation: cgmath::Quaternion::<f32>, scale: cgmath::Vector3::<f32>) -> Self{
         let value = cgmath::Matrix4::from_translation(position) * cgmath::Matrix4::from(rotation) * cgmath::Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
-        let uniform = TransformUniform::new();
         Self{
             position,
             rotation,
             scale,
             value,
-            buffer: UniformUtils::generate_empty_buffer(renderer_reference),
-            uniform,
+            world: value,
+            dirty: true,
+            previous_position: position,
+            previous_rotation: rotation,
             id: ID
         }
     }
@@ -51,63 +55,118 @@ impl Transform{
         ID
     }
 
-    pub fn create_uniforms(&mut self, renderer_reference: &Renderer) -> (wgpu::BindGroup, wgpu::BindGroupLayout, &TransformUniform){
-        self.uniform.update(OPENGL_TO_WGPU_MATRIX * self.value);
-        let buffer = self.uniform.create_uniform_buffer(renderer_reference);
-        let layout = UniformUtils::create_bind_group_layout(renderer_reference, 0, wgpu::ShaderStage::VERTEX, Some("Transform"));
-        self.buffer = buffer;
-        (UniformUtils::create_bind_group(&renderer_reference, &self.buffer, &layout, 0, Some("Transform")), layout, &self.uniform)
+    // Built from pos/rot/scale by the loader, so it has no keyword of its own
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.reserve("Transform", ID)
     }
 
-    pub fn get_buffer_reference(&self) -> &wgpu::Buffer{
-        &self.buffer
+    // Recalculate value from position, rotation and scale
+    pub fn generate_matrix(&mut self) -> cgmath::Matrix4::<f32>{
+        self.value = self.get_matrix();
+        self.value
     }
 
-    pub fn generate_matrix(&mut self) -> cgmath::Matrix4::<f32>{
-        self.value = cgmath::Matrix4::from_translation(self.position) * 
-        cgmath::Matrix4::from(self.rotation) * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z);
+    // The model matrix for the current position, rotation and scale
+    pub fn get_matrix(&self) -> cgmath::Matrix4::<f32>{
+        cgmath::Matrix4::from_translation(self.position) *
+        cgmath::Matrix4::from(self.rotation) * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
 
-        OPENGL_TO_WGPU_MATRIX * self.value
+    pub fn get_position(&self) -> cgmath::Vector3::<f32>{
+        self.position
     }
 
-    pub fn get_uniform(&self) -> Rc<RefCell<TransformUniform>>{
-        Rc::new(RefCell::new(self.uniform))
+    pub fn get_rotation(&self) -> cgmath::Quaternion::<f32>{
+        self.rotation
     }
 
-    pub fn update_uniform_buffers(&mut self, renderer_reference: &Renderer){
-        let matrix = self.generate_matrix();
-        self.uniform.update(matrix);
-        renderer_reference.write_buffer(self.get_buffer_reference(), 0, &[self.uniform]);
+    pub fn get_scale(&self) -> cgmath::Vector3::<f32>{
+        self.scale
     }
-}
 
-// We need this for Rust to store our data correctly for the shaders
-#[repr(C)]
-// This is so we can store this in a buffer
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct TransformUniform{
-    transform: [[f32; 4]; 4] // Store our rotation as a 4x4 matrix
-}
-impl TransformUniform{
-    pub fn new() -> Self{
-        Self{
-            transform: cgmath::Matrix4::identity().into()
+    // Setting the value it already has doesn't mark the transform dirty, so resting bodies don't redo their children
+    pub fn set_position(&mut self, position: cgmath::Vector3::<f32>){
+        if self.position != position{
+            self.position = position;
+            self.dirty = true;
         }
     }
 
-    pub fn update(&mut self, value: cgmath::Matrix4::<f32>){
-        self.transform = value.into();
+    pub fn set_rotation(&mut self, rotation: cgmath::Quaternion::<f32>){
+        if self.rotation != rotation{
+            self.rotation = rotation;
+            self.dirty = true;
+        }
     }
 
-    pub fn create_uniform_buffer(&self, renderer_reference: &Renderer) -> wgpu::Buffer{
-        renderer_reference.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Rotation Uniform Buffer"),
-                contents: bytemuck::cast_slice(&[*self]),
-                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            }
-        )
+    pub fn set_scale(&mut self, scale: cgmath::Vector3::<f32>){
+        if self.scale != scale{
+            self.scale = scale;
+            self.dirty = true;
+        }
+    }
+
+    pub fn mark_dirty(&mut self){
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool{
+        self.dirty
+    }
+
+    // Recalculate the world matrix if the transform is dirty or its parent's world matrix changed.
+    // Returns true if it was recalculated
+    pub fn update_world(&mut self, parent_world: Option<cgmath::Matrix4::<f32>>, parent_changed: bool) -> bool{
+        if !self.dirty && !parent_changed{
+            return false;
+        }
+        let local = self.get_matrix();
+        self.value = local;
+        self.world = match parent_world{
+            Some(parent) => parent * local,
+            None => local,
+        };
+        self.dirty = false;
+        true
     }
-}
 
-impl UniformBuffer for TransformUniform{}
\ No newline at end of file
+    // As of the last propagation
+    pub fn get_world_matrix(&self) -> cgmath::Matrix4::<f32>{
+        self.world
+    }
+
+    pub fn get_world_position(&self) -> cgmath::Vector3::<f32>{
+        self.world.w.truncate()
+    }
+
+    // A point relative to this transform, in world space
+    pub fn local_to_world(&self, point: cgmath::Point3::<f32>) -> cgmath::Point3::<f32>{
+        self.world.transform_point(point)
+    }
+
+    // A world space point, relative to this transform. Returns the point unchanged if the transform has a zero scale
+    pub fn world_to_local(&self, point: cgmath::Point3::<f32>) -> cgmath::Point3::<f32>{
+        match self.world.invert(){
+            Some(inverse) => inverse.transform_point(point),
+            None => point,
+        }
+    }
+
+    // Call before moving the transform in a fixed step. Also call after teleporting it, so it doesn't get drawn sliding there
+    pub fn store_previous(&mut self){
+        self.previous_position = self.position;
+        self.previous_rotation = self.rotation;
+    }
+
+    // The model matrix blended between the previous fixed step (alpha 0) and the current one (alpha 1)
+    pub fn interpolated_matrix(&self, alpha: f32) -> cgmath::Matrix4::<f32>{
+        let position = self.interpolated_position(alpha);
+        let rotation = self.previous_rotation.slerp(self.rotation, alpha);
+        cgmath::Matrix4::from_translation(position) *
+        cgmath::Matrix4::from(rotation) * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
+    pub fn interpolated_position(&self, alpha: f32) -> cgmath::Vector3::<f32>{
+        self.previous_position.lerp(self.position, alpha)
+    }
+}
@@ -1,10 +1,11 @@
-use crate::{ComponentBase, UniformBuffer, Renderer};
-use wgpu::util::DeviceExt;
-use cgmath::{InnerSpace, SquareMatrix};
+use crate::ComponentBase;
+use cgmath::InnerSpace;
+use crate::scene::ComponentRegistry;
 use std::any::Any;
 
 const ID: u32 = 1;
 
+// Sets the position of the entity's Transform every fixed step (See TransformCompositionSystem)
 pub struct Translation{
     pub value: cgmath::Vector3::<f32>,
     id: u32,
@@ -20,11 +21,13 @@ impl ComponentBase for Translation{
     fn as_any_mut(&mut self) -> &mut Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 
 impl Translation{
     pub fn new(value: cgmath::Vector3::<f32>) -> Self{
-        log::warn!("Make sure to add this data to a Transform component");
         Self{
             value,
             id: ID
@@ -33,11 +36,16 @@ impl Translation{
     pub fn get_component_id() -> u32{
         ID
     }
+
+    // Not a scene component yet, but the id still has to be unique
+    pub fn register(registry: &mut ComponentRegistry) -> Result<(), String>{
+        registry.reserve("Translation", ID)
+    }
     pub fn translate(&mut self, value: cgmath::Vector3::<f32>){
         self.value += value;
     }
 
-    pub fn magnitude(&mut self) -> f32{
+    pub fn magnitude(&self) -> f32{
         self.value.magnitude()
     }
 }